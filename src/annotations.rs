@@ -0,0 +1,182 @@
+//! Labelled boxes on a capture, kept in a JSON file beside it.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::args::command_line;
+use crate::axis::{format_seconds, format_si};
+use crate::{Operation, Samples};
+
+/// How much wider than the annotation the output band is, as in `signals`.
+const GUARD: f64 = 1.25;
+
+/// A time and frequency range of a capture, and some notes about it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Annotation {
+    /// Seconds from the start of the capture.
+    pub start: f64,
+    pub end: f64,
+
+    /// Hz, relative to the centre of the capture.
+    pub low: f64,
+    pub high: f64,
+
+    pub label: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub comment: String,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct Sidecar {
+    annotations: Vec<Annotation>,
+}
+
+impl Annotation {
+    /// The slice, shift and lowpass which cut this out, at the lowest reasonable rate.
+    pub fn operations(&self, sample_rate: u64) -> Vec<Operation> {
+        cut(self.start, self.end, self.low, self.high, sample_rate)
+    }
+}
+
+/// The slice, shift and lowpass which cut out a box, at the lowest reasonable rate.
+pub fn cut(start: f64, end: f64, low: f64, high: f64, sample_rate: u64) -> Vec<Operation> {
+    let micros = |seconds: f64| (seconds * 1e6).round() / 1e6;
+    let centre = (low + high) / 2.;
+    let cutoff = ((high - low) / 2.).ceil().max(1.);
+    let decimate = ((sample_rate as f64 / (cutoff * 2. * GUARD)) as u64).max(1);
+
+    vec![
+        Operation::Slice {
+            start: Some(micros(start.max(0.))),
+            end: Some(micros(end)),
+        },
+        Operation::Shift {
+            frequency: -centre.round() as i64,
+        },
+        Operation::LowPass {
+            // sharper filters for narrower bands, within reason
+            size: 2 * (decimate * 5).clamp(20, 500) as usize,
+            decimate,
+            frequency: cutoff as u64,
+        },
+    ]
+}
+
+/// Where the annotations for a capture live: `capture.cu8` has `capture.cu8.annotations.json`.
+pub fn sidecar_path(capture: &Path) -> PathBuf {
+    let mut name = capture.as_os_str().to_owned();
+    name.push(".annotations.json");
+    PathBuf::from(name)
+}
+
+/// The annotations for a capture, which are empty if nobody has written any.
+pub fn load(capture: &Path) -> Result<Vec<Annotation>> {
+    let path = sidecar_path(capture);
+    let json = match fs::read_to_string(&path) {
+        Ok(json) => json,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).with_context(|| anyhow!("reading {:?}", path)),
+    };
+    let sidecar: Sidecar =
+        serde_json::from_str(&json).with_context(|| anyhow!("parsing {:?}", path))?;
+    Ok(sidecar.annotations)
+}
+
+/// Replace the annotations for a capture; removing the sidecar if there are none.
+pub fn save(capture: &Path, annotations: &[Annotation]) -> Result<()> {
+    let path = sidecar_path(capture);
+    if annotations.is_empty() {
+        return match fs::remove_file(&path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                Err(e).with_context(|| anyhow!("removing {:?}", path))
+            }
+            _ => Ok(()),
+        };
+    }
+
+    let json = serde_json::to_string_pretty(&Sidecar {
+        annotations: annotations.to_vec(),
+    })?;
+    fs::write(&path, json + "\n").with_context(|| anyhow!("writing {:?}", path))
+}
+
+/// The only annotation on a capture with this label.
+pub fn find(capture: &Path, label: &str) -> Result<Annotation> {
+    let annotations = load(capture)?;
+    let mut matching = annotations.iter().filter(|a| a.label == label);
+    match (matching.next(), matching.next()) {
+        (Some(annotation), None) => Ok(annotation.clone()),
+        (Some(_), Some(_)) => bail!("more than one annotation is labelled {:?}", label),
+        (None, _) => bail!(
+            "no annotation labelled {:?} in {:?}; there are: {:?}",
+            label,
+            sidecar_path(capture),
+            annotations.iter().map(|a| &a.label).collect::<Vec<_>>()
+        ),
+    }
+}
+
+pub fn print_annotations(samples: &dyn Samples, annotations: &[Annotation]) {
+    let centre = samples.centre_frequency().unwrap_or(0) as f64;
+    println!(
+        "{:<16} {:>10} {:>10} {:>12} {:>12}  pipeline",
+        "label", "start", "end", "low", "high"
+    );
+    for annotation in annotations {
+        let pipeline = annotation
+            .operations(samples.sample_rate())
+            .iter()
+            .filter_map(command_line)
+            .collect::<Vec<_>>()
+            .join(" ");
+        println!(
+            "{:<16} {:>10} {:>10} {:>12} {:>12}  {}",
+            annotation.label,
+            format_seconds(annotation.start, 0.001),
+            format_seconds(annotation.end, 0.001),
+            format_si(annotation.low + centre),
+            format_si(annotation.high + centre),
+            pipeline
+        );
+        if !annotation.comment.is_empty() {
+            println!("    {}", annotation.comment.replace('\n', "\n    "));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find, load, save, sidecar_path, Annotation};
+
+    #[test]
+    fn sidecar() {
+        let dir = std::env::temp_dir().join(format!("quadrs-annotations-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let capture = dir.join("fsk.sr2M.cu8");
+        assert_eq!(
+            dir.join("fsk.sr2M.cu8.annotations.json"),
+            sidecar_path(&capture)
+        );
+
+        assert!(load(&capture).unwrap().is_empty());
+        let preamble = Annotation {
+            start: 0.5,
+            end: 0.75,
+            low: -20_000.,
+            high: 20_000.,
+            label: "preamble".to_string(),
+            comment: String::new(),
+        };
+        save(&capture, std::slice::from_ref(&preamble)).unwrap();
+        assert_eq!(preamble, find(&capture, "preamble").unwrap());
+        assert!(find(&capture, "payload").is_err());
+
+        save(&capture, &[]).unwrap();
+        assert!(!sidecar_path(&capture).exists());
+        std::fs::remove_dir(&dir).unwrap();
+    }
+}
@@ -1,3 +1,12 @@
+use crate::annotations;
+use crate::axis::{FrequencyReference, TimeUnit};
+use crate::colormap::Colormap;
+use crate::detect::{Bursts, Signals};
+use crate::fft::SparkFft;
+use crate::ffts::{Scale, Windowing};
+use crate::psd::{Psd, PsdFormat};
+use crate::render::Png;
+use crate::tui::Tui;
 use crate::{FileDetails, FileFormat, Operation};
 use anyhow::anyhow;
 use anyhow::bail;
@@ -8,37 +17,51 @@ use regex::Regex;
 use std::collections::HashMap;
 use std::convert::TryFrom;
 use std::iter::Peekable;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 pub enum Command {
     Octagon(Operation),
-    Ui,
     Eui { filename: Option<PathBuf> },
+    Tui(Tui),
 }
 
 pub fn parse<'a, I: Iterator<Item = &'a String>>(args: I) -> Result<Vec<Command>> {
     let mut matched = vec![];
     let mut args = args.peekable();
 
+    // the most recent `from`, whose annotations we can refer to
+    let mut capture: Option<PathBuf> = None;
+
     while let Some(cmd) = args.next() {
         let map =
             read_just_args(&mut args).with_context(|| anyhow!("finding args for {:?}", cmd))?;
 
-        matched.push(
-            match cmd.as_str() {
-                "from" => parse_from(&mut args, no_duplicates(map)?),
-                "shift" => parse_shift(&mut args, no_duplicates(map)?),
-                "lowpass" => parse_lowpass(&mut args, no_duplicates(map)?),
-                "sparkfft" => parse_sparkfft(&mut args, no_duplicates(map)?),
-                "bucket" => parse_bucket(&mut args, no_duplicates(map)?),
-                "write" => parse_write(&mut args, no_duplicates(map)?),
-                "gen" => parse_gen(&mut args, map),
-                "ui" => parse_ui(&mut args, no_duplicates(map)?),
-                "eui" => parse_eui(&mut args, no_duplicates(map)?),
-                _ => Err(anyhow!("unrecognised command")),
-            }
-            .with_context(|| anyhow!("processing command: {:?}", cmd))?,
-        );
+        let command = match cmd.as_str() {
+            "from" => parse_from(&mut args, no_duplicates(map)?),
+            "shift" => parse_shift(&mut args, no_duplicates(map)?),
+            "slice" => parse_slice(&mut args, no_duplicates(map)?, capture.as_deref()),
+            "extract" => parse_extract(&mut args, no_duplicates(map)?, capture.as_deref()),
+            "annotations" => parse_annotations(&mut args, no_duplicates(map)?, capture.as_deref()),
+            "lowpass" => parse_lowpass(&mut args, no_duplicates(map)?),
+            "resample" => parse_resample(&mut args, no_duplicates(map)?),
+            "sparkfft" => parse_sparkfft(&mut args, no_duplicates(map)?),
+            "png" => parse_png(&mut args, no_duplicates(map)?),
+            "psd" => parse_psd(&mut args, no_duplicates(map)?),
+            "signals" => parse_signals(&mut args, no_duplicates(map)?),
+            "bursts" => parse_bursts(&mut args, no_duplicates(map)?),
+            "bucket" => parse_bucket(&mut args, no_duplicates(map)?),
+            "write" => parse_write(&mut args, no_duplicates(map)?),
+            "gen" => parse_gen(&mut args, map),
+            "eui" => parse_eui(&mut args, no_duplicates(map)?),
+            "tui" => parse_tui(&mut args, no_duplicates(map)?),
+            _ => Err(anyhow!("unrecognised command")),
+        }
+        .with_context(|| anyhow!("processing command: {:?}", cmd))?;
+
+        if let Command::Octagon(Operation::From { ref filename, .. }) = command {
+            capture = Some(PathBuf::from(filename));
+        }
+        matched.push(command);
     }
 
     Ok(matched)
@@ -54,25 +77,107 @@ fn parse_from<'a, I: Iterator<Item = &'a String>>(
 
     let provided_sample_rate = map.remove("sr");
     let provided_format = map.remove("format");
+    let provided_centre = map.remove("centre");
     ensure!(map.is_empty(), "invalid flags: {:?}", map.keys());
 
     Ok(Command::Octagon(Operation::From {
-        details: guess_details(&filename, provided_sample_rate, provided_format)?,
+        details: guess_details(
+            filename,
+            provided_sample_rate,
+            provided_format,
+            provided_centre,
+        )?,
         filename: filename.to_string(),
     }))
 }
 
+/// The arguments which would `parse` back into this operation, if it's a simple one.
+pub fn command_line(op: &Operation) -> Option<String> {
+    use crate::axis::format_si_exact;
+    Some(match *op {
+        Operation::From {
+            ref details,
+            ref filename,
+        } => {
+            let mut line = format!(
+                "from -sr {} -format {}",
+                format_si_exact(i64::try_from(details.sample_rate).ok()?),
+                details.format.extension()
+            );
+            if let Some(centre) = details.centre_frequency {
+                line.push_str(&format!(
+                    " -centre {}",
+                    format_si_exact(i64::try_from(centre).ok()?)
+                ));
+            }
+            format!("{} {}", line, quote(filename))
+        }
+        Operation::Shift { frequency } => format!("shift {}", format_si_exact(frequency)),
+        Operation::Slice { start, end } => {
+            let mut line = "slice".to_string();
+            if let Some(start) = start {
+                line.push_str(&format!(" -start {}s", start));
+            }
+            if let Some(end) = end {
+                line.push_str(&format!(" -end {}s", end));
+            }
+            line
+        }
+        Operation::LowPass {
+            size,
+            decimate,
+            frequency,
+        } if 0 == size % 2 => format!(
+            "lowpass -power {} -decimate {} {}",
+            size / 2,
+            decimate,
+            format_si_exact(i64::try_from(frequency).ok()?)
+        ),
+        Operation::Resample { rate } => {
+            format!("resample {}", format_si_exact(i64::try_from(rate).ok()?))
+        }
+        Operation::Write {
+            overwrite,
+            ref prefix,
+            format,
+        } => format!(
+            "write{} -format {} {}",
+            if overwrite { " -overwrite yes" } else { "" },
+            format.extension(),
+            quote(prefix)
+        ),
+        _ => return None,
+    })
+}
+
+/// Enough quoting for a POSIX shell.
+fn quote(arg: &str) -> String {
+    if !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./+:,=@%".contains(c))
+    {
+        return arg.to_string();
+    }
+    format!("'{}'", arg.replace('\'', "'\\''"))
+}
+
 pub fn guess_details(
     filename: &str,
     override_sample_rate: Option<String>,
     override_format: Option<String>,
+    override_centre: Option<String>,
 ) -> Result<FileDetails> {
-    let (mut sample_rate, mut format) = guess_format_from_name(filename)?;
+    let (mut sample_rate, mut format, mut centre) = guess_format_from_name(filename)?;
 
     if let Some(provided) = override_sample_rate {
         sample_rate = Some(provided);
     }
 
+    if let Some(provided) = override_centre {
+        centre = Some(provided);
+    }
+
     if let Some(provided) = override_format {
         format = Some(
             guess_from_extension(&provided)
@@ -93,12 +198,24 @@ pub fn guess_details(
                 filename
             )
         })?,
+        centre_frequency: match centre {
+            Some(centre) => Some(
+                parse_si_f64(&centre)
+                    .with_context(|| anyhow!("parsing centre frequency {:?}", centre))?
+                    .round() as u64,
+            ),
+            None => None,
+        },
     };
     Ok(details)
 }
 
-fn guess_format_from_name(filename: &str) -> Result<(Option<String>, Option<FileFormat>)> {
+pub type Guesses = (Option<String>, Option<FileFormat>, Option<String>);
+
+/// Sample rate, format and centre frequency, from the filename, if possible.
+pub fn guess_format_from_name(filename: &str) -> Result<Guesses> {
     let mut sample_rate = None;
+    let mut centre = None;
 
     if let Some(guess) = guess_sample_rate(filename) {
         sample_rate = Some(guess);
@@ -108,19 +225,21 @@ fn guess_format_from_name(filename: &str) -> Result<(Option<String>, Option<File
 
     // More specifically, it could be a gqrx file of this format:
     // gqrx_20180126_111922_868000000_8000000_fc.raw'
-    if let Some(gqrx_sample_rate) = Regex::new("gqrx_.*?_[0-9]+_([0-9]+)_fc.raw")?
+    if let Some(gqrx) = Regex::new("gqrx_.*?_([0-9]+)_([0-9]+)_fc.raw")?
         .captures_iter(filename)
         .next()
     {
-        sample_rate = Some(gqrx_sample_rate[1].to_string());
+        centre = Some(gqrx[1].to_string());
+        sample_rate = Some(gqrx[2].to_string());
         format = Some(FileFormat::ComplexFloat32);
     }
 
-    if let Some(rtl433) = Regex::new(r#"g\d+_\d+(?:\.\d+)?M_(\d+k).cu8"#)?
+    if let Some(rtl433) = Regex::new(r#"g\d+_(\d+(?:\.\d+)?M)_(\d+k).cu8"#)?
         .captures_iter(filename)
         .next()
     {
-        sample_rate = Some(rtl433[1].to_string());
+        centre = Some(rtl433[1].to_string());
+        sample_rate = Some(rtl433[2].to_string());
         format = Some(FileFormat::ComplexUint8);
     }
 
@@ -131,7 +250,7 @@ fn guess_format_from_name(filename: &str) -> Result<(Option<String>, Option<File
             format = Some(guess);
         }
     }
-    Ok((sample_rate, format))
+    Ok((sample_rate, format, centre))
 }
 
 fn parse_shift<'a, I: Iterator<Item = &'a String>>(
@@ -148,6 +267,69 @@ fn parse_shift<'a, I: Iterator<Item = &'a String>>(
     }))
 }
 
+fn parse_slice<'a, I: Iterator<Item = &'a String>>(
+    _args: I,
+    mut map: HashMap<String, String>,
+    capture: Option<&Path>,
+) -> Result<Command> {
+    if let Some(label) = map.remove("label") {
+        ensure!(
+            map.is_empty(),
+            "-label can't be used with: {:?}",
+            map.keys()
+        );
+        let capture = capture.ok_or_else(|| anyhow!("-label requires an earlier 'from'"))?;
+        let annotation = annotations::find(capture, &label)?;
+        return Ok(Command::Octagon(Operation::Slice {
+            start: Some(annotation.start),
+            end: Some(annotation.end),
+        }));
+    }
+
+    let start = match map.remove("start") {
+        Some(val) => Some(parse_seconds(&val)?),
+        None => None,
+    };
+
+    let end = match map.remove("end") {
+        Some(val) => Some(parse_seconds(&val)?),
+        None => None,
+    };
+
+    ensure!(map.is_empty(), "invalid flags: {:?}", map.keys());
+
+    Ok(Command::Octagon(Operation::Slice { start, end }))
+}
+
+fn parse_extract<'a, I: Iterator<Item = &'a String>>(
+    mut args: I,
+    map: HashMap<String, String>,
+    capture: Option<&Path>,
+) -> Result<Command> {
+    let label = args
+        .next()
+        .ok_or_else(|| anyhow!("'extract' requires a label argument"))?;
+    ensure!(map.is_empty(), "invalid flags: {:?}", map.keys());
+
+    let capture = capture.ok_or_else(|| anyhow!("'extract' requires an earlier 'from'"))?;
+    Ok(Command::Octagon(Operation::Extract(annotations::find(
+        capture, label,
+    )?)))
+}
+
+fn parse_annotations<'a, I: Iterator<Item = &'a String>>(
+    _args: I,
+    map: HashMap<String, String>,
+    capture: Option<&Path>,
+) -> Result<Command> {
+    ensure!(map.is_empty(), "invalid flags: {:?}", map.keys());
+
+    let capture = capture.ok_or_else(|| anyhow!("'annotations' requires an earlier 'from'"))?;
+    Ok(Command::Octagon(Operation::Annotations(annotations::load(
+        capture,
+    )?)))
+}
+
 fn parse_lowpass<'a, I: Iterator<Item = &'a String>>(
     mut args: I,
     mut map: HashMap<String, String>,
@@ -179,6 +361,20 @@ fn parse_lowpass<'a, I: Iterator<Item = &'a String>>(
     }))
 }
 
+fn parse_resample<'a, I: Iterator<Item = &'a String>>(
+    mut args: I,
+    map: HashMap<String, String>,
+) -> Result<Command> {
+    ensure!(map.is_empty(), "'resample' has no named arguments");
+
+    Ok(Command::Octagon(Operation::Resample {
+        rate: parse_si_u64(
+            args.next()
+                .ok_or_else(|| anyhow!("'resample' requires a sample rate argument"))?,
+        )?,
+    }))
+}
+
 fn parse_sparkfft<'a, I: Iterator<Item = &'a String>>(
     _args: I,
     mut map: HashMap<String, String>,
@@ -194,28 +390,283 @@ fn parse_sparkfft<'a, I: Iterator<Item = &'a String>>(
     };
 
     let (min, max) = match map.remove("range") {
-        Some(val) => {
-            let (min, max) = val.split_at(
-                val.find(':')
-                    .ok_or_else(|| anyhow!("range argument must contain a ':': '{}'", val))?,
-            );
+        Some(val) => parse_range(&val)?,
+        None => (None, None),
+    };
 
-            let min: f32 = min.parse()?;
-            let max: f32 = max.chars().skip(1).collect::<String>().parse()?;
+    let window = match map.remove("window") {
+        Some(val) => parse_window(&val)?,
+        None => Windowing::BlackmanHarris,
+    };
 
-            (Some(min), Some(max))
-        }
-        None => (None, None),
+    let scale = match map.remove("scale") {
+        Some(val) => parse_scale(&val)?,
+        None => Scale::Decibels,
+    };
+
+    let axis = match map.remove("axis") {
+        Some(val) => parse_frequency_reference(&val)?,
+        None => None,
+    };
+
+    let time = match map.remove("time") {
+        Some(val) => parse_time_unit(&val)?,
+        None => None,
+    };
+
+    let colormap = match map.remove("colormap") {
+        Some(val) => Some(parse_colormap(&val)?),
+        None => None,
     };
 
+    ensure!(width > 0, "-width must be positive");
+    ensure!(stride > 0, "-stride must be positive");
     ensure!(map.is_empty(), "invalid flags: {:?}", map.keys());
 
-    Ok(Command::Octagon(Operation::SparkFft {
+    Ok(Command::Octagon(Operation::SparkFft(SparkFft {
         width,
         stride,
+        window,
+        scale,
         min,
         max,
-    }))
+        axis,
+        time,
+        colormap,
+    })))
+}
+
+fn parse_png<'a, I: Iterator<Item = &'a String>>(
+    mut args: I,
+    mut map: HashMap<String, String>,
+) -> Result<Command> {
+    let width = match map.remove("width") {
+        Some(val) => usize::try_from(parse_si_u64(&val)?)?,
+        None => 1024,
+    };
+
+    let height = match map.remove("height") {
+        Some(val) => usize::try_from(parse_si_u64(&val)?)?,
+        None => 1024,
+    };
+
+    let window = match map.remove("window") {
+        Some(val) => parse_window(&val)?,
+        None => Windowing::BlackmanHarris,
+    };
+
+    let scale = match map.remove("scale") {
+        Some(val) => parse_scale(&val)?,
+        None => Scale::Decibels,
+    };
+
+    let (min, max) = match map.remove("range") {
+        Some(val) => parse_range(&val)?,
+        None => (None, None),
+    };
+
+    let colormap = match map.remove("colormap") {
+        Some(val) => parse_colormap(&val)?,
+        None => Colormap::Viridis,
+    };
+
+    let start = match map.remove("start") {
+        Some(val) => Some(parse_seconds(&val)?),
+        None => None,
+    };
+
+    let end = match map.remove("end") {
+        Some(val) => Some(parse_seconds(&val)?),
+        None => None,
+    };
+
+    let axis = match map.remove("axis") {
+        Some(val) => parse_frequency_reference(&val)?,
+        None => None,
+    };
+
+    ensure!(width > 0, "-width must be positive");
+    ensure!(height > 0, "-height must be positive");
+    ensure!(map.is_empty(), "invalid flags: {:?}", map.keys());
+
+    let filename = args
+        .next()
+        .ok_or_else(|| anyhow!("'png' requires a filename argument"))?
+        .to_string();
+
+    Ok(Command::Octagon(Operation::Png(Png {
+        filename,
+        width,
+        window,
+        scale,
+        colormap,
+        min,
+        max,
+        start,
+        end,
+        height,
+        axis,
+    })))
+}
+
+fn parse_psd<'a, I: Iterator<Item = &'a String>>(
+    _args: I,
+    mut map: HashMap<String, String>,
+) -> Result<Command> {
+    let width = match map.remove("width") {
+        Some(val) => usize::try_from(parse_si_u64(&val)?)?,
+        None => 1024,
+    };
+
+    let overlap = match map.remove("overlap") {
+        Some(val) => {
+            let percent = parse_si_f64(&val)?;
+            ensure!(
+                (0. ..100.).contains(&percent),
+                "overlap is a percentage, at least 0, and under 100: {:?}",
+                val
+            );
+            percent / 100.
+        }
+        None => 0.5,
+    };
+
+    let window = match map.remove("window") {
+        Some(val) => parse_window(&val)?,
+        None => Windowing::BlackmanHarris,
+    };
+
+    let format = match map.remove("format") {
+        Some(ref val) if val == "csv" => PsdFormat::Csv,
+        Some(ref val) if val == "json" => PsdFormat::Json,
+        Some(other) => bail!("unrecognised psd format: {:?}", other),
+        None => PsdFormat::Csv,
+    };
+
+    let output = map.remove("output");
+
+    let plot = match map.remove("plot") {
+        Some(val) => parse_bool(&val)?,
+        None => false,
+    };
+
+    ensure!(width > 0, "-width must be positive");
+    ensure!(
+        (width as f64 * (1. - overlap)).round() >= 1.,
+        "-overlap leaves no gap between ffts of width {}",
+        width
+    );
+    ensure!(map.is_empty(), "invalid flags: {:?}", map.keys());
+
+    Ok(Command::Octagon(Operation::Psd(Psd {
+        width,
+        overlap,
+        window,
+        format,
+        output,
+        plot,
+    })))
+}
+
+fn parse_signals<'a, I: Iterator<Item = &'a String>>(
+    _args: I,
+    mut map: HashMap<String, String>,
+) -> Result<Command> {
+    let width = match map.remove("width") {
+        Some(val) => usize::try_from(parse_si_u64(&val)?)?,
+        None => 1024,
+    };
+
+    let window = match map.remove("window") {
+        Some(val) => parse_window(&val)?,
+        None => Windowing::BlackmanHarris,
+    };
+
+    let rows = match map.remove("rows") {
+        Some(val) => usize::try_from(parse_si_u64(&val)?)?,
+        None => 256,
+    };
+
+    let threshold = match map.remove("threshold") {
+        Some(val) => val.parse()?,
+        None => 10.,
+    };
+
+    ensure!(width > 0, "-width must be positive");
+    ensure!(rows > 0, "-rows must be positive");
+    ensure!(map.is_empty(), "invalid flags: {:?}", map.keys());
+
+    Ok(Command::Octagon(Operation::Signals(Signals {
+        width,
+        window,
+        rows,
+        threshold,
+    })))
+}
+
+fn parse_bursts<'a, I: Iterator<Item = &'a String>>(
+    _args: I,
+    mut map: HashMap<String, String>,
+) -> Result<Command> {
+    let block = match map.remove("block") {
+        Some(val) => parse_seconds(&val)?,
+        None => 0.001,
+    };
+
+    let threshold = match map.remove("threshold") {
+        Some(val) => val.parse()?,
+        None => 10.,
+    };
+
+    let hysteresis = match map.remove("hysteresis") {
+        Some(val) => val.parse()?,
+        None => 3.,
+    };
+
+    let min_length = match map.remove("min") {
+        Some(val) => parse_seconds(&val)?,
+        None => 0.,
+    };
+
+    let pad = match map.remove("pad") {
+        Some(val) => parse_seconds(&val)?,
+        None => 0.,
+    };
+
+    let width = match map.remove("width") {
+        Some(val) => usize::try_from(parse_si_u64(&val)?)?,
+        None => 1024,
+    };
+
+    let write = map.remove("write");
+
+    let format = match map.remove("format") {
+        Some(val) => {
+            guess_from_extension(&val).ok_or_else(|| anyhow!("unrecognised format: {:?}", val))?
+        }
+        None => FileFormat::ComplexFloat32,
+    };
+
+    let overwrite = match map.remove("overwrite") {
+        Some(val) => parse_bool(&val)?,
+        None => false,
+    };
+
+    ensure!(block > 0., "-block must be positive");
+    ensure!(width > 0, "-width must be positive");
+    ensure!(map.is_empty(), "invalid flags: {:?}", map.keys());
+
+    Ok(Command::Octagon(Operation::Bursts(Bursts {
+        block,
+        threshold,
+        hysteresis,
+        min_length,
+        pad,
+        width,
+        write,
+        format,
+        overwrite,
+    })))
 }
 
 fn parse_bucket<'a, I: Iterator<Item = &'a String>>(
@@ -260,14 +711,25 @@ fn parse_write<'a, I: Iterator<Item = &'a String>>(
         None => false,
     };
 
+    let format = match map.remove("format") {
+        Some(val) => {
+            guess_from_extension(&val).ok_or_else(|| anyhow!("unrecognised format: {:?}", val))?
+        }
+        None => FileFormat::ComplexFloat32,
+    };
+
     ensure!(map.is_empty(), "invalid flags: {:?}", map.keys());
 
     let prefix: String = args
         .next()
-        .ok_or_else(|| anyhow!("'lowpass' requires a frequency argument"))?
+        .ok_or_else(|| anyhow!("'write' requires a filename prefix argument"))?
         .to_string();
 
-    Ok(Command::Octagon(Operation::Write { overwrite, prefix }))
+    Ok(Command::Octagon(Operation::Write {
+        overwrite,
+        prefix,
+        format,
+    }))
 }
 
 fn parse_gen<'a, I: Iterator<Item = &'a String>>(
@@ -306,13 +768,39 @@ fn parse_gen<'a, I: Iterator<Item = &'a String>>(
     }))
 }
 
-fn parse_ui<'a, I: Iterator<Item = &'a String>>(
+fn parse_tui<'a, I: Iterator<Item = &'a String>>(
     _args: I,
-    map: HashMap<String, String>,
+    mut map: HashMap<String, String>,
 ) -> Result<Command> {
+    let width = match map.remove("width") {
+        Some(val) => usize::try_from(parse_si_u64(&val)?)?,
+        None => 512,
+    };
+
+    let window = match map.remove("window") {
+        Some(val) => parse_window(&val)?,
+        None => Windowing::BlackmanHarris,
+    };
+
+    let scale = match map.remove("scale") {
+        Some(val) => parse_scale(&val)?,
+        None => Scale::Decibels,
+    };
+
+    let colormap = match map.remove("colormap") {
+        Some(val) => parse_colormap(&val)?,
+        None => Colormap::Viridis,
+    };
+
+    ensure!(width.is_power_of_two(), "-width must be a power of two");
     ensure!(map.is_empty(), "invalid flags: {:?}", map.keys());
 
-    Ok(Command::Ui)
+    Ok(Command::Tui(Tui {
+        width,
+        window,
+        scale,
+        colormap,
+    }))
 }
 
 fn parse_eui<'a, I: Iterator<Item = &'a String>>(
@@ -356,18 +844,18 @@ fn parse_si_i64<S: AsRef<str>>(from: S) -> Result<i64> {
     let (val, mul) = find_multiplication_suffix(from);
     let parsed: i64 = val.parse()?;
     //        .with_context(|| anyhow!("parsing signed integer {:?}", from))?;
-    Ok(parsed
-        .checked_mul(i64::try_from(mul)?)
-        .ok_or_else(|| anyhow!("unit is out of range: {}", from))?)
+    parsed
+        .checked_mul(i64::from(mul))
+        .ok_or_else(|| anyhow!("unit is out of range: {}", from))
 }
 
 fn parse_si_u64(from: &str) -> Result<u64> {
     let (val, mul) = find_multiplication_suffix(from);
     let parsed: u64 = val.parse()?;
     //        .with_context(|| anyhow!("parsing unsigned integer {:?}", from))?;
-    Ok(parsed
+    parsed
         .checked_mul(u64::from(mul))
-        .ok_or_else(|| anyhow!("unit is out of range: {}", from))?)
+        .ok_or_else(|| anyhow!("unit is out of range: {}", from))
 }
 
 fn parse_si_f64<S: AsRef<str>>(from: S) -> Result<f64> {
@@ -378,6 +866,27 @@ fn parse_si_f64<S: AsRef<str>>(from: S) -> Result<f64> {
     Ok(parsed * f64::from(mul))
 }
 
+/// `LOW:HIGH`, both of which are required
+fn parse_range(val: &str) -> Result<(Option<f32>, Option<f32>)> {
+    let (min, max) = val.split_at(
+        val.find(':')
+            .ok_or_else(|| anyhow!("range argument must contain a ':': '{}'", val))?,
+    );
+
+    let min: f32 = min.parse()?;
+    let max: f32 = max.chars().skip(1).collect::<String>().parse()?;
+
+    Ok((Some(min), Some(max)))
+}
+
+/// A number of seconds, optionally suffixed with an `s`.
+fn parse_seconds(from: &str) -> Result<f64> {
+    let seconds = parse_si_f64(from.strip_suffix('s').unwrap_or(from))
+        .with_context(|| anyhow!("parsing seconds: {:?}", from))?;
+    ensure!(seconds >= 0., "seconds may not be negative: {:?}", from);
+    Ok(seconds)
+}
+
 fn parse_bool(from: &str) -> Result<bool> {
     match from.parse() {
         Ok(val) => Ok(val),
@@ -389,6 +898,61 @@ fn parse_bool(from: &str) -> Result<bool> {
     }
 }
 
+fn parse_window(from: &str) -> Result<Windowing> {
+    Ok(match from {
+        "rectangular" | "rect" | "none" => Windowing::Rectangular,
+        "blackman-harris" | "bh" => Windowing::BlackmanHarris,
+        "hann" | "hanning" => Windowing::Hann,
+        "hamming" => Windowing::Hamming,
+        "flat-top" | "flattop" => Windowing::FlatTop,
+        "kaiser" => Windowing::Kaiser { beta: 8.6 },
+        other => match other.strip_prefix("kaiser:") {
+            Some(beta) => {
+                let beta = parse_si_f64(beta)?;
+                ensure!(beta >= 0., "kaiser beta can't be negative: {}", beta);
+                Windowing::Kaiser { beta: beta as f32 }
+            }
+            None => bail!("unrecognised window: '{}'", other),
+        },
+    })
+}
+
+fn parse_scale(from: &str) -> Result<Scale> {
+    Ok(match from {
+        "linear" | "lin" => Scale::Linear,
+        "db" | "dB" => Scale::Decibels,
+        other => bail!("unrecognised scale: '{}'", other),
+    })
+}
+
+fn parse_frequency_reference(from: &str) -> Result<Option<FrequencyReference>> {
+    Ok(match from {
+        "none" | "no" => None,
+        "relative" | "rel" => Some(FrequencyReference::Relative),
+        "absolute" | "abs" => Some(FrequencyReference::Absolute),
+        other => bail!("unrecognised axis: '{}'", other),
+    })
+}
+
+fn parse_time_unit(from: &str) -> Result<Option<TimeUnit>> {
+    Ok(match from {
+        "none" | "no" => None,
+        "seconds" | "s" => Some(TimeUnit::Seconds),
+        "samples" => Some(TimeUnit::Samples),
+        other => bail!("unrecognised time unit: '{}'", other),
+    })
+}
+
+fn parse_colormap(from: &str) -> Result<Colormap> {
+    Colormap::from_name(from).ok_or_else(|| {
+        anyhow!(
+            "unrecognised colormap: '{}', try: {:?}",
+            from,
+            Colormap::ALL.iter().map(|c| c.name()).collect::<Vec<_>>()
+        )
+    })
+}
+
 fn guess_from_extension(ext: &str) -> Option<FileFormat> {
     use self::FileFormat::*;
     Some(match ext {
@@ -407,25 +971,20 @@ where
 {
     let mut ret = HashMap::new();
 
-    loop {
-        // borrow checker :((
-        if let Some(opt) = iter.peek() {
-            if opt.is_empty() {
-                break;
-            }
+    while let Some(opt) = iter.peek() {
+        if opt.is_empty() {
+            break;
+        }
 
-            if !opt.starts_with('-') {
-                break;
-            }
+        if !opt.starts_with('-') {
+            break;
+        }
 
-            // it's a minus, so probably an option.. but is it a number?
-            if let Some(c) = opt.chars().nth(2) {
-                if c.is_digit(10) {
-                    break;
-                }
+        // it's a minus, so probably an option.. but is it a number, like -5k?
+        if let Some(c) = opt.chars().nth(1) {
+            if c.is_ascii_digit() {
+                break;
             }
-        } else {
-            break;
         }
 
         let opt = iter.next().expect("just peeked that");
@@ -437,7 +996,7 @@ where
         };
 
         ret.entry(opt[1..].to_string())
-            .or_insert_with(|| Vec::new())
+            .or_insert_with(Vec::new)
             .push(arg.to_string());
     }
 
@@ -455,6 +1014,121 @@ fn no_duplicates(map: HashMap<String, Vec<String>>) -> Result<HashMap<String, St
 
 #[cfg(test)]
 mod tests {
+    #[test]
+    fn command_line() {
+        use super::{command_line, parse, Command};
+        use crate::{FileDetails, FileFormat, Operation};
+
+        let ops = [
+            Operation::From {
+                details: FileDetails {
+                    format: FileFormat::ComplexUint8,
+                    sample_rate: 2_400_000,
+                    centre_frequency: Some(433_920_000),
+                },
+                filename: "it's here.cu8".to_string(),
+            },
+            Operation::Slice {
+                start: Some(1.25),
+                end: None,
+            },
+            Operation::Shift { frequency: -12_500 },
+            Operation::Shift { frequency: -5_000 },
+            Operation::LowPass {
+                size: 40,
+                decimate: 8,
+                frequency: 100_000,
+            },
+            Operation::Resample { rate: 48_000 },
+            Operation::Write {
+                overwrite: true,
+                prefix: "out".to_string(),
+                format: FileFormat::ComplexInt16,
+            },
+        ];
+
+        let line = ops
+            .iter()
+            .map(|op| command_line(op).unwrap())
+            .collect::<Vec<_>>()
+            .join(" ");
+        assert_eq!(
+            "from -sr 2400k -format cu8 -centre 433920k 'it'\\''s here.cu8' slice -start 1.25s \
+             shift -12500 shift -5k lowpass -power 20 -decimate 8 100k resample 48k \
+             write -overwrite yes -format cs16 out",
+            line
+        );
+
+        // as the shell would split it
+        let args: Vec<String> = line
+            .replace("'it'\\''s here.cu8'", "it's_here.cu8")
+            .split(' ')
+            .map(|s| s.replace('_', " "))
+            .collect();
+        let parsed = parse(args.iter()).unwrap();
+        assert_eq!(ops.len(), parsed.len());
+        for (op, parsed) in ops.iter().zip(parsed) {
+            match parsed {
+                Command::Octagon(parsed) => {
+                    assert_eq!(format!("{:?}", op), format!("{:?}", parsed))
+                }
+                _ => panic!("not an operation"),
+            }
+        }
+    }
+
+    #[test]
+    fn negative_values() {
+        use super::{parse, Command};
+        use crate::axis::format_si_exact;
+        use crate::Operation;
+
+        // as printed by `signals`
+        let line = format!(
+            "gen -cos 1000 8000 shift {} lowpass -decimate 8 {} shift {}",
+            format_si_exact(-5_000),
+            format_si_exact(2_500),
+            format_si_exact(-2_000_000)
+        );
+        assert_eq!(
+            "gen -cos 1000 8000 shift -5k lowpass -decimate 8 2500 shift -2M",
+            line
+        );
+        let args: Vec<String> = line.split(' ').map(|s| s.to_string()).collect();
+        let shifts: Vec<i64> = parse(args.iter())
+            .unwrap()
+            .into_iter()
+            .filter_map(|command| match command {
+                Command::Octagon(Operation::Shift { frequency }) => Some(frequency),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(vec![-5_000, -2_000_000], shifts);
+    }
+
+    #[test]
+    fn zero_sizes() {
+        use super::parse;
+        let rejected = |line: &str| {
+            let args: Vec<String> = line.split(' ').map(|s| s.to_string()).collect();
+            parse(args.iter()).is_err()
+        };
+        assert!(!rejected("gen -cos 1k 8k sparkfft -width 64 -stride 1"));
+        assert!(rejected("gen -cos 1k 8k sparkfft -width 0"));
+        assert!(rejected("gen -cos 1k 8k sparkfft -stride 0"));
+
+        assert!(!rejected("gen -cos 1k 8k png -width 64 -height 64 x.png"));
+        assert!(rejected("gen -cos 1k 8k png -width 0 x.png"));
+        assert!(rejected("gen -cos 1k 8k png -height 0 x.png"));
+
+        assert!(!rejected("gen -cos 1k 8k psd -width 4 -overlap 75"));
+        assert!(rejected("gen -cos 1k 8k psd -width 0"));
+        assert!(rejected("gen -cos 1k 8k psd -width 4 -overlap 90"));
+
+        assert!(!rejected("gen -cos 1k 8k signals -width 64"));
+        assert!(rejected("gen -cos 1k 8k signals -width 0"));
+    }
+
     #[test]
     fn mega() {
         use super::parse_si_u64;
@@ -463,4 +1137,25 @@ mod tests {
         assert_eq!(47_000, parse_si_u64("47k").unwrap());
         assert_eq!(0, parse_si_u64("0M").unwrap());
     }
+
+    #[test]
+    fn centre_from_name() {
+        use super::guess_details;
+        let gqrx = guess_details(
+            "gqrx_20180126_111922_868000000_8000000_fc.raw",
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+        assert_eq!(8_000_000, gqrx.sample_rate);
+        assert_eq!(Some(868_000_000), gqrx.centre_frequency);
+
+        let rtl433 = guess_details("g001_433.92M_250k.cu8", None, None, None).unwrap();
+        assert_eq!(250_000, rtl433.sample_rate);
+        assert_eq!(Some(433_920_000), rtl433.centre_frequency);
+
+        let plain = guess_details("foo.sr2M.cf32", None, None, Some("1.2G".to_string())).unwrap();
+        assert_eq!(Some(1_200_000_000), plain.centre_frequency);
+    }
 }
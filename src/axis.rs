@@ -0,0 +1,141 @@
+//! Labelling of frequency and time axes, shared between the various renderers.
+
+/// Whether frequencies are shown relative to the centre of the band, or as absolute
+/// frequencies, which requires the centre frequency of the input to be known.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum FrequencyReference {
+    Relative,
+    Absolute,
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum TimeUnit {
+    Seconds,
+    Samples,
+}
+
+/// "Nice" (1, 2, 5 times a power of ten) values between `lo` and `hi`, at most `max_ticks` of them.
+pub fn ticks(lo: f64, hi: f64, max_ticks: usize) -> Vec<f64> {
+    if hi <= lo || hi.is_nan() || lo.is_nan() || 0 == max_ticks {
+        return Vec::new();
+    }
+
+    let raw = (hi - lo) / max_ticks as f64;
+    let magnitude = 10f64.powf(raw.log10().floor());
+    let step = [1., 2., 5., 10.]
+        .iter()
+        .map(|m| m * magnitude)
+        .find(|&step| step >= raw)
+        .expect("10 * magnitude >= raw");
+
+    let first = (lo / step).ceil() as i64;
+    let last = (hi / step).floor() as i64;
+    (first..=last).map(|i| i as f64 * step).collect()
+}
+
+/// Roughly how many characters to allow for each label in a `text_header`.
+const LABEL_SPACING: usize = 12;
+
+/// Labels for some of the `columns`, positioned after a `|` marking the column.
+///
+/// `first` and `last` are the values at the centres of the first and last columns.
+pub fn text_header(columns: usize, first: f64, last: f64) -> String {
+    let mut header = vec![' '; columns];
+    if columns < 2 {
+        return header.into_iter().collect();
+    }
+
+    let per_column = (last - first) / (columns - 1) as f64;
+    let mut free_from = 0;
+    for tick in ticks(first, last, columns / LABEL_SPACING) {
+        let column = ((tick - first) / per_column).round() as usize;
+        let label = format!("|{}", format_si(tick));
+        if column < free_from || column + label.chars().count() > columns {
+            continue;
+        }
+        for (i, c) in label.chars().enumerate() {
+            header[column + i] = c;
+        }
+        free_from = column + label.chars().count() + 1;
+    }
+
+    header.into_iter().collect()
+}
+
+/// A short, approximate, human-readable version of a value, e.g. `433.92M`.
+///
+/// This is accepted by arguments which take a fractional value, such as `gen -len`.
+pub fn format_si(val: f64) -> String {
+    let (scaled, suffix) = match val.abs() {
+        v if v >= 1e9 => (val / 1e9, "G"),
+        v if v >= 1e6 => (val / 1e6, "M"),
+        v if v >= 1e3 => (val / 1e3, "k"),
+        _ => (val, ""),
+    };
+
+    let mut num = format!("{:.3}", scaled);
+    while num.ends_with('0') {
+        num.pop();
+    }
+    if num.ends_with('.') {
+        num.pop();
+    }
+    if "-0" == num {
+        num = "0".to_string();
+    }
+
+    format!("{}{}", num, suffix)
+}
+
+/// An exact version of an integer, e.g. `433920k`, as accepted by arguments like `shift`.
+pub fn format_si_exact(val: i64) -> String {
+    for (suffix, mul) in [("G", 1_000_000_000), ("M", 1_000_000), ("k", 1_000)] {
+        if 0 != val && 0 == val % mul {
+            return format!("{}{}", val / mul, suffix);
+        }
+    }
+    format!("{}", val)
+}
+
+/// Seconds, with enough decimal places to distinguish things `resolution` seconds apart.
+pub fn format_seconds(seconds: f64, resolution: f64) -> String {
+    let places = if resolution > 0. {
+        (-resolution.log10()).ceil().clamp(0., 9.) as usize
+    } else {
+        9
+    };
+    format!("{:.*}s", places, seconds)
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn ticks() {
+        use super::ticks;
+        assert_eq!(vec![-500., 0., 500.], ticks(-512., 511., 4));
+        let fractional = ticks(0.1, 0.9, 5);
+        assert_eq!(4, fractional.len());
+        assert!((fractional[2] - 0.6).abs() < 1e-9);
+        assert!(ticks(1., 1., 3).is_empty());
+    }
+
+    #[test]
+    fn format_si() {
+        use super::format_si;
+        assert_eq!("433.92M", format_si(433_920_000.));
+        assert_eq!("-250k", format_si(-250_000.));
+        assert_eq!("1.5k", format_si(1_500.));
+        assert_eq!("0", format_si(-0.0001));
+        assert_eq!("12", format_si(12.));
+    }
+
+    #[test]
+    fn format_si_exact() {
+        use super::format_si_exact;
+        assert_eq!("433920k", format_si_exact(433_920_000));
+        assert_eq!("-125k", format_si_exact(-125_000));
+        assert_eq!("-1500", format_si_exact(-1_500));
+        assert_eq!("2G", format_si_exact(2_000_000_000));
+        assert_eq!("0", format_si_exact(0));
+    }
+}
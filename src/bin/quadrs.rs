@@ -1,20 +1,31 @@
 use std::env;
 
+use anyhow::anyhow;
 use anyhow::bail;
 use anyhow::Error;
 
 use quadrs::args;
-use quadrs::ui;
 
 fn usage(us: &str) {
     println!("usage: {} \\", us);
-    println!("    from [-sr SAMPLE_RATE] [-format cf32|cs8|cu8|cs16] FILENAME.sr32k.cf32 \\");
+    println!("    from [-sr SAMPLE_RATE] [-format cf32|cs8|cu8|cs16] [-centre FREQUENCY] FILENAME.sr32k.cf32 \\");
     println!("   shift [-]FREQUENCY \\");
+    println!("   slice [-start SECONDS] [-end SECONDS] | [-label ANNOTATION] \\");
     println!(" lowpass [-power 20] [-decimate 8] FREQUENCY \\");
-    println!("sparkfft [-width 128] [-stride =width] [-range LOW:HIGH] \\");
+    println!("resample SAMPLE_RATE \\");
+    println!(" extract ANNOTATION \\");
+    println!("annotations \\");
+    println!("sparkfft [-width 128] [-stride =width] [-window bh|rect|hann|hamming|kaiser[:BETA]|flattop] [-scale db|linear] [-range LOW:HIGH (auto)] \\");
+    println!("         [-axis none|relative|absolute] [-time none|seconds|samples] [-colormap viridis|inferno|turbo|grayscale] \\");
+    println!("     png [-width 1024] [-height 1024] [-window bh|rect|hann|hamming|kaiser[:BETA]|flattop] [-scale db|linear] [-range LOW:HIGH (auto)] \\");
+    println!("         [-colormap viridis] [-start SECONDS] [-end SECONDS] [-axis none|relative|absolute] FILENAME.png \\");
+    println!("     psd [-width 1024] [-overlap 50 (%)] [-window bh|rect|hann|hamming|kaiser[:BETA]|flattop] [-format csv|json] [-output FILENAME] [-plot no] \\");
+    println!(" signals [-width 1024] [-window bh|rect|hann|hamming|kaiser[:BETA]|flattop] [-rows 256] [-threshold 10 (dB)] \\");
+    println!("  bursts [-block 0.001 (s)] [-threshold 10 (dB)] [-hysteresis 3 (dB)] [-min 0 (s)] [-pad 0 (s)] [-width 1024] [-write PREFIX] [-format cf32|cs8|cu8|cs16] [-overwrite no] \\");
     println!("  bucket [-width 128] [-stride =width] [-by freq] COUNT \\");
-    println!("   write [-overwrite no] FILENAME_PREFIX \\");
+    println!("   write [-overwrite no] [-format cf32|cs8|cu8|cs16] FILENAME_PREFIX \\");
     println!("     gen [-cos FREQUENCY]* [-len 1 (second)] SAMPLE_RATE \\");
+    println!("     tui [-width 512] [-window bh|rect|hann|hamming|kaiser[:BETA]|flattop] [-scale db|linear] [-colormap viridis] \\");
 
     println!();
     println!();
@@ -50,8 +61,13 @@ fn main() -> Result<(), Error> {
         use quadrs::args::Command::*;
         match command {
             Octagon(op) => samples = op.exec(samples)?,
-            Ui => ui::display(samples.take().expect("ui requires an input FOR NOW"))?,
-            Eui { filename } => quadrs::eui::display(&filename)?,
+            Eui { filename } => quadrs::eui::display(samples.take(), &filename)?,
+            Tui(settings) => {
+                let samples = samples
+                    .take()
+                    .ok_or_else(|| anyhow!("tui needs something to show: from FILENAME tui"))?;
+                quadrs::tui::run(samples.as_ref(), &settings)?
+            }
         }
     }
 
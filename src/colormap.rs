@@ -0,0 +1,101 @@
+//! Mapping of normalised values onto colours, for the renderers which can show colour.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Serialize, Deserialize)]
+pub enum Colormap {
+    Viridis,
+    Inferno,
+    Turbo,
+    Grayscale,
+}
+
+// Evenly spaced samples of the matplotlib colormaps, linearly interpolated between.
+const VIRIDIS: [u32; 11] = [
+    0x440154, 0x482475, 0x414487, 0x355f8d, 0x2a788e, 0x21918c, 0x22a884, 0x44bf70, 0x7ad151,
+    0xbddf26, 0xfde725,
+];
+
+const INFERNO: [u32; 11] = [
+    0x000004, 0x160b39, 0x420a68, 0x6a176e, 0x932667, 0xbc3754, 0xdd513a, 0xf37819, 0xfca50a,
+    0xf6d746, 0xfcffa4,
+];
+
+const TURBO: [u32; 11] = [
+    0x30123b, 0x4662d7, 0x36aaf9, 0x1ae4b6, 0x72fe5e, 0xc7ef34, 0xfabb39, 0xf66b19, 0xcb2a04,
+    0xa11201, 0x7a0403,
+];
+
+const GRAYSCALE: [u32; 2] = [0x000000, 0xffffff];
+
+impl Colormap {
+    pub const ALL: [Colormap; 4] = [
+        Colormap::Viridis,
+        Colormap::Inferno,
+        Colormap::Turbo,
+        Colormap::Grayscale,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Colormap::Viridis => "viridis",
+            Colormap::Inferno => "inferno",
+            Colormap::Turbo => "turbo",
+            Colormap::Grayscale => "grayscale",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Colormap> {
+        Colormap::ALL
+            .iter()
+            .find(|map| map.name() == name)
+            .cloned()
+            .or(match name {
+                "greyscale" | "gray" | "grey" => Some(Colormap::Grayscale),
+                _ => None,
+            })
+    }
+
+    /// The one after (or before) this, going round.
+    pub fn cycle(&self, forwards: bool) -> Colormap {
+        let all = Colormap::ALL;
+        let i = all.iter().position(|c| c == self).unwrap_or(0);
+        let step = if forwards { 1 } else { all.len() - 1 };
+        all[(i + step) % all.len()]
+    }
+
+    fn stops(&self) -> &'static [u32] {
+        match *self {
+            Colormap::Viridis => &VIRIDIS,
+            Colormap::Inferno => &INFERNO,
+            Colormap::Turbo => &TURBO,
+            Colormap::Grayscale => &GRAYSCALE,
+        }
+    }
+
+    /// The colour for `t`, which is clamped to `0..=1`.
+    pub fn rgb(&self, t: f32) -> [u8; 3] {
+        let stops = self.stops();
+        let t = if t.is_nan() { 0. } else { t.clamp(0., 1.) };
+        let pos = t * (stops.len() - 1) as f32;
+        let lower = (pos.floor() as usize).min(stops.len() - 2);
+        let frac = pos - lower as f32;
+
+        let a = channels(stops[lower]);
+        let b = channels(stops[lower + 1]);
+        let mut ret = [0u8; 3];
+        for i in 0..3 {
+            ret[i] = (f32::from(a[i]) + (f32::from(b[i]) - f32::from(a[i])) * frac).round() as u8;
+        }
+        ret
+    }
+
+    /// The colour for `val`, scaled such that `min` is the bottom of the map, and `max` the top.
+    pub fn rgb_in(&self, val: f32, min: f32, max: f32) -> [u8; 3] {
+        self.rgb((val - min) / (max - min))
+    }
+}
+
+fn channels(rgb: u32) -> [u8; 3] {
+    [(rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8]
+}
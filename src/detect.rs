@@ -0,0 +1,542 @@
+//! Finding things of interest in an input: emitters, and bursts of energy.
+
+use anyhow::{ensure, Result};
+use num_traits::Zero;
+use rustfft::num_complex::Complex;
+
+use crate::axis::{format_seconds, format_si, format_si_exact};
+use crate::ffts::{percentile, FftConfig, FftReader, Windowing};
+use crate::psd::welch;
+use crate::samples::Samples;
+use crate::slice::Slice;
+use crate::{do_write, u64_from, usize_from, FileFormat};
+
+#[derive(Debug, Clone)]
+pub struct Signals {
+    pub width: usize,
+    pub window: Windowing,
+
+    /// How many slices to divide the input into, i.e. the time resolution.
+    pub rows: usize,
+
+    /// How far above the noise floor a bin must be to be considered active, in dB.
+    pub threshold: f32,
+}
+
+/// Something which was transmitting at some point in the input.
+#[derive(Debug, Clone)]
+pub struct Emitter {
+    /// Relative to the centre of the input, in Hz.
+    pub centre: f64,
+    pub bandwidth: f64,
+    pub peak_db: f32,
+
+    /// In seconds from the start of the input.
+    pub first_seen: f64,
+    pub last_seen: f64,
+
+    /// Fraction of the input for which the emitter was active.
+    pub duty_cycle: f64,
+}
+
+/// Every `width` samples are transformed, then averaged into (at most) `rows` rows, in dBFS.
+fn average_rows(samples: &dyn Samples, config: &FftConfig, rows: usize) -> Result<Vec<Vec<f32>>> {
+    let width = u64_from(config.width);
+    let segments = samples.len() / width;
+    ensure!(
+        segments > 0,
+        "input is too short ({} samples) for a {} wide fft",
+        samples.len(),
+        width
+    );
+    let rows = usize_from(segments.min(u64_from(rows)));
+    let full_scale = config.windowing.gain(config.width);
+
+    let mut reader = FftReader::new(config);
+    let mut power = vec![vec![0f32; config.width]; rows];
+    let mut counts = vec![0u32; rows];
+    for segment in 0..segments {
+        let row = (segment * u64_from(rows) / segments) as usize;
+        counts[row] += 1;
+        for (total, norm) in power[row]
+            .iter_mut()
+            .zip(reader.magnitudes_at(samples, segment * width)?)
+        {
+            *total += norm * norm;
+        }
+    }
+
+    Ok(power
+        .into_iter()
+        .zip(counts)
+        .map(|(row, count)| {
+            row.into_iter()
+                .map(|total| {
+                    10. * (total / count as f32 / full_scale / full_scale)
+                        .max(1e-20)
+                        .log10()
+                })
+                .collect()
+        })
+        .collect())
+}
+
+pub fn find_signals(samples: &dyn Samples, signals: &Signals) -> Result<Vec<Emitter>> {
+    let config = FftConfig {
+        width: signals.width,
+        windowing: signals.window,
+    };
+    let rows = average_rows(samples, &config, signals.rows)?;
+
+    // the median of each row; assuming most of the band is empty at any one time
+    let floors: Vec<f32> = rows
+        .iter()
+        .map(|row| percentile(&mut row.clone(), 50.))
+        .collect();
+
+    let active: Vec<Vec<bool>> = rows
+        .iter()
+        .zip(&floors)
+        .map(|(row, floor)| row.iter().map(|v| *v > floor + signals.threshold).collect())
+        .collect();
+
+    let seen_in_bin: Vec<usize> = (0..signals.width)
+        .map(|bin| active.iter().filter(|row| row[bin]).count())
+        .collect();
+
+    let sample_rate = samples.sample_rate() as f64;
+    let bin_width = sample_rate / signals.width as f64;
+    let row_seconds = samples.len() as f64 / sample_rate / rows.len() as f64;
+
+    let mut emitters = Vec::new();
+    for (first_bin, last_bin) in runs(&seen_in_bin) {
+        let active_rows: Vec<usize> = (0..rows.len())
+            .filter(|&row| active[row][first_bin..=last_bin].iter().any(|&a| a))
+            .collect();
+
+        // a single cell is almost certainly noise
+        let cells: usize = seen_in_bin[first_bin..=last_bin].iter().sum();
+        if cells < 2 {
+            continue;
+        }
+
+        // the centre of mass of the power above the noise floor, when active
+        let mut weight = 0.;
+        let mut moment = 0.;
+        let mut peak_db = f32::NEG_INFINITY;
+        for &row in &active_rows {
+            for (bin, &val) in rows[row]
+                .iter()
+                .enumerate()
+                .take(last_bin + 1)
+                .skip(first_bin)
+            {
+                let above = f64::from(val - floors[row]);
+                let power = 10f64.powf(above / 10.) - 1.;
+                weight += power.max(0.);
+                moment += power.max(0.) * bin as f64;
+                peak_db = peak_db.max(val);
+            }
+        }
+        let centre_bin = if weight > 0. {
+            moment / weight
+        } else {
+            (first_bin + last_bin) as f64 / 2.
+        };
+
+        emitters.push(Emitter {
+            centre: (centre_bin - (signals.width / 2) as f64) * bin_width,
+            bandwidth: (last_bin - first_bin + 1) as f64 * bin_width,
+            peak_db,
+            first_seen: active_rows[0] as f64 * row_seconds,
+            last_seen: (active_rows[active_rows.len() - 1] + 1) as f64 * row_seconds,
+            duty_cycle: active_rows.len() as f64 / rows.len() as f64,
+        });
+    }
+
+    Ok(emitters)
+}
+
+/// Inclusive ranges of non-zero values, allowing single-value gaps.
+fn runs(counts: &[usize]) -> Vec<(usize, usize)> {
+    let mut ret: Vec<(usize, usize)> = Vec::new();
+    for (i, &count) in counts.iter().enumerate() {
+        if 0 == count {
+            continue;
+        }
+        match ret.last_mut() {
+            Some((_, last)) if *last + 2 >= i => *last = i,
+            _ => ret.push((i, i)),
+        }
+    }
+    ret
+}
+
+pub fn print_signals(samples: &dyn Samples, signals: &Signals) -> Result<()> {
+    let emitters = find_signals(samples, signals)?;
+    let sample_rate = samples.sample_rate() as f64;
+    let centre = samples.centre_frequency();
+    let bin_width = sample_rate / signals.width as f64;
+
+    // there's no point printing frequencies more precisely than we measured them
+    let precision = 10f64.powf(bin_width.log10().floor());
+    let round = |val: f64| ((val / precision).round() * precision) as i64;
+
+    println!(
+        "{:>12} {:>10} {:>8} {:>10} {:>10} {:>6}  pipeline",
+        if centre.is_some() {
+            "frequency"
+        } else {
+            "offset"
+        },
+        "bandwidth",
+        "peak",
+        "first",
+        "last",
+        "duty"
+    );
+
+    for emitter in emitters {
+        let cutoff = (emitter.bandwidth / 2.).max(precision);
+        let decimate = ((sample_rate / (cutoff * 2. * 1.25)) as u64).max(1);
+        println!(
+            "{:>12} {:>10} {:>6.1}dB {:>10} {:>10} {:>5.1}%  shift {} lowpass -decimate {} {}",
+            format_si(emitter.centre + centre.unwrap_or(0) as f64),
+            format_si(emitter.bandwidth),
+            emitter.peak_db,
+            format_seconds(emitter.first_seen, 0.001),
+            format_seconds(emitter.last_seen, 0.001),
+            emitter.duty_cycle * 100.,
+            format_si_exact(-round(emitter.centre)),
+            decimate,
+            format_si_exact(round(cutoff.ceil()).max(1)),
+        );
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Clone)]
+pub struct Bursts {
+    /// Length of the blocks that energy is measured over, in seconds;
+    /// never fewer than `MIN_BLOCK` samples.
+    pub block: f64,
+
+    /// How far above the noise floor a block must be to start a burst, in dB.
+    pub threshold: f32,
+
+    /// How far below the `threshold` a block must drop to end a burst, in dB.
+    pub hysteresis: f32,
+
+    /// Shortest burst to report, in seconds.
+    pub min_length: f64,
+
+    /// How much to extend each burst by, at both ends, in seconds.
+    pub pad: f64,
+
+    /// Width of the fft used to estimate each burst's frequency.
+    pub width: usize,
+
+    /// Write each burst to a separate file, with this prefix, in this format.
+    pub write: Option<String>,
+    pub format: FileFormat,
+    pub overwrite: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Burst {
+    /// Sample offsets into the input, including padding; `end` is exclusive.
+    pub start: u64,
+    pub end: u64,
+
+    /// Mean power of the loudest block, in dB above the noise floor.
+    pub peak_db: f32,
+
+    /// The strongest frequency, relative to the centre of the input, in Hz;
+    /// `None` if the burst is too short to estimate it.
+    pub frequency: Option<f64>,
+}
+
+/// Fewest samples in a block, however short `-block` is at this sample rate.
+const MIN_BLOCK: u64 = 16;
+
+/// Fewest samples to estimate a frequency from.
+const MIN_FFT: u64 = 4;
+
+pub fn find_bursts(samples: &dyn Samples, bursts: &Bursts) -> Result<Vec<Burst>> {
+    let sample_rate = samples.sample_rate() as f64;
+    let block = ((bursts.block * sample_rate).round() as u64).max(MIN_BLOCK);
+    let blocks = samples.len() / block;
+    ensure!(
+        blocks > 1,
+        "input is too short ({} samples) for {} sample blocks",
+        samples.len(),
+        block
+    );
+
+    let mut buf = vec![Complex::zero(); usize_from(block)];
+    let mut energy = Vec::with_capacity(usize_from(blocks));
+    for i in 0..blocks {
+        samples.read_exact_at(i * block, &mut buf)?;
+        let power: f32 = buf.iter().map(|c| c.norm_sqr()).sum::<f32>() / block as f32;
+        energy.push(10. * power.max(1e-20).log10());
+    }
+
+    // most of the input is expected to be quiet
+    let floor = percentile(&mut energy.clone(), 20.);
+    let high = floor + bursts.threshold;
+    let low = high - bursts.hysteresis;
+
+    let mut found: Vec<(u64, u64, f32)> = Vec::new();
+    let mut current: Option<(u64, f32)> = None;
+    for (i, &db) in energy.iter().enumerate() {
+        let i = u64_from(i);
+        current = match current {
+            None if db >= high => Some((i, db)),
+            None => None,
+            Some((start, peak)) if db >= low => Some((start, peak.max(db))),
+            Some((start, peak)) => {
+                found.push((start, i, peak));
+                None
+            }
+        };
+    }
+    if let Some((start, peak)) = current {
+        found.push((start, blocks, peak));
+    }
+
+    let min_blocks = (bursts.min_length * sample_rate / block as f64).ceil() as u64;
+    let pad = (bursts.pad * sample_rate).round() as u64;
+
+    let mut ret: Vec<Burst> = Vec::new();
+    // the unpadded samples of each burst, which may be several, merged by their padding
+    let mut spans: Vec<(u64, u64)> = Vec::new();
+    for (start, end, peak) in found {
+        if end - start < min_blocks.max(1) {
+            continue;
+        }
+
+        let unpadded = (start * block, end * block);
+        let start = unpadded.0.saturating_sub(pad);
+        let end = (unpadded.1 + pad).min(samples.len());
+
+        if let (Some(previous), Some(span)) = (ret.last_mut(), spans.last_mut()) {
+            if previous.end >= start {
+                previous.end = end;
+                previous.peak_db = previous.peak_db.max(peak - floor);
+                span.1 = unpadded.1;
+                continue;
+            }
+        }
+
+        ret.push(Burst {
+            start,
+            end,
+            peak_db: peak - floor,
+            frequency: None,
+        });
+        spans.push(unpadded);
+    }
+
+    for (burst, span) in ret.iter_mut().zip(spans) {
+        burst.frequency = strongest_frequency(samples, span, bursts.width)?;
+    }
+
+    Ok(ret)
+}
+
+/// The centre of the loudest bin of the average spectrum of a range of the input,
+/// if it's long enough to take an fft of.
+fn strongest_frequency(
+    samples: &dyn Samples,
+    range: (u64, u64),
+    width: usize,
+) -> Result<Option<f64>> {
+    let slice = Slice::new(samples, range.0, range.1)?;
+    if slice.len() < MIN_FFT {
+        return Ok(None);
+    }
+
+    // bursts which are shorter than the requested fft get a narrower one
+    let mut width = width;
+    while u64_from(width) > slice.len() && u64_from(width) > MIN_FFT {
+        width /= 2;
+    }
+
+    let spectrum = welch(
+        &slice,
+        &FftConfig {
+            width,
+            windowing: Windowing::BlackmanHarris,
+        },
+        0.5,
+    )?;
+
+    let centre = samples.centre_frequency().unwrap_or(0) as f64;
+    Ok(spectrum
+        .bins
+        .iter()
+        .max_by(|a, b| a.dbfs.total_cmp(&b.dbfs))
+        .map(|bin| bin.frequency - centre))
+}
+
+pub fn print_bursts(samples: &dyn Samples, bursts: &Bursts) -> Result<()> {
+    let found = find_bursts(samples, bursts)?;
+    let sample_rate = samples.sample_rate() as f64;
+    let centre = samples.centre_frequency();
+    let resolution = bursts.block.min(1.);
+
+    println!(
+        "{:>4} {:>12} {:>12} {:>12} {:>8} {:>12}",
+        "#",
+        "start",
+        "end",
+        "length",
+        "peak",
+        if centre.is_some() {
+            "frequency"
+        } else {
+            "offset"
+        }
+    );
+
+    for (i, burst) in found.iter().enumerate() {
+        println!(
+            "{:>4} {:>12} {:>12} {:>12} {:>6.1}dB {:>12}",
+            i,
+            format_seconds(burst.start as f64 / sample_rate, resolution),
+            format_seconds(burst.end as f64 / sample_rate, resolution),
+            format_seconds((burst.end - burst.start) as f64 / sample_rate, resolution),
+            burst.peak_db,
+            burst
+                .frequency
+                .map(|frequency| format_si(frequency + centre.unwrap_or(0) as f64))
+                .unwrap_or_else(|| "-".to_string()),
+        );
+
+        if let Some(ref prefix) = bursts.write {
+            let mut slice = Slice::new(samples, burst.start, burst.end)?;
+            do_write(
+                &mut slice,
+                bursts.overwrite,
+                &format!("{}.off{}", prefix, burst.start),
+                bursts.format,
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn runs() {
+        use super::runs;
+        assert_eq!(vec![(1, 4), (7, 7)], runs(&[0, 1, 2, 0, 3, 0, 0, 1, 0]));
+        assert!(runs(&[0, 0]).is_empty());
+    }
+
+    #[test]
+    fn merged_bursts() {
+        use super::{find_bursts, Bursts};
+        use crate::{FileFormat, Samples};
+        use rustfft::num_complex::Complex;
+        use std::f64::consts::TAU;
+
+        struct Tones(Vec<Complex<f32>>);
+        impl Samples for Tones {
+            fn len(&self) -> u64 {
+                self.0.len() as u64
+            }
+            fn sample_rate(&self) -> u64 {
+                8_000
+            }
+            fn read_at(&self, off: u64, buf: &mut [Complex<f32>]) -> usize {
+                let from = &self.0[off as usize..];
+                let read = buf.len().min(from.len());
+                buf[..read].copy_from_slice(&from[..read]);
+                read
+            }
+        }
+
+        // a short burst at 1kHz, then a long one at -2kHz, which the padding joins up
+        let tone = |i: usize, frequency: f64| {
+            let phase = TAU * frequency * i as f64 / 8_000.;
+            Complex::new(phase.cos() as f32, phase.sin() as f32)
+        };
+        let samples = Tones(
+            (0..8_000)
+                .map(|i| match i {
+                    1_024..=1_407 => tone(i, 1_000.),
+                    1_600..=3_599 => tone(i, -2_000.),
+                    _ => Complex::new(0., 0.),
+                })
+                .collect(),
+        );
+
+        let found = find_bursts(
+            &samples,
+            &Bursts {
+                block: 0.001,
+                threshold: 10.,
+                hysteresis: 3.,
+                min_length: 0.,
+                pad: 0.05,
+                width: 1024,
+                write: None,
+                format: FileFormat::ComplexFloat32,
+                overwrite: false,
+            },
+        )
+        .unwrap();
+        assert_eq!(1, found.len(), "{:?}", found);
+        assert_eq!((624, 4_000), (found[0].start, found[0].end));
+
+        // from the whole of the merged burst, so the louder, later one
+        let frequency = found[0].frequency.unwrap();
+        assert!((frequency + 2_000.).abs() < 10., "{}", frequency);
+    }
+
+    #[test]
+    fn bursts_at_a_low_rate() {
+        use super::{find_bursts, Bursts, MIN_BLOCK};
+        use crate::samples::SampleFile;
+        use crate::{FileFormat, Samples};
+
+        let path = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/examples/cupboard-superdec.sr400.cf32"
+        );
+        let samples = SampleFile::new(
+            std::fs::File::open(path).unwrap(),
+            FileFormat::ComplexFloat32,
+            400,
+            None,
+        );
+
+        // a millisecond is less than one sample at 400Hz
+        let found = find_bursts(
+            &samples,
+            &Bursts {
+                block: 0.001,
+                threshold: 10.,
+                hysteresis: 3.,
+                min_length: 0.,
+                pad: 0.,
+                width: 1024,
+                write: None,
+                format: FileFormat::ComplexFloat32,
+                overwrite: false,
+            },
+        )
+        .unwrap();
+        assert!(!found.is_empty());
+        for burst in found {
+            assert!(burst.end - burst.start >= MIN_BLOCK);
+            assert!(burst.end <= samples.len());
+            let frequency = burst.frequency.unwrap();
+            assert!(frequency.abs() <= 200., "{}", frequency);
+        }
+    }
+}
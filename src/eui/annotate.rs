@@ -0,0 +1,311 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use egui::{Align2, Color32, FontId, Pos2, Rect, Stroke};
+
+use super::axes::Geometry;
+use super::pipeline;
+use super::view::View;
+use super::ManageApp;
+use crate::annotations::{self, Annotation};
+use crate::axis::{format_seconds, format_si};
+use crate::{Operation, Samples};
+
+/// How much of the view is around the annotation, after jumping to it.
+const JUMP_MARGIN: f64 = 0.5;
+
+const COLOUR: Color32 = Color32::from_rgb(80, 220, 255);
+
+#[derive(Default)]
+pub struct Annotations {
+    pub list: Vec<Annotation>,
+    /// The capture they belong to, if they can be saved beside it.
+    pub capture: Option<PathBuf>,
+    /// There are changes which aren't in the sidecar.
+    pub unsaved: bool,
+    /// The one being edited, which is drawn highlighted.
+    pub editing: Option<usize>,
+}
+
+impl ManageApp {
+    /// Read the sidecar for the source, if it's a file.
+    pub(super) fn load_annotations(&mut self) {
+        let capture = self.source.as_ref().and_then(|source| match &source.from {
+            Some(Operation::From { filename, .. }) => Some(PathBuf::from(filename)),
+            _ => None,
+        });
+
+        let list = match capture.as_deref().map(annotations::load) {
+            Some(Ok(list)) => list,
+            Some(Err(e)) => {
+                self.error = Some(format!("{:#}", e));
+                Vec::new()
+            }
+            None => Vec::new(),
+        };
+
+        self.annotations = Annotations {
+            list,
+            capture,
+            ..Annotations::default()
+        };
+    }
+
+    fn save_annotations(&mut self) {
+        let capture = match &self.annotations.capture {
+            Some(capture) => capture,
+            None => return,
+        };
+        match annotations::save(capture, &self.annotations.list) {
+            Ok(()) => self.annotations.unsaved = false,
+            Err(e) => self.error = Some(format!("{:#}", e)),
+        }
+    }
+
+    /// Annotations are stored relative to the capture, but the stages may have moved it.
+    fn annotation_offset(&self) -> f64 {
+        pipeline::frequency_offset(&self.stages)
+    }
+
+    /// Where an annotation is, in terms of the pipeline's output.
+    pub(super) fn annotation_box(&self, annotation: &Annotation) -> View {
+        let offset = self.annotation_offset();
+        View {
+            start: annotation.start,
+            end: annotation.end,
+            low: annotation.low + offset,
+            high: annotation.high + offset,
+        }
+    }
+
+    /// Make a new annotation of the selection, and start editing it.
+    pub(super) fn annotate(&mut self, selection: &View) {
+        let offset = self.annotation_offset();
+        self.annotations.list.push(Annotation {
+            start: selection.start,
+            end: selection.end,
+            low: selection.low - offset,
+            high: selection.high - offset,
+            label: format!("annotation {}", self.annotations.list.len() + 1),
+            comment: String::new(),
+        });
+        self.annotations.editing = Some(self.annotations.list.len() - 1);
+        self.annotations.unsaved = true;
+        self.show_annotations = true;
+    }
+
+    pub(super) fn paint_annotations(&self, painter: &egui::Painter, geometry: &Geometry) {
+        for (i, annotation) in self.annotations.list.iter().enumerate() {
+            let view = self.annotation_box(annotation);
+            let rect = Rect::from_min_max(
+                Pos2::new(geometry.x_of(view.low), geometry.y_of(view.start)),
+                Pos2::new(geometry.x_of(view.high), geometry.y_of(view.end)),
+            );
+            if !rect.intersects(geometry.plot) {
+                continue;
+            }
+            let width = if Some(i) == self.annotations.editing {
+                2.5
+            } else {
+                1.
+            };
+            painter.rect_stroke(
+                rect,
+                0.,
+                Stroke::new(width, COLOUR),
+                egui::StrokeKind::Middle,
+            );
+            // keep the label on screen while the top of the box is scrolled off
+            let corner = Pos2::new(
+                rect.left().max(geometry.plot.left()) + 2.,
+                rect.top().max(geometry.plot.top()) + 1.,
+            );
+            painter.text(
+                corner,
+                Align2::LEFT_TOP,
+                &annotation.label,
+                FontId::proportional(12.),
+                COLOUR,
+            );
+        }
+    }
+
+    /// The annotation which starts next (or previously), from the one being edited, or the view.
+    pub(super) fn neighbouring_annotation(&self, forwards: bool) -> Option<usize> {
+        let list = &self.annotations.list;
+        let (from, current) = match self.annotations.editing.and_then(|i| list.get(i)) {
+            Some(annotation) => (annotation.start, self.annotations.editing),
+            None => ((self.view.start + self.view.end) / 2., None),
+        };
+        let candidates = list
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| Some(*i) != current)
+            .filter(|(_, a)| {
+                if forwards {
+                    a.start >= from
+                } else {
+                    a.start <= from
+                }
+            });
+        if forwards {
+            candidates
+                .min_by(|(_, a), (_, b)| a.start.total_cmp(&b.start))
+                .map(|(i, _)| i)
+        } else {
+            candidates
+                .max_by(|(_, a), (_, b)| a.start.total_cmp(&b.start))
+                .map(|(i, _)| i)
+        }
+    }
+
+    /// Show an annotation, with some context around it, and start editing it.
+    pub(super) fn jump_to_annotation(&mut self, index: usize, samples: &Arc<dyn Samples>) {
+        let annotation = match self.annotations.list.get(index) {
+            Some(annotation) => annotation,
+            None => return,
+        };
+        let target = self.annotation_box(annotation);
+        let duration = target.end - target.start;
+        let bandwidth = target.high - target.low;
+        let view = View {
+            start: target.start - duration * JUMP_MARGIN,
+            end: target.end + duration * JUMP_MARGIN,
+            low: target.low - bandwidth * JUMP_MARGIN,
+            high: target.high + bandwidth * JUMP_MARGIN,
+        };
+        self.annotations.editing = Some(index);
+        self.remember_view(true);
+        self.set_view(view, samples);
+    }
+
+    /// The list of annotations, for the side panel.
+    pub(super) fn annotations_ui(&mut self, ui: &mut egui::Ui, samples: &Arc<dyn Samples>) {
+        ui.horizontal(|ui| {
+            ui.heading("Annotations");
+            if self.annotations.unsaved {
+                ui.label("•").on_hover_text("unsaved changes");
+            }
+        });
+
+        ui.horizontal(|ui| {
+            let saveable = self.annotations.capture.is_some();
+            if ui
+                .add_enabled(
+                    saveable && self.annotations.unsaved,
+                    egui::Button::new("Save"),
+                )
+                .on_disabled_hover_text(if saveable {
+                    "nothing has changed"
+                } else {
+                    "only files opened here have a sidecar"
+                })
+                .on_hover_text(
+                    self.annotations
+                        .capture
+                        .as_deref()
+                        .map(|capture| annotations::sidecar_path(capture).display().to_string())
+                        .unwrap_or_default(),
+                )
+                .clicked()
+            {
+                self.save_annotations();
+            }
+            if ui
+                .add_enabled(saveable, egui::Button::new("Reload"))
+                .on_hover_text("discard changes, and read the sidecar again")
+                .clicked()
+            {
+                self.load_annotations();
+            }
+
+            let previous = self.neighbouring_annotation(false);
+            let next = self.neighbouring_annotation(true);
+            if ui
+                .add_enabled(previous.is_some(), egui::Button::new("⏶"))
+                .on_hover_text("previous")
+                .clicked()
+            {
+                self.jump_to_annotation(previous.expect("enabled"), samples);
+            }
+            if ui
+                .add_enabled(next.is_some(), egui::Button::new("⏷"))
+                .on_hover_text("next")
+                .clicked()
+            {
+                self.jump_to_annotation(next.expect("enabled"), samples);
+            }
+        });
+
+        if self.annotations.list.is_empty() {
+            ui.label("None yet: shift+drag to select, then \"Annotate\".");
+            return;
+        }
+
+        // in terms of the capture, like the annotations themselves
+        let centre = self
+            .source
+            .as_ref()
+            .and_then(|source| source.samples.centre_frequency())
+            .unwrap_or(0) as f64;
+        let mut jump = None;
+        let mut remove = None;
+        let mut select = None;
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            for (i, annotation) in self.annotations.list.iter_mut().enumerate() {
+                let editing = Some(i) == self.annotations.editing;
+                let summary = format!(
+                    "{}\n{} to {}, {}Hz",
+                    annotation.label,
+                    format_seconds(annotation.start, 0.001),
+                    format_seconds(annotation.end, 0.001),
+                    format_si((annotation.low + annotation.high) / 2. + centre)
+                );
+                if ui.selectable_label(editing, summary).clicked() {
+                    jump = Some(i);
+                }
+                if !editing {
+                    continue;
+                }
+
+                egui::Frame::group(ui.style()).show(ui, |ui| {
+                    let changed = ui.text_edit_singleline(&mut annotation.label).changed()
+                        | ui.add(
+                            egui::TextEdit::multiline(&mut annotation.comment)
+                                .hint_text("comment")
+                                .desired_rows(3),
+                        )
+                        .changed();
+                    if changed {
+                        self.annotations.unsaved = true;
+                    }
+                    ui.horizontal(|ui| {
+                        if ui
+                            .button("Select")
+                            .on_hover_text("to export it, or look closer")
+                            .clicked()
+                        {
+                            select = Some(i);
+                        }
+                        if ui.button("🗑").on_hover_text("remove").clicked() {
+                            remove = Some(i);
+                        }
+                    });
+                });
+            }
+        });
+
+        if let Some(i) = jump {
+            self.jump_to_annotation(i, samples);
+        }
+        if let Some(i) = select {
+            self.selection = Some(self.annotation_box(&self.annotations.list[i]));
+            self.export.outcome = None;
+        }
+        if let Some(i) = remove {
+            self.annotations.list.remove(i);
+            self.annotations.editing = None;
+            self.annotations.unsaved = true;
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use egui::{Align2, Color32, FontId, Pos2, Rect, Stroke};
+
+use crate::axis::{format_seconds, format_si, format_si_exact, ticks};
+
+/// Space around the waterfall for the labels.
+const MARGIN_LEFT: f32 = 72.;
+const MARGIN_TOP: f32 = 18.;
+const TICK_LENGTH: f32 = 4.;
+
+/// Where the waterfall is on screen, and what it covers.
+#[derive(Copy, Clone, Debug)]
+pub struct Geometry {
+    pub plot: Rect,
+
+    /// Relative to the centre, at the left and right edges.
+    pub frequencies: (f64, f64),
+
+    /// Seconds from the start of the samples, at the top and bottom edges.
+    pub times: (f64, f64),
+
+    /// What to add to relative frequencies for display, if anything.
+    pub centre: Option<f64>,
+}
+
+impl Geometry {
+    /// `outer` with room taken for the labels.
+    pub fn plot_area(outer: Rect) -> Rect {
+        Rect::from_min_max(outer.min + egui::vec2(MARGIN_LEFT, MARGIN_TOP), outer.max)
+    }
+
+    pub fn frequency_at(&self, x: f32) -> f64 {
+        let (left, right) = self.frequencies;
+        left + f64::from((x - self.plot.left()) / self.plot.width()) * (right - left)
+    }
+
+    pub fn time_at(&self, y: f32) -> f64 {
+        let (top, bottom) = self.times;
+        top + f64::from((y - self.plot.top()) / self.plot.height()) * (bottom - top)
+    }
+
+    pub fn x_of(&self, frequency: f64) -> f32 {
+        let (left, right) = self.frequencies;
+        self.plot.left() + ((frequency - left) / (right - left)) as f32 * self.plot.width()
+    }
+
+    pub fn y_of(&self, time: f64) -> f32 {
+        let (top, bottom) = self.times;
+        self.plot.top() + ((time - top) / (bottom - top)) as f32 * self.plot.height()
+    }
+
+    /// Seconds covered by one pixel, for choosing how precisely to print times.
+    pub fn time_resolution(&self) -> f64 {
+        (self.times.1 - self.times.0) / f64::from(self.plot.height())
+    }
+
+    /// A frequency for display: absolute, if we know where we are.
+    pub fn display_frequency(&self, relative: f64) -> f64 {
+        relative + self.centre.unwrap_or(0.)
+    }
+
+    pub fn paint(&self, painter: &egui::Painter, colour: Color32) {
+        let font = FontId::proportional(11.);
+        let stroke = Stroke::new(1., colour);
+
+        let (left, right) = self.frequencies;
+        let offset = self.centre.unwrap_or(0.);
+        let max_ticks = (self.plot.width() / 90.).max(2.) as usize;
+        for tick in ticks(left + offset, right + offset, max_ticks) {
+            let x = self.x_of(tick - offset);
+            let top = self.plot.top();
+            painter.line_segment([Pos2::new(x, top - TICK_LENGTH), Pos2::new(x, top)], stroke);
+            painter.text(
+                Pos2::new(x, top - TICK_LENGTH),
+                Align2::CENTER_BOTTOM,
+                format_si(tick),
+                font.clone(),
+                colour,
+            );
+        }
+
+        let (top, bottom) = self.times;
+        let max_ticks = (self.plot.height() / 40.).max(2.) as usize;
+        let times = ticks(top, bottom, max_ticks);
+        let step = match times.as_slice() {
+            [a, b, ..] => b - a,
+            _ => bottom - top,
+        };
+        for tick in times {
+            let y = self.y_of(tick);
+            let left = self.plot.left();
+            painter.line_segment(
+                [Pos2::new(left - TICK_LENGTH, y), Pos2::new(left, y)],
+                stroke,
+            );
+            painter.text(
+                Pos2::new(left - TICK_LENGTH - 2., y),
+                Align2::RIGHT_CENTER,
+                format_seconds(tick, step),
+                font.clone(),
+                colour,
+            );
+        }
+    }
+}
+
+/// The `shift` argument which would move `frequency` (relative) to zero.
+pub fn shift_argument(frequency: f64) -> String {
+    format!("shift {}", format_si_exact(-frequency.round() as i64))
+}
+
+/// The `slice` argument which would start (or end) at `time`.
+pub fn slice_argument(flag: &str, time: f64, resolution: f64) -> String {
+    format!(
+        "slice -{} {}",
+        flag,
+        format_seconds(time.max(0.), resolution)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn shift_argument_parses() {
+        use super::shift_argument;
+        use crate::args::{parse, Command};
+        use crate::Operation;
+
+        let copied = shift_argument(5_000.);
+        assert_eq!("shift -5k", copied);
+        let args: Vec<String> = copied.split(' ').map(|s| s.to_string()).collect();
+        match parse(args.iter()).unwrap().as_slice() {
+            [Command::Octagon(Operation::Shift { frequency })] => assert_eq!(-5_000, *frequency),
+            _ => panic!("not a shift"),
+        }
+    }
+}
@@ -0,0 +1,293 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use poll_promise::Promise;
+
+use super::view::View;
+use super::ManageApp;
+use crate::annotations::cut;
+use crate::args::command_line;
+use crate::axis::{format_seconds, format_si};
+use crate::{do_write, FileFormat, Operation, Samples};
+
+pub struct ExportSettings {
+    pub format: FileFormat,
+    /// `None` to generate one from the selection.
+    pub prefix: Option<String>,
+    pub overwrite: bool,
+
+    pub running: Option<Promise<Result<String, String>>>,
+    pub outcome: Option<Result<String, String>>,
+}
+
+impl Default for ExportSettings {
+    fn default() -> Self {
+        ExportSettings {
+            format: FileFormat::ComplexFloat32,
+            prefix: None,
+            overwrite: false,
+            running: None,
+            outcome: None,
+        }
+    }
+}
+
+/// The slice, shift and lowpass which cut out the selection, at the lowest reasonable rate.
+pub fn operations(selection: &View, sample_rate: u64) -> Vec<Operation> {
+    cut(
+        selection.start,
+        selection.end,
+        selection.low,
+        selection.high,
+        sample_rate,
+    )
+}
+
+/// A name for the output, from the input's, and where the selection is.
+fn default_prefix(source: &str, selection: &View, centre: Option<u64>) -> String {
+    let path = Path::new(source);
+    let mut stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_else(|| "selection".to_string());
+    if let Some(rate) = stem.rfind(".sr") {
+        stem.truncate(rate);
+    }
+    let stem = match path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        Some(dir) => dir.join(stem).to_string_lossy().to_string(),
+        None => stem,
+    };
+
+    let frequency = (selection.low + selection.high) / 2. + centre.unwrap_or(0) as f64;
+    format!(
+        "{}.{}-{}.{}Hz",
+        stem,
+        format_seconds(selection.start, 0.001),
+        format_seconds(selection.end, 0.001),
+        format_si(frequency)
+    )
+}
+
+impl ManageApp {
+    /// The selection, and how to export it, if there is one.
+    pub(super) fn export_ui(&mut self, ctx: &egui::Context, samples: &Arc<dyn Samples>) {
+        let selection = match self.selection {
+            Some(selection) => selection,
+            None => return,
+        };
+
+        if let Some(running) = self.export.running.as_ref() {
+            if running.ready().is_some() {
+                self.export.outcome = self.export.running.take().and_then(|p| p.try_take().ok());
+            }
+        }
+
+        let sample_rate = samples.sample_rate();
+        let centre = samples.centre_frequency();
+        let source_name = self
+            .source
+            .as_ref()
+            .map(|source| source.name.clone())
+            .unwrap_or_default();
+        let prefix = self
+            .export
+            .prefix
+            .clone()
+            .unwrap_or_else(|| default_prefix(&source_name, &selection, centre));
+
+        let cut = operations(&selection, sample_rate);
+        let write = Operation::Write {
+            overwrite: self.export.overwrite,
+            prefix: prefix.clone(),
+            format: self.export.format,
+        };
+
+        // the whole thing, from the start, as far as we can tell
+        let pipeline = self
+            .pipeline_commands()
+            .into_iter()
+            .chain(
+                cut.iter()
+                    .chain(Some(&write))
+                    .map(|op| command_line(op).unwrap_or_else(|| "…".to_string())),
+            )
+            .collect::<Vec<_>>()
+            .join(" \\\n    ");
+
+        let mut open = true;
+        let mut clear = false;
+        let mut annotate = false;
+        egui::Window::new("Selection")
+            .open(&mut open)
+            .default_width(420.)
+            .show(ctx, |ui| {
+                egui::Grid::new("selection").num_columns(2).show(ui, |ui| {
+                    ui.label("time");
+                    ui.label(format!(
+                        "{} to {} ({})",
+                        format_seconds(selection.start, 1e-6),
+                        format_seconds(selection.end, 1e-6),
+                        format_seconds(selection.end - selection.start, 1e-6)
+                    ));
+                    ui.end_row();
+
+                    ui.label("frequency");
+                    ui.label(format!(
+                        "{}Hz to {}Hz ({}Hz wide)",
+                        format_si(selection.low + centre.unwrap_or(0) as f64),
+                        format_si(selection.high + centre.unwrap_or(0) as f64),
+                        format_si(selection.high - selection.low)
+                    ));
+                    ui.end_row();
+
+                    self.measurement_ui(ui, &selection, samples);
+
+                    ui.label("format");
+                    egui::ComboBox::from_id_salt("export format")
+                        .selected_text(self.export.format.extension())
+                        .show_ui(ui, |ui| {
+                            for format in FileFormat::ALL {
+                                ui.selectable_value(
+                                    &mut self.export.format,
+                                    format,
+                                    format.extension(),
+                                );
+                            }
+                        });
+                    ui.end_row();
+
+                    ui.label("prefix");
+                    ui.horizontal(|ui| {
+                        let mut edited = prefix.clone();
+                        if ui.text_edit_singleline(&mut edited).changed() {
+                            self.export.prefix = Some(edited);
+                        }
+                        if ui
+                            .add_enabled(self.export.prefix.is_some(), egui::Button::new("⟲"))
+                            .on_hover_text("generate from the selection")
+                            .clicked()
+                        {
+                            self.export.prefix = None;
+                        }
+                    });
+                    ui.end_row();
+
+                    ui.label("");
+                    ui.checkbox(&mut self.export.overwrite, "overwrite");
+                    ui.end_row();
+                });
+
+                ui.separator();
+                ui.label("equivalent to:");
+                ui.horizontal(|ui| {
+                    ui.add(egui::Label::new(egui::RichText::new(&pipeline).monospace()).wrap());
+                    if ui.small_button("📋").on_hover_text("copy").clicked() {
+                        ui.ctx().copy_text(pipeline.clone());
+                    }
+                });
+                ui.separator();
+
+                ui.horizontal(|ui| {
+                    let running = self.export.running.is_some();
+                    if ui
+                        .add_enabled(!running, egui::Button::new("Export"))
+                        .clicked()
+                    {
+                        self.export.outcome = None;
+                        self.export.running = Some(run(
+                            Arc::clone(samples),
+                            cut.clone(),
+                            self.export.overwrite,
+                            prefix.clone(),
+                            self.export.format,
+                        ));
+                    }
+                    if running {
+                        ui.spinner();
+                    }
+                    if ui.button("Clear selection").clicked() {
+                        clear = true;
+                    }
+                    if ui.button("Annotate").clicked() {
+                        annotate = true;
+                    }
+                    ui.toggle_value(&mut self.show_time_domain, "Time domain");
+                    ui.toggle_value(&mut self.symbols.show, "Symbols");
+                });
+
+                match &self.export.outcome {
+                    Some(Ok(filename)) => {
+                        ui.label(format!("wrote {}", filename));
+                    }
+                    Some(Err(e)) => {
+                        ui.colored_label(ui.visuals().error_fg_color, e);
+                    }
+                    None => (),
+                }
+            });
+
+        if annotate {
+            self.annotate(&selection);
+        }
+
+        if !open || clear {
+            self.selection = None;
+            self.export.outcome = None;
+        }
+    }
+}
+
+fn run(
+    samples: Arc<dyn Samples>,
+    operations: Vec<Operation>,
+    overwrite: bool,
+    prefix: String,
+    format: FileFormat,
+) -> Promise<Result<String, String>> {
+    Promise::spawn_thread("export", move || {
+        let mut samples: Box<dyn Samples> = Box::new(samples);
+        for op in &operations {
+            samples = op
+                .exec(Some(samples))
+                .map_err(|e| format!("{:#}", e))?
+                .ok_or_else(|| "no output".to_string())?;
+        }
+        do_write(&mut samples, overwrite, &prefix, format).map_err(|e| format!("{:#}", e))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{default_prefix, operations};
+    use crate::eui::view::View;
+    use crate::Operation;
+
+    #[test]
+    fn derived() {
+        let selection = View {
+            start: 1.5,
+            end: 2.,
+            low: 100_000.,
+            high: 140_000.,
+        };
+        let ops = operations(&selection, 2_000_000);
+        match ops.as_slice() {
+            [Operation::Slice { start, end }, Operation::Shift { frequency }, Operation::LowPass {
+                decimate,
+                frequency: cutoff,
+                ..
+            }] => {
+                assert_eq!((Some(1.5), Some(2.)), (*start, *end));
+                assert_eq!(-120_000, *frequency);
+                assert_eq!(20_000, *cutoff);
+                assert_eq!(40, *decimate);
+            }
+            other => panic!("unexpected: {:?}", other),
+        }
+
+        assert_eq!(
+            "caps/fsk.1.500s-2.000s.433.04MHz",
+            default_prefix("caps/fsk.sr2M.cu8", &selection, Some(432_920_000))
+        );
+    }
+}
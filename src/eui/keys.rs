@@ -0,0 +1,412 @@
+use std::ops::RangeInclusive;
+use std::sync::Arc;
+use std::time::Duration;
+
+use egui::{Key, KeyboardShortcut, Modifiers};
+use poll_promise::Promise;
+
+use super::view::View;
+use super::{take_ready, Layout, ManageApp, FFT_POWERS};
+use crate::detect::{find_bursts, Burst, Bursts};
+use crate::ffts::Scale;
+use crate::{FileFormat, Samples};
+
+/// How far the arrow keys move, as a fraction of the view.
+const STEP: f64 = 0.1;
+
+/// How much one press of a zoom key zooms.
+const ZOOM: f64 = 0.5;
+
+/// Where a burst which has been jumped to starts, as a fraction of the view from the top.
+const BURST_LEAD: f64 = 0.1;
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Action {
+    Help,
+    NextTab,
+    PreviousTab,
+    PageForward,
+    PageBack,
+    StepForward,
+    StepBack,
+    StepUp,
+    StepDown,
+    ZoomInTime,
+    ZoomOutTime,
+    ZoomInFrequency,
+    ZoomOutFrequency,
+    Start,
+    End,
+    WholeFile,
+    Back,
+    BiggerFft,
+    SmallerFft,
+    NextColormap,
+    PreviousColormap,
+    ToggleScale,
+    ToggleLayout,
+    NextBurst,
+    PreviousBurst,
+    NextAnnotation,
+    PreviousAnnotation,
+    ClearSelection,
+}
+
+impl Action {
+    pub fn describe(&self) -> &'static str {
+        match self {
+            Action::Help => "show or hide this",
+            Action::NextTab => "next tab",
+            Action::PreviousTab => "previous tab",
+            Action::PageForward => "forwards a whole view",
+            Action::PageBack => "back a whole view",
+            Action::StepForward => "forwards a little",
+            Action::StepBack => "back a little",
+            Action::StepUp => "up in frequency",
+            Action::StepDown => "down in frequency",
+            Action::ZoomInTime => "zoom in, in time",
+            Action::ZoomOutTime => "zoom out, in time",
+            Action::ZoomInFrequency => "zoom in, in frequency",
+            Action::ZoomOutFrequency => "zoom out, in frequency",
+            Action::Start => "the start of the file",
+            Action::End => "the end of the file",
+            Action::WholeFile => "the whole file",
+            Action::Back => "undo the last zoom or pan",
+            Action::BiggerFft => "double the fft width",
+            Action::SmallerFft => "halve the fft width",
+            Action::NextColormap => "next colormap",
+            Action::PreviousColormap => "previous colormap",
+            Action::ToggleScale => "dB or linear",
+            Action::ToggleLayout => "waterfall or wrapped rows",
+            Action::NextBurst => "next burst",
+            Action::PreviousBurst => "previous burst",
+            Action::NextAnnotation => "next annotation",
+            Action::PreviousAnnotation => "previous annotation",
+            Action::ClearSelection => "clear the selection, or close this",
+        }
+    }
+}
+
+/// Every binding, in the order they're checked; with shift before without, as shift is
+/// otherwise ignored, and in the order they're listed in the help.
+pub const BINDINGS: &[(Modifiers, Key, Action)] = &[
+    (Modifiers::NONE, Key::Questionmark, Action::Help),
+    (Modifiers::NONE, Key::F1, Action::Help),
+    (Modifiers::COMMAND, Key::PageDown, Action::NextTab),
+    (Modifiers::COMMAND, Key::PageUp, Action::PreviousTab),
+    (Modifiers::SHIFT, Key::Space, Action::PageBack),
+    (Modifiers::NONE, Key::PageDown, Action::PageForward),
+    (Modifiers::NONE, Key::Space, Action::PageForward),
+    (Modifiers::NONE, Key::PageUp, Action::PageBack),
+    (Modifiers::NONE, Key::ArrowDown, Action::StepForward),
+    (Modifiers::NONE, Key::ArrowUp, Action::StepBack),
+    (Modifiers::NONE, Key::ArrowRight, Action::StepUp),
+    (Modifiers::NONE, Key::ArrowLeft, Action::StepDown),
+    (Modifiers::NONE, Key::Plus, Action::ZoomInTime),
+    (Modifiers::NONE, Key::Equals, Action::ZoomInTime),
+    (Modifiers::NONE, Key::Minus, Action::ZoomOutTime),
+    (Modifiers::NONE, Key::CloseBracket, Action::ZoomInFrequency),
+    (Modifiers::NONE, Key::OpenBracket, Action::ZoomOutFrequency),
+    (Modifiers::NONE, Key::Home, Action::Start),
+    (Modifiers::NONE, Key::End, Action::End),
+    (Modifiers::NONE, Key::Num0, Action::WholeFile),
+    (Modifiers::NONE, Key::Backspace, Action::Back),
+    (Modifiers::SHIFT, Key::F, Action::SmallerFft),
+    (Modifiers::NONE, Key::F, Action::BiggerFft),
+    (Modifiers::SHIFT, Key::C, Action::PreviousColormap),
+    (Modifiers::NONE, Key::C, Action::NextColormap),
+    (Modifiers::NONE, Key::S, Action::ToggleScale),
+    (Modifiers::NONE, Key::W, Action::ToggleLayout),
+    (Modifiers::SHIFT, Key::B, Action::PreviousBurst),
+    (Modifiers::NONE, Key::B, Action::NextBurst),
+    (Modifiers::SHIFT, Key::A, Action::PreviousAnnotation),
+    (Modifiers::NONE, Key::A, Action::NextAnnotation),
+    (Modifiers::NONE, Key::Escape, Action::ClearSelection),
+];
+
+/// What's been pressed this frame, unless something else wants the keyboard.
+pub fn pressed(ctx: &egui::Context) -> Vec<Action> {
+    if ctx.wants_keyboard_input() {
+        return Vec::new();
+    }
+    ctx.input_mut(|input| {
+        BINDINGS
+            .iter()
+            .filter(|(modifiers, key, _)| input.consume_key(*modifiers, *key))
+            .map(|(_, _, action)| *action)
+            .collect()
+    })
+}
+
+/// Every binding, grouped by what it does.
+pub fn help_ui(ctx: &egui::Context, open: &mut bool) {
+    egui::Window::new("Keyboard")
+        .open(open)
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+        .show(ctx, |ui| {
+            egui::Grid::new("keys").num_columns(2).show(ui, |ui| {
+                let mut shown: Vec<Action> = Vec::new();
+                for (_, _, action) in BINDINGS {
+                    if shown.contains(action) {
+                        continue;
+                    }
+                    shown.push(*action);
+                    let keys = BINDINGS
+                        .iter()
+                        .filter(|(_, _, a)| a == action)
+                        .map(|(modifiers, key, _)| {
+                            ctx.format_shortcut(&KeyboardShortcut::new(*modifiers, *key))
+                        })
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    ui.monospace(keys);
+                    ui.label(action.describe());
+                    ui.end_row();
+                }
+            });
+        });
+}
+
+/// The next power of two along, within the powers on offer.
+fn step_power(width: usize, bigger: bool, powers: RangeInclusive<u32>) -> usize {
+    let power = width.trailing_zeros();
+    let power = if bigger {
+        power + 1
+    } else {
+        power.saturating_sub(1)
+    };
+    1 << power.clamp(*powers.start(), *powers.end())
+}
+
+/// The bursts in the samples, found the first time they're jumped to, and
+/// looked for again on the next jump if that failed.
+#[derive(Default)]
+pub struct BurstJumps {
+    found: Option<Vec<Burst>>,
+    finding: Option<Promise<Result<Vec<Burst>, String>>>,
+    /// Which way to go once they're found: forwards, or not.
+    pending: Option<bool>,
+    /// The burst last jumped to, and the view that gave, so repeated jumps step through them.
+    last: Option<(usize, View)>,
+}
+
+impl ManageApp {
+    /// Do what was pressed, unless it's for the workspace.
+    pub(super) fn act(&mut self, action: Action, samples: &Arc<dyn Samples>) {
+        let mut view = self.view;
+        let duration = view.end - view.start;
+        let bandwidth = view.high - view.low;
+        let whole = View::whole(samples.as_ref());
+        match action {
+            Action::PageForward => view.pan(duration, 0.),
+            Action::PageBack => view.pan(-duration, 0.),
+            Action::StepForward => view.pan(duration * STEP, 0.),
+            Action::StepBack => view.pan(-duration * STEP, 0.),
+            Action::StepUp => view.pan(0., bandwidth * STEP),
+            Action::StepDown => view.pan(0., -bandwidth * STEP),
+            Action::ZoomInTime => view.zoom_time(ZOOM, view.start + duration / 2.),
+            Action::ZoomOutTime => view.zoom_time(1. / ZOOM, view.start + duration / 2.),
+            Action::ZoomInFrequency => view.zoom_frequency(ZOOM, view.low + bandwidth / 2.),
+            Action::ZoomOutFrequency => view.zoom_frequency(1. / ZOOM, view.low + bandwidth / 2.),
+            Action::Start => view.pan(whole.start - view.start, 0.),
+            Action::End => view.pan(whole.end - view.end, 0.),
+            Action::WholeFile => view = whole,
+            Action::Back => {
+                if let Some(previous) = self.history.pop() {
+                    self.set_view(previous, samples);
+                }
+                return;
+            }
+            Action::BiggerFft | Action::SmallerFft => {
+                let bigger = Action::BiggerFft == action;
+                match self.layout {
+                    Layout::Waterfall => {
+                        self.fft.width = step_power(self.fft.width, bigger, FFT_POWERS);
+                        self.invalidate();
+                    }
+                    Layout::Wrapped => {
+                        let settings = &mut self.wrapped.settings;
+                        settings.fft_width =
+                            step_power(settings.fft_width, bigger, super::wrapped::FFT_POWERS);
+                    }
+                }
+                return;
+            }
+            Action::NextColormap | Action::PreviousColormap => {
+                self.colormap = self.colormap.cycle(Action::NextColormap == action);
+                self.recolour = true;
+                return;
+            }
+            Action::ToggleScale => {
+                self.scale = match self.scale {
+                    Scale::Decibels => Scale::Linear,
+                    Scale::Linear => Scale::Decibels,
+                };
+                if self.auto_levels {
+                    self.auto_level();
+                }
+                self.recolour = true;
+                return;
+            }
+            Action::ToggleLayout => {
+                self.layout = match self.layout {
+                    Layout::Waterfall => Layout::Wrapped,
+                    Layout::Wrapped => Layout::Waterfall,
+                };
+                return;
+            }
+            Action::NextBurst | Action::PreviousBurst => {
+                self.jump_to_burst(Action::NextBurst == action, samples);
+                return;
+            }
+            Action::NextAnnotation | Action::PreviousAnnotation => {
+                if let Some(i) = self.neighbouring_annotation(Action::NextAnnotation == action) {
+                    self.jump_to_annotation(i, samples);
+                }
+                return;
+            }
+            Action::ClearSelection => {
+                self.selection = None;
+                return;
+            }
+            Action::Help | Action::NextTab | Action::PreviousTab => return,
+        }
+        if view != self.view {
+            self.remember_view(true);
+            self.set_view(view, samples);
+        }
+    }
+
+    /// Pick up the bursts, if they've been found, and go to one if that was asked for.
+    pub(super) fn poll_bursts(&mut self, ctx: &egui::Context, samples: &Arc<dyn Samples>) {
+        match take_ready(&mut self.bursts.finding) {
+            Some(Ok(found)) => {
+                self.bursts.found = Some(found);
+                if let Some(forwards) = self.bursts.pending.take() {
+                    self.jump_to_burst(forwards, samples);
+                }
+            }
+            Some(Err(e)) => {
+                self.bursts.pending = None;
+                self.error = Some(format!("finding bursts: {}", e));
+            }
+            None => (),
+        }
+        if self.bursts.finding.is_some() {
+            ctx.request_repaint_after(Duration::from_millis(50));
+        }
+    }
+
+    /// Show the next (or previous) burst, near the top of the view, looking for them first.
+    fn jump_to_burst(&mut self, forwards: bool, samples: &Arc<dyn Samples>) {
+        let found = match &self.bursts.found {
+            Some(found) => found,
+            None => {
+                self.bursts.pending = Some(forwards);
+                if self.bursts.finding.is_none() {
+                    let samples = Arc::clone(samples);
+                    self.bursts.finding = Some(Promise::spawn_thread("bursts", move || {
+                        find_bursts(samples.as_ref(), &default_bursts())
+                            .map_err(|e| format!("{:#}", e))
+                    }));
+                }
+                return;
+            }
+        };
+
+        let sample_rate = samples.sample_rate() as f64;
+        let duration = self.view.end - self.view.start;
+        let index = match self.bursts.last.filter(|(_, view)| *view == self.view) {
+            Some((last, _)) if forwards => Some(last + 1).filter(|&i| i < found.len()),
+            Some((last, _)) => last.checked_sub(1),
+            None => {
+                let anchor = ((self.view.start + duration * BURST_LEAD) * sample_rate) as u64;
+                if forwards {
+                    found.iter().position(|burst| burst.start > anchor)
+                } else {
+                    found.iter().rposition(|burst| burst.start < anchor)
+                }
+            }
+        };
+        let burst = match index.and_then(|i| found.get(i)) {
+            Some(burst) => burst,
+            None => return,
+        };
+
+        let start = burst.start as f64 / sample_rate;
+        let length = (burst.end - burst.start) as f64 / sample_rate;
+        let duration = duration.max(length / (1. - 2. * BURST_LEAD));
+        let mut view = self.view;
+        view.start = start - duration * BURST_LEAD;
+        view.end = view.start + duration;
+        self.remember_view(true);
+        self.set_view(view, samples);
+        self.bursts.last = index.map(|i| (i, self.view));
+    }
+}
+
+/// As the `bursts` command does, without any flags.
+fn default_bursts() -> Bursts {
+    Bursts {
+        block: 0.001,
+        threshold: 10.,
+        hysteresis: 3.,
+        min_length: 0.,
+        pad: 0.,
+        width: 1024,
+        write: None,
+        format: FileFormat::ComplexFloat32,
+        overwrite: false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{step_power, Action, BINDINGS};
+    use crate::colormap::Colormap;
+
+    #[test]
+    fn bindings() {
+        assert_eq!(1_024, step_power(512, true, 4..=16));
+        assert_eq!(16, step_power(16, false, 4..=16));
+        assert_eq!(1 << 16, step_power(1 << 16, true, 4..=16));
+
+        assert_eq!(Colormap::Inferno, Colormap::Viridis.cycle(true));
+        assert_eq!(Colormap::Grayscale, Colormap::Viridis.cycle(false));
+
+        // shift is ignored when matching, so must come first
+        for (i, (modifiers, key, _)) in BINDINGS.iter().enumerate() {
+            if modifiers.shift {
+                continue;
+            }
+            assert!(
+                !BINDINGS[i..].iter().any(|(m, k, _)| m.shift && k == key),
+                "{:?} is shadowed",
+                key
+            );
+        }
+        assert!(BINDINGS.iter().any(|(_, _, a)| Action::Help == *a));
+    }
+
+    #[test]
+    fn bursts_at_a_low_rate() {
+        use super::default_bursts;
+        use crate::detect::find_bursts;
+        use crate::samples::SampleFile;
+        use crate::FileFormat;
+
+        let path = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/examples/cupboard-superdec.sr400.cf32"
+        );
+        let samples = SampleFile::new(
+            std::fs::File::open(path).unwrap(),
+            FileFormat::ComplexFloat32,
+            400,
+            None,
+        );
+        assert!(!find_bursts(&samples, &default_bursts()).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,296 @@
+use std::sync::Arc;
+
+use egui::{Align2, Color32, FontId, Pos2, Stroke};
+use poll_promise::Promise;
+use serde::{Deserialize, Serialize};
+
+use super::axes::Geometry;
+use super::view::View;
+use super::ManageApp;
+use crate::axis::{format_seconds, format_si};
+use crate::ffts::{FftConfig, Windowing};
+use crate::psd::{band_power, welch, BandPower};
+use crate::{Operation, Samples};
+
+/// Measuring more than this much of a selection is slow, and rarely changes the answer.
+const MAX_SAMPLES: u64 = 1 << 24;
+
+/// Roughly how many bins the selection's band should span.
+const BAND_BINS: f64 = 64.;
+const MIN_WIDTH: usize = 64;
+const MAX_WIDTH: usize = 1 << 16;
+
+const COLOUR: Color32 = Color32::from_rgb(255, 120, 200);
+
+/// A point on the waterfall: seconds, and Hz relative to the centre.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Marker {
+    pub time: f64,
+    pub frequency: f64,
+}
+
+/// The selection, and the window it was measured with.
+type Key = (View, Windowing);
+
+/// The power in the selection, and how it was found.
+pub struct Measured {
+    pub band: BandPower,
+    pub width: usize,
+    pub resolution: f64,
+    /// Only the start of the selection was measured.
+    pub truncated: bool,
+}
+
+#[derive(Default)]
+pub struct Markers {
+    pub list: Vec<Marker>,
+    pub show: bool,
+    pub measurement: Option<(Key, Promise<Result<Measured, String>>)>,
+}
+
+/// The fft width which puts about `BAND_BINS` bins across the band, if there's room.
+fn width_for(sample_rate: u64, bandwidth: f64, samples: u64) -> usize {
+    let wanted = (sample_rate as f64 / bandwidth.max(1.) * BAND_BINS).min(MAX_WIDTH as f64);
+    let width = (wanted.max(1.) as usize)
+        .next_power_of_two()
+        .clamp(MIN_WIDTH, MAX_WIDTH);
+    // at least a few ffts to average
+    let room = (samples / 4).max(1);
+    let room = 1 << (63 - room.leading_zeros());
+    width.min(room as usize)
+}
+
+/// Channel power and occupied bandwidth of the selection, from the samples themselves.
+fn measure(
+    samples: Arc<dyn Samples>,
+    selection: View,
+    windowing: Windowing,
+) -> Result<Measured, String> {
+    let sample_rate = samples.sample_rate();
+    let limit = selection.start + MAX_SAMPLES as f64 / sample_rate as f64;
+    let slice = Operation::Slice {
+        start: Some(selection.start.max(0.)),
+        end: Some(selection.end.min(limit)),
+    };
+    let sliced = slice
+        .exec(Some(Box::new(samples)))
+        .map_err(|e| format!("{:#}", e))?
+        .ok_or_else(|| "no output".to_string())?;
+
+    let width = width_for(sample_rate, selection.high - selection.low, sliced.len());
+    if (sliced.len() as usize) < width.max(MIN_WIDTH) {
+        return Err("the selection is too short to measure".to_string());
+    }
+    let spectrum = welch(sliced.as_ref(), &FftConfig { width, windowing }, 0.5)
+        .map_err(|e| format!("{:#}", e))?;
+
+    // the bins are absolute, if the centre is known
+    let centre = sliced.centre_frequency().unwrap_or(0) as f64;
+    let band = band_power(
+        &spectrum,
+        selection.low + centre,
+        selection.high + centre,
+        f64::from(windowing.noise_bandwidth(width)),
+    )
+    .ok_or_else(|| "the selection is narrower than a bin".to_string())?;
+
+    Ok(Measured {
+        band,
+        width,
+        resolution: sample_rate as f64 / width as f64,
+        truncated: selection.end > limit,
+    })
+}
+
+/// The difference between two markers, as text.
+fn delta(from: &Marker, to: &Marker) -> String {
+    let dt = to.time - from.time;
+    let rate = if dt.abs() > f64::EPSILON {
+        format!(", 1/Δt {}Hz", format_si(1. / dt.abs()))
+    } else {
+        String::new()
+    };
+    format!(
+        "Δt {}, Δf {}Hz{}",
+        format_seconds(dt, 1e-6),
+        format_si(to.frequency - from.frequency),
+        rate
+    )
+}
+
+impl ManageApp {
+    pub(super) fn place_marker(&mut self, time: f64, frequency: f64) {
+        self.markers.list.push(Marker { time, frequency });
+        self.markers.show = true;
+    }
+
+    pub(super) fn paint_markers(&self, painter: &egui::Painter, geometry: &Geometry) {
+        let stroke = Stroke::new(1., COLOUR.gamma_multiply(0.7));
+        for (i, marker) in self.markers.list.iter().enumerate() {
+            let pos = Pos2::new(geometry.x_of(marker.frequency), geometry.y_of(marker.time));
+            painter.hline(geometry.plot.x_range(), pos.y, stroke);
+            painter.vline(pos.x, geometry.plot.y_range(), stroke);
+            painter.circle_stroke(pos, 4., Stroke::new(1.5, COLOUR));
+            painter.text(
+                pos + egui::vec2(5., 2.),
+                Align2::LEFT_TOP,
+                format!("M{}", i + 1),
+                FontId::proportional(12.),
+                COLOUR,
+            );
+        }
+    }
+
+    /// The distance from the last marker to a point, for the readout.
+    pub(super) fn marker_delta(&self, time: f64, frequency: f64) -> Option<String> {
+        let last = self.markers.list.last()?;
+        Some(format!(
+            "from M{}: {}",
+            self.markers.list.len(),
+            delta(last, &Marker { time, frequency })
+        ))
+    }
+
+    /// Each marker, and how far it is from the one before.
+    pub(super) fn markers_ui(&mut self, ctx: &egui::Context, samples: &Arc<dyn Samples>) {
+        if self.markers.list.is_empty() {
+            return;
+        }
+        let centre = samples
+            .centre_frequency()
+            .filter(|_| self.absolute)
+            .unwrap_or(0) as f64;
+
+        let mut open = self.markers.show;
+        let mut remove = None;
+        let mut clear = false;
+        egui::Window::new("Markers")
+            .open(&mut open)
+            .default_width(360.)
+            .show(ctx, |ui| {
+                egui::Grid::new("markers")
+                    .num_columns(4)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for (i, marker) in self.markers.list.iter().enumerate() {
+                            ui.label(format!("M{}", i + 1));
+                            ui.monospace(format_seconds(marker.time, 1e-6));
+                            ui.monospace(format!("{}Hz", format_si(marker.frequency + centre)));
+                            if ui.small_button("🗑").on_hover_text("remove").clicked() {
+                                remove = Some(i);
+                            }
+                            ui.end_row();
+
+                            if let Some(next) = self.markers.list.get(i + 1) {
+                                ui.label("");
+                                ui.label(delta(marker, next)).on_hover_text(format!(
+                                    "from M{} to M{}",
+                                    i + 1,
+                                    i + 2
+                                ));
+                                ui.end_row();
+                            }
+                        }
+                    });
+                if ui.button("Clear").clicked() {
+                    clear = true;
+                }
+            });
+        self.markers.show = open;
+
+        if let Some(i) = remove {
+            self.markers.list.remove(i);
+        }
+        if clear {
+            self.markers.list.clear();
+        }
+    }
+
+    /// Measure the selection, once it's stopped moving, and show how it's going.
+    pub(super) fn measurement_ui(
+        &mut self,
+        ui: &mut egui::Ui,
+        selection: &View,
+        samples: &Arc<dyn Samples>,
+    ) {
+        let key = (*selection, self.fft.windowing);
+        let stale = self.markers.measurement.as_ref().map(|(k, _)| *k) != Some(key);
+        if stale && self.selecting.is_none() {
+            let samples = Arc::clone(samples);
+            let (selection, windowing) = key;
+            self.markers.measurement = Some((
+                key,
+                Promise::spawn_thread("measure", move || measure(samples, selection, windowing)),
+            ));
+        }
+
+        // the measurement is absolute whenever it can be
+        let offset = match samples.centre_frequency() {
+            Some(centre) if !self.absolute => -(centre as f64),
+            _ => 0.,
+        };
+        let result = match self.markers.measurement.as_ref().filter(|_| !stale) {
+            Some((_, promise)) => promise.ready(),
+            None => None,
+        };
+        match result {
+            Some(Ok(measured)) => {
+                let band = &measured.band;
+                ui.label("power");
+                ui.label(format!("{:.1}dBFS", band.dbfs))
+                    .on_hover_text(format!(
+                        "{} point ffts, {}Hz bins{}",
+                        measured.width,
+                        format_si(measured.resolution),
+                        if measured.truncated {
+                            "; only the start of the selection"
+                        } else {
+                            ""
+                        }
+                    ));
+                ui.end_row();
+
+                ui.label("occupied");
+                ui.label(format!(
+                    "{}Hz (99%), {}Hz (-20dB)",
+                    format_si(band.occupied),
+                    format_si(band.below_peak)
+                ));
+                ui.end_row();
+
+                ui.label("peak");
+                ui.label(format!(
+                    "{}Hz, {:.1}dBFS",
+                    format_si(band.peak.frequency + offset),
+                    band.peak.dbfs
+                ));
+                ui.end_row();
+            }
+            Some(Err(e)) => {
+                ui.label("power");
+                ui.colored_label(ui.visuals().error_fg_color, e);
+                ui.end_row();
+            }
+            None => {
+                ui.label("power");
+                ui.spinner();
+                ui.end_row();
+                ui.ctx().request_repaint();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::width_for;
+
+    #[test]
+    fn widths() {
+        // 64 bins across 20kHz of 2MHz
+        assert_eq!(8_192, width_for(2_000_000, 20_000., 1_000_000));
+        // but only as much as the selection can average
+        assert_eq!(1_024, width_for(2_000_000, 20_000., 5_000));
+        assert_eq!(64, width_for(2_000_000, 2_000_000., 1_000_000));
+    }
+}
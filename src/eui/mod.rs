@@ -1,17 +1,53 @@
-use crate::args::guess_details;
-use crate::ffts::{take_fft, FftConfig, Windowing};
-use crate::samples::SampleFile;
-use crate::{u64_from, usize_from, Samples};
-use anyhow::{anyhow, Result};
-use egui::{ColorImage, Vec2};
-use num_traits::Zero;
+mod annotate;
+mod axes;
+mod export;
+mod keys;
+mod measure;
+mod open;
+mod pipeline;
+mod snapshot;
+mod spectrum;
+mod state;
+mod symbols;
+mod tiles;
+mod timedomain;
+pub mod view;
+mod waterfall;
+mod workspace;
+mod wrapped;
+
+use crate::args::command_line;
+use crate::colormap::Colormap;
+use crate::ffts::{Combine, FftResult, Scale, Windowing};
+use crate::render;
+use crate::{Operation, Samples};
+use annotate::Annotations;
+use anyhow::{ensure, Result};
+use egui::ColorImage;
+use export::ExportSettings;
+use keys::BurstJumps;
+use measure::Markers;
+use pipeline::Source;
 use poll_promise::Promise;
-use rustfft::num_complex::Complex;
-use std::fs;
+use snapshot::SnapshotSettings;
+use state::Memory;
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
+use symbols::SymbolSettings;
+use tiles::{Colouring, Tiles};
+use timedomain::TimeDomain;
+use view::View;
+use waterfall::{FftSettings, Waterfall};
+use workspace::Workspace;
+use wrapped::Wrapped;
+
+pub fn display(samples: Option<Box<dyn Samples>>, filename: &Option<PathBuf>) -> Result<()> {
+    ensure!(
+        samples.is_none() || filename.is_none(),
+        "eui takes either a pipeline or a filename, not both"
+    );
 
-pub fn display(filename: &Option<PathBuf>) -> Result<()> {
     let native_options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
             .with_inner_size([400.0, 300.0])
@@ -19,168 +55,530 @@ pub fn display(filename: &Option<PathBuf>) -> Result<()> {
         ..Default::default()
     };
 
-    let filename = filename
-        .as_ref()
-        .ok_or_else(|| anyhow!("filename currently required"))?;
-
-    let filename = filename
-        .to_str()
-        .ok_or_else(|| anyhow!("filename contains invalid UTF-8"))?
-        .to_string();
-
-    let details = guess_details(&filename, None, None)?;
-    let file = SampleFile::new(
-        fs::File::open(filename)?,
-        details.format,
-        details.sample_rate,
-    );
+    let filename = filename.clone();
 
     eframe::run_native(
-        "eframe template",
+        "quadrs",
         native_options,
-        Box::new(|cc| Ok(Box::new(ManageApp::new(cc, Arc::new(file))))),
+        Box::new(|cc| Ok(Box::new(Workspace::new(cc, samples, filename)))),
     )
     .expect("TODO: non-anyhow-compatible error");
     Ok(())
 }
 
+/// How the samples are drawn.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+enum Layout {
+    /// Time down the screen, frequency across.
+    Waterfall,
+    /// An fft at every sample, in columns wrapped into rows.
+    Wrapped,
+}
+
+/// One source, its pipeline, and everything about how it's being looked at; i.e. a tab.
 struct ManageApp {
-    start: f32,
-    end: f32,
+    layout: Layout,
+    view: View,
+    history: Vec<View>,
+    gesture_at: Option<Instant>,
+
+    /// A time and frequency range, for exporting.
+    selection: Option<View>,
+    /// Where the current selection drag started: time and frequency.
+    selecting: Option<(f64, f64)>,
+    export: ExportSettings,
+
+    show_time_domain: bool,
+    /// The samples in the selection, and which selection that was.
+    time_domain: Option<(View, Result<TimeDomain, String>)>,
+    magnitude_db: bool,
+
+    /// Constellation and eye diagram.
+    symbols: SymbolSettings,
+
+    annotations: Annotations,
+    show_annotations: bool,
+
+    /// For jumping between bursts from the keyboard.
+    bursts: BurstJumps,
 
-    fft_width: f32,
+    /// Points to measure between, and the measurement of the selection.
+    markers: Markers,
 
-    samples: Arc<dyn Samples>,
+    /// The mean and max-hold spectrum under the waterfall, and what it was made from.
+    show_spectrum: bool,
+    spectrum: Option<(spectrum::Key, spectrum::Trace)>,
 
-    texture: Option<egui::TextureHandle>,
-    next_image: Option<Arc<ColorImage>>,
-    renderation: Option<Promise<ColorImage>>,
+    /// Saving the view as a picture.
+    snapshot: SnapshotSettings,
+
+    fft: FftSettings,
+    /// The height of the waterfall, in pixels.
+    rows: usize,
+
+    colormap: Colormap,
+    scale: Scale,
+    floor: f32,
+    ceiling: f32,
+    auto_levels: bool,
+
+    source: Option<Source>,
+    stages: Vec<Operation>,
+    pipeline_error: Option<String>,
+
+    /// The output of the pipeline
+    samples: Option<Arc<dyn Samples>>,
+
+    error: Option<String>,
+
+    /// Label the frequency axis with real frequencies, when the centre is known.
+    absolute: bool,
+    /// Where the context menu was opened: time and (relative) frequency.
+    clicked_at: Option<(f64, f64)>,
+
+    tiles: Tiles,
+    wrapped: Wrapped,
+
+    /// The overview needs colouring in again.
+    recolour: bool,
+    overview: Option<Arc<Waterfall>>,
+    overview_texture: Option<egui::TextureHandle>,
+    overview_renderation: Option<Promise<Result<Waterfall, String>>>,
 }
 
 impl ManageApp {
-    pub fn new(_cc: &eframe::CreationContext<'_>, samples: Arc<dyn Samples>) -> Self {
+    pub fn new(ctx: &egui::Context, source: Source, memory: &Memory) -> Self {
+        let defaults = memory.defaults;
         let mut us = ManageApp {
-            samples,
-            start: 46.0,
-            end: 46.3,
-            fft_width: 512.,
-            texture: None,
-            next_image: None,
-            renderation: None,
+            source: None,
+            stages: Vec::new(),
+            pipeline_error: None,
+            samples: None,
+            error: None,
+            layout: Layout::Waterfall,
+            view: View {
+                start: 0.,
+                end: 1.,
+                low: -0.5,
+                high: 0.5,
+            },
+            history: Vec::new(),
+            gesture_at: None,
+            selection: None,
+            selecting: None,
+            export: ExportSettings::default(),
+            show_time_domain: false,
+            time_domain: None,
+            magnitude_db: false,
+            symbols: SymbolSettings::default(),
+            annotations: Annotations::default(),
+            show_annotations: false,
+            bursts: BurstJumps::default(),
+            markers: Markers::default(),
+            show_spectrum: true,
+            spectrum: None,
+            snapshot: SnapshotSettings::default(),
+            fft: defaults.fft,
+            rows: 1024,
+            colormap: defaults.colormap,
+            scale: defaults.scale,
+            floor: defaults.floor,
+            ceiling: defaults.ceiling,
+            auto_levels: defaults.auto_levels,
+            absolute: defaults.absolute,
+            clicked_at: None,
+            tiles: Tiles::new(ctx),
+            wrapped: Wrapped::default(),
+            recolour: false,
+            overview: None,
+            overview_texture: None,
+            overview_renderation: None,
         };
+        us.set_source(source, memory);
+        us
+    }
 
-        us.trigger_redraw();
+    /// What to call the tab.
+    fn title(&self) -> String {
+        let name = self
+            .source
+            .as_ref()
+            .map(|source| source.name.as_str())
+            .unwrap_or_default();
+        PathBuf::from(name)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| name.to_string())
+    }
 
-        us
+    fn set_source(&mut self, source: Source, memory: &Memory) {
+        self.view = View::whole(source.samples.as_ref());
+        self.history.clear();
+        self.selection = None;
+        self.markers = Markers::default();
+        self.source = Some(source);
+        self.load_annotations();
+
+        let restored = match self.annotations.capture.clone() {
+            Some(capture) => self.restore_file(&capture, memory),
+            None => None,
+        };
+        self.rebuild();
+        if self.pipeline_error.is_some() && !self.stages.is_empty() {
+            // the saved stages don't fit the file any more
+            self.stages.clear();
+            self.rebuild();
+        }
+        if let (Some((view, markers)), Some(samples)) = (restored, self.samples.clone()) {
+            self.set_view(view, &samples);
+            self.markers.list = markers;
+        }
     }
 
-    fn trigger_redraw(&mut self) {
-        let samples = Arc::clone(&self.samples);
-        let fft_width = self.fft_width as usize;
-
-        let fft = rustfft::FftPlanner::<f32>::new().plan_fft_forward(fft_width);
-
-        let start = self.start;
-        let end = self.end;
-
-        self.renderation = Some(Promise::spawn_thread("renderation", move || {
-            let hoight = 2048;
-            let mut buf = vec![egui::Color32::TRANSPARENT; fft_width * hoight];
-
-            let start_sample = (samples.len() as f32 * start / 100.) as u64;
-            let end_sample = (samples.len() as f32 * end / 100.) as u64;
-            let fft = take_fft(
-                &*samples,
-                Some((start_sample, end_sample)),
-                &FftConfig {
-                    width: fft_width,
-                    windowing: Windowing::BlackmanHarris,
-                },
-                hoight,
-            )
-            .expect("Failed to take FFT");
-            for row in 0..fft.output_len() {
-                for (i, c) in fft.get(row).iter().enumerate() {
-                    let b = (c / 10. * 256.) as u8;
-                    buf[row * fft_width + i] = egui::Color32::from_rgb(0, 0, b);
-                }
-            }
+    /// Re-run the pipeline stages over the source, keeping the old output if they're invalid.
+    fn rebuild(&mut self) {
+        let source = match self.source.as_ref() {
+            Some(source) => source,
+            None => return,
+        };
 
-            ColorImage {
-                size: [fft_width, hoight],
-                pixels: buf,
+        match pipeline::build(&source.samples, &self.stages) {
+            Ok(samples) => {
+                self.view.clamp(samples.as_ref());
+                self.render_overview(&samples);
+                self.samples = Some(samples);
+                self.time_domain = None;
+                self.markers.measurement = None;
+                self.bursts = BurstJumps::default();
+                self.pipeline_error = None;
+                self.invalidate();
             }
+            Err(e) => self.pipeline_error = Some(format!("{:#}", e)),
+        }
+    }
+
+    fn pipeline_ui(&mut self, ui: &mut egui::Ui) {
+        let source = match self.source.as_ref() {
+            Some(source) => source,
+            None => return,
+        };
+
+        ui.heading("Pipeline");
+        ui.label(source.name.as_str());
+        ui.small(describe(source.samples.as_ref()));
+        ui.separator();
+
+        if pipeline::edit(ui, &mut self.stages) {
+            self.rebuild();
+        }
+
+        if let Some(error) = &self.pipeline_error {
+            ui.colored_label(ui.visuals().error_fg_color, error);
+        }
+
+        if let Some(samples) = self.samples.as_ref().filter(|_| !self.stages.is_empty()) {
+            ui.separator();
+            ui.label("output");
+            ui.small(describe(samples.as_ref()));
+        }
+    }
+
+    /// The source and stages, as command line arguments, as far as they can be.
+    fn pipeline_commands(&self) -> Vec<String> {
+        self.source
+            .iter()
+            .flat_map(|source| source.from.iter())
+            .chain(self.stages.iter())
+            .map(|op| command_line(op).unwrap_or_else(|| "…".to_string()))
+            .collect()
+    }
+
+    /// The samples or the fft settings have changed, so none of the rendering is any good.
+    fn invalidate(&mut self) {
+        self.tiles.reset();
+    }
+
+    fn render_overview(&mut self, samples: &Arc<dyn Samples>) {
+        let samples = Arc::clone(samples);
+        let fft = FftSettings {
+            width: OVERVIEW_FFT_WIDTH,
+            combine: Combine::First,
+            ..self.fft
+        };
+        self.overview_renderation = Some(Promise::spawn_thread("overview", move || {
+            let whole = View::whole(samples.as_ref());
+            Waterfall::render(samples.as_ref(), &whole, &fft, OVERVIEW_ROWS)
         }));
     }
-}
 
-impl eframe::App for ManageApp {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        // Put your widgets into a `SidePanel`, `TopBottomPanel`, `CentralPanel`, `Window` or `Area`.
+    /// Pick up any finished renders.
+    fn poll_renders(&mut self, ctx: &egui::Context) {
+        if self.tiles.poll() && self.auto_levels {
+            self.auto_level();
+        }
+        if let Some(e) = self.tiles.error.take() {
+            self.error = Some(e);
+        }
 
-        egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
-            egui::menu::bar(ui, |ui| {
-                ui.menu_button("File", |ui| {
-                    if ui.button("Quit").clicked() {
-                        ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+        if let Some(result) = take_ready(&mut self.overview_renderation) {
+            self.overview = result.ok().map(Arc::new);
+            self.recolour = true;
+        }
+
+        if self.overview_renderation.is_some() {
+            ctx.request_repaint_after(Duration::from_millis(50));
+        }
+    }
+
+    /// The rendered ffts for the current view, coarsest first.
+    fn visible_ffts(&self) -> impl Iterator<Item = &FftResult> {
+        self.tiles
+            .visible(&self.view)
+            .into_iter()
+            .filter_map(|key| self.tiles.waterfall(&key))
+            .map(|waterfall| &waterfall.fft)
+    }
+
+    /// Set the floor and ceiling to cover everything in the current FFT.
+    fn auto_level(&mut self) {
+        if Layout::Wrapped == self.layout {
+            self.wrapped_auto_level();
+            return;
+        }
+        let (min, max, full_scale) = self
+            .visible_ffts()
+            .fold((f32::INFINITY, 0f32, 0.), |(min, max, _), fft| {
+                (min.min(fft.min()), max.max(fft.max()), fft.full_scale())
+            });
+        if 0. == full_scale {
+            return;
+        }
+        self.ceiling = self.scale.apply(max, full_scale);
+        self.floor = self.scale.apply(min, full_scale);
+
+        // the quietest bin is often far, far below the noise floor
+        if Scale::Decibels == self.scale {
+            self.floor = self.floor.max(self.ceiling - AUTO_LEVEL_DB_RANGE);
+        }
+    }
+
+    fn fft_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            let before = self.fft;
+
+            egui::ComboBox::from_label("fft")
+                .selected_text(self.fft.width.to_string())
+                .show_ui(ui, |ui| {
+                    for power in FFT_POWERS {
+                        let width = 1 << power;
+                        ui.selectable_value(&mut self.fft.width, width, width.to_string());
                     }
                 });
-                ui.add_space(16.0);
 
-                egui::widgets::global_theme_preference_buttons(ui);
-            });
+            egui::ComboBox::from_label("window")
+                .selected_text(self.fft.windowing.name())
+                .show_ui(ui, |ui| {
+                    for windowing in Windowing::ALL {
+                        // don't reset the beta if it's already a kaiser
+                        let current = self.fft.windowing.name() == windowing.name();
+                        if ui.selectable_label(current, windowing.name()).clicked() && !current {
+                            self.fft.windowing = windowing;
+                        }
+                    }
+                });
+            if let Windowing::Kaiser { beta } = &mut self.fft.windowing {
+                ui.add(
+                    egui::DragValue::new(beta)
+                        .range(0.0..=30.0)
+                        .speed(0.1)
+                        .prefix("β "),
+                );
+            }
+
+            ui.separator();
+            ui.label("each row:");
+            ui.selectable_value(&mut self.fft.combine, Combine::First, "one fft")
+                .on_hover_text("fast, but misses anything short between rows");
+            ui.selectable_value(&mut self.fft.combine, Combine::Mean, "mean");
+            ui.selectable_value(&mut self.fft.combine, Combine::Max, "max hold");
+
+            let mut percent = self.fft.overlap * 100.;
+            if ui
+                .add_enabled(
+                    self.fft.combine != Combine::First,
+                    egui::Slider::new(&mut percent, 0.0..=90.0)
+                        .suffix("%")
+                        .text("overlap"),
+                )
+                .changed()
+            {
+                self.fft.overlap = percent / 100.;
+            }
+
+            if before != self.fft {
+                self.invalidate();
+            }
         });
+    }
 
-        egui::CentralPanel::default().show(ctx, |ui| {
-            if self.renderation.as_ref().and_then(|v| v.ready()).is_some() {
-                match self.renderation.take().expect("just checked").try_take() {
-                    Ok(image) => {
-                        self.next_image = Some(Arc::new(image));
+    fn levels_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.spacing_mut().slider_width = 150.;
+            let before = (self.colormap, self.scale, self.floor, self.ceiling);
+
+            egui::ComboBox::from_label("colormap")
+                .selected_text(self.colormap.name())
+                .show_ui(ui, |ui| {
+                    for colormap in Colormap::ALL {
+                        ui.selectable_value(&mut self.colormap, colormap, colormap.name());
                     }
-                    Err(_) => unreachable!(),
+                });
+
+            let scale_changed = ui
+                .selectable_value(&mut self.scale, Scale::Decibels, "dB")
+                .changed()
+                | ui.selectable_value(&mut self.scale, Scale::Linear, "linear")
+                    .changed();
+
+            let limits = match self.scale {
+                Scale::Decibels => -200.0..=50.0,
+                Scale::Linear => {
+                    0.0..=self
+                        .visible_ffts()
+                        .map(|fft| fft.max())
+                        .fold(f32::EPSILON, f32::max)
                 }
+            };
+            let levels = [
+                ui.add(egui::Slider::new(&mut self.floor, limits.clone()).text("floor")),
+                ui.add(egui::Slider::new(&mut self.ceiling, limits).text("ceiling")),
+            ];
+            if levels.iter().any(|slider| slider.changed()) {
+                self.auto_levels = false;
             }
 
-            if let Some(next_image) = self.next_image.take() {
-                self.texture = Some(ui.ctx().load_texture(
-                    "next_image",
-                    next_image,
-                    Default::default(),
-                ));
+            ui.checkbox(&mut self.auto_levels, "auto");
+            if ui.button("auto levels").clicked() || (self.auto_levels && scale_changed) {
+                self.auto_level();
             }
 
-            ui.spacing_mut().slider_width = ui.available_width() - 100.;
+            if before != (self.colormap, self.scale, self.floor, self.ceiling) {
+                self.recolour = true;
+            }
+        });
+    }
+}
 
-            let sliders = vec![
-                ui.add(egui::Slider::new(&mut self.start, 0.0..=100.0).text("start")),
-                ui.add(egui::Slider::new(&mut self.end, 0.0..=100.0).text("end")),
-                ui.add(egui::Slider::new(&mut self.fft_width, 4.0..=4096.0).text("fft")),
-            ];
+fn colour_image(fft: &FftResult, colouring: Colouring) -> ColorImage {
+    let image = render::waterfall(fft, colouring.scale, colouring.colormap, colouring.range);
+    ColorImage::from_rgb(
+        [image.width() as usize, image.height() as usize],
+        image.as_raw(),
+    )
+}
 
-            if sliders.iter().any(|slider| slider.changed()) {
-                self.trigger_redraw();
-            }
+/// Sample rate, length and centre frequency, for humans.
+fn describe(samples: &dyn Samples) -> String {
+    let sample_rate = samples.sample_rate();
+    let mut description = format!(
+        "{}sps, {:.3}s",
+        crate::axis::format_si(sample_rate as f64),
+        samples.len() as f64 / sample_rate as f64
+    );
+    if let Some(centre) = samples.centre_frequency() {
+        description.push_str(&format!(
+            ", centre {}Hz",
+            crate::axis::format_si(centre as f64)
+        ));
+    }
+    description
+}
 
-            ui.separator();
+/// The whole-file overview is only a rough guide.
+const OVERVIEW_FFT_WIDTH: usize = 256;
 
-            // println!("{:?}", ui.available_size());
-            if let Some(texture) = self.texture.as_ref() {
-                ui.image((
-                    texture.id(),
-                    Vec2::new(
-                        ui.available_size_before_wrap().x,
-                        ui.available_height() - 60.0,
-                    ),
-                ));
-            } else {
-                ui.spinner();
-            }
+/// The fft widths on offer, as powers of two.
+const FFT_POWERS: std::ops::RangeInclusive<u32> = 4..=16;
+const OVERVIEW_ROWS: usize = 1024;
 
-            ui.separator();
+/// The result of a promise, if it's finished.
+fn take_ready<T: Send>(promise: &mut Option<Promise<T>>) -> Option<T> {
+    promise.as_ref()?.ready()?;
+    promise.take()?.try_take().ok()
+}
+
+/// How far below the loudest bin to put the floor, when automatically setting it.
+const AUTO_LEVEL_DB_RANGE: f32 = 100.;
 
-            ui.with_layout(egui::Layout::bottom_up(egui::Align::LEFT), |ui| {
-                egui::warn_if_debug_build(ui);
+impl ManageApp {
+    /// The pipeline on the left, and the annotations on the right, if they're wanted.
+    fn side_panels(&mut self, ctx: &egui::Context) {
+        egui::SidePanel::left("pipeline")
+            .resizable(true)
+            .show(ctx, |ui| {
+                egui::ScrollArea::vertical().show(ui, |ui| self.pipeline_ui(ui));
             });
-        });
+
+        if let Some(samples) = self.samples.clone().filter(|_| self.show_annotations) {
+            egui::SidePanel::right("annotations")
+                .resizable(true)
+                .show(ctx, |ui| self.annotations_ui(ui, &samples));
+        }
+    }
+
+    /// The settings and the waterfall.
+    fn central_ui(&mut self, ui: &mut egui::Ui) {
+        if let Some(error) = &self.error {
+            ui.colored_label(ui.visuals().error_fg_color, error);
+        }
+
+        let samples = match self.samples.clone() {
+            Some(samples) => samples,
+            None => return,
+        };
+
+        self.poll_renders(ui.ctx());
+        self.poll_bursts(ui.ctx(), &samples);
+
+        match self.layout {
+            Layout::Waterfall => self.fft_ui(ui),
+            Layout::Wrapped => self.wrapped_settings_ui(ui),
+        }
+        self.levels_ui(ui);
+
+        if self.recolour {
+            self.recolour = false;
+            if let Some(overview) = self.overview.clone() {
+                let range = overview.fft.auto_range(self.scale);
+                let image = colour_image(
+                    &overview.fft,
+                    Colouring {
+                        colormap: self.colormap,
+                        scale: self.scale,
+                        range,
+                    },
+                );
+                self.overview_texture =
+                    Some(ui.ctx().load_texture("overview", image, Default::default()));
+            }
+        }
+
+        ui.separator();
+
+        match self.layout {
+            Layout::Waterfall => self.waterfall_ui(ui, &samples),
+            Layout::Wrapped => self.wrapped_ui(ui, &samples),
+        }
+    }
+
+    /// The selection, and everything else which floats over the waterfall.
+    fn windows(&mut self, ctx: &egui::Context) {
+        if let Some(samples) = self.samples.clone() {
+            self.export_ui(ctx, &samples);
+            self.time_domain_ui(ctx, &samples);
+            self.symbols_ui(ctx, &samples);
+            self.markers_ui(ctx, &samples);
+            self.snapshot_ui(ctx, &samples);
+        }
     }
 }
@@ -0,0 +1,255 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::args::{guess_details, guess_format_from_name};
+use crate::{FileDetails, FileFormat};
+
+/// How many files to remember in File → Open recent.
+const MAX_RECENT: usize = 10;
+
+pub enum Choice<T> {
+    Pending,
+    Chosen(T),
+    Cancelled,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Recent {
+    pub path: PathBuf,
+    pub details: FileDetails,
+}
+
+/// Move (or add) `path` to the front of the list, forgetting the oldest entries.
+pub fn remember(recent: &mut Vec<Recent>, path: &Path, details: &FileDetails) {
+    recent.retain(|r| r.path != path);
+    recent.insert(
+        0,
+        Recent {
+            path: path.to_path_buf(),
+            details: details.clone(),
+        },
+    );
+    recent.truncate(MAX_RECENT);
+}
+
+/// A directory listing, as there's no native file dialog available.
+pub struct FileBrowser {
+    dir: PathBuf,
+    location: String,
+    entries: Vec<(PathBuf, bool)>,
+    selected: Option<PathBuf>,
+    error: Option<String>,
+}
+
+impl FileBrowser {
+    pub fn new(dir: PathBuf) -> Self {
+        let mut us = FileBrowser {
+            location: String::new(),
+            dir: PathBuf::new(),
+            entries: Vec::new(),
+            selected: None,
+            error: None,
+        };
+        us.change_dir(dir);
+        us
+    }
+
+    fn change_dir(&mut self, dir: PathBuf) {
+        let listing = fs::read_dir(&dir).and_then(|entries| {
+            entries
+                .map(|entry| {
+                    let entry = entry?;
+                    Ok((entry.path(), entry.file_type()?.is_dir()))
+                })
+                .collect::<Result<Vec<_>, std::io::Error>>()
+        });
+
+        match listing {
+            Ok(mut entries) => {
+                // directories first, then by name
+                entries.sort_by(|(a, a_dir), (b, b_dir)| b_dir.cmp(a_dir).then(a.cmp(b)));
+                entries.retain(|(path, _)| {
+                    !path
+                        .file_name()
+                        .map(|name| name.to_string_lossy().starts_with('.'))
+                        .unwrap_or(true)
+                });
+                self.location = dir.to_string_lossy().to_string();
+                self.dir = dir;
+                self.entries = entries;
+                self.selected = None;
+                self.error = None;
+            }
+            Err(e) => self.error = Some(format!("{:?}: {}", dir, e)),
+        }
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context) -> Choice<PathBuf> {
+        let mut choice = Choice::Pending;
+        let mut change_dir = None;
+
+        egui::Window::new("Open")
+            .collapsible(false)
+            .default_size([500., 400.])
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    if ui.button("⬆").on_hover_text("parent directory").clicked() {
+                        change_dir = self.dir.parent().map(Path::to_path_buf);
+                    }
+                    let location = ui.add(
+                        egui::TextEdit::singleline(&mut self.location).desired_width(f32::INFINITY),
+                    );
+                    if location.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                        change_dir = Some(PathBuf::from(&self.location));
+                    }
+                });
+
+                if let Some(error) = &self.error {
+                    ui.colored_label(ui.visuals().error_fg_color, error);
+                }
+
+                ui.separator();
+
+                egui::ScrollArea::vertical()
+                    .max_height(ui.available_height() - 30.)
+                    .show(ui, |ui| {
+                        for (path, is_dir) in &self.entries {
+                            let name = path
+                                .file_name()
+                                .map(|name| name.to_string_lossy().to_string())
+                                .unwrap_or_default();
+                            let label = if *is_dir {
+                                format!("🗀 {}", name)
+                            } else {
+                                name
+                            };
+                            let selected = self.selected.as_ref() == Some(path);
+                            let item = ui.selectable_label(selected, label);
+                            if *is_dir {
+                                if item.clicked() {
+                                    change_dir = Some(path.to_path_buf());
+                                }
+                            } else if item.double_clicked() {
+                                choice = Choice::Chosen(path.to_path_buf());
+                            } else if item.clicked() {
+                                self.selected = Some(path.to_path_buf());
+                            }
+                        }
+                    });
+
+                ui.separator();
+
+                ui.horizontal(|ui| {
+                    let open = ui.add_enabled(self.selected.is_some(), egui::Button::new("Open"));
+                    if open.clicked() {
+                        if let Some(path) = self.selected.take() {
+                            choice = Choice::Chosen(path);
+                        }
+                    }
+                    if ui.button("Cancel").clicked() {
+                        choice = Choice::Cancelled;
+                    }
+                });
+            });
+
+        if let Some(dir) = change_dir {
+            self.change_dir(dir);
+        }
+
+        choice
+    }
+}
+
+/// Ask for whatever `guess_details` couldn't work out from the filename.
+pub struct DetailsDialog {
+    path: PathBuf,
+    format: Option<FileFormat>,
+    sample_rate: String,
+    centre: String,
+    error: Option<String>,
+}
+
+impl DetailsDialog {
+    pub fn new(path: PathBuf, problem: String) -> Self {
+        let (sample_rate, format, centre) =
+            guess_format_from_name(&path.to_string_lossy()).unwrap_or((None, None, None));
+        DetailsDialog {
+            path,
+            format,
+            sample_rate: sample_rate.unwrap_or_default(),
+            centre: centre.unwrap_or_default(),
+            error: Some(problem),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn details(&self) -> anyhow::Result<FileDetails> {
+        let non_empty = |s: &str| Some(s.trim().to_string()).filter(|s| !s.is_empty());
+        guess_details(
+            &self.path.to_string_lossy(),
+            non_empty(&self.sample_rate),
+            self.format.map(|format| format.extension().to_string()),
+            non_empty(&self.centre),
+        )
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context) -> Choice<FileDetails> {
+        let mut choice = Choice::Pending;
+
+        egui::Window::new("File details")
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label(self.path.to_string_lossy());
+
+                egui::Grid::new("details").num_columns(2).show(ui, |ui| {
+                    ui.label("format");
+                    egui::ComboBox::from_id_salt("format")
+                        .selected_text(self.format.map(|f| f.extension()).unwrap_or("?"))
+                        .show_ui(ui, |ui| {
+                            for format in FileFormat::ALL {
+                                ui.selectable_value(
+                                    &mut self.format,
+                                    Some(format),
+                                    format.extension(),
+                                );
+                            }
+                        });
+                    ui.end_row();
+
+                    ui.label("sample rate");
+                    ui.text_edit_singleline(&mut self.sample_rate)
+                        .on_hover_text("e.g. 2M, 250k, 48000");
+                    ui.end_row();
+
+                    ui.label("centre frequency");
+                    ui.text_edit_singleline(&mut self.centre)
+                        .on_hover_text("optional, e.g. 433.92M");
+                    ui.end_row();
+                });
+
+                if let Some(error) = &self.error {
+                    ui.colored_label(ui.visuals().error_fg_color, error);
+                }
+
+                ui.horizontal(|ui| {
+                    if ui.button("Open").clicked() {
+                        match self.details() {
+                            Ok(details) => choice = Choice::Chosen(details),
+                            Err(e) => self.error = Some(format!("{:#}", e)),
+                        }
+                    }
+                    if ui.button("Cancel").clicked() {
+                        choice = Choice::Cancelled;
+                    }
+                });
+            });
+
+        choice
+    }
+}
@@ -0,0 +1,170 @@
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+
+use crate::{Operation, Samples};
+
+/// Where the samples come from, before any stages.
+pub struct Source {
+    pub samples: Arc<dyn Samples>,
+    pub name: String,
+
+    /// How to open it from the command line, if that's possible.
+    pub from: Option<Operation>,
+}
+
+/// Apply the stages, in order, to the source.
+pub fn build(source: &Arc<dyn Samples>, stages: &[Operation]) -> Result<Arc<dyn Samples>> {
+    let mut samples: Box<dyn Samples> = Box::new(Arc::clone(source));
+    for (i, stage) in stages.iter().enumerate() {
+        samples = stage
+            .exec(Some(samples))
+            .map_err(|e| anyhow!("stage {}: {:#}", i + 1, e))?
+            .ok_or_else(|| anyhow!("stage {} produced no output", i + 1))?;
+    }
+    Ok(Arc::from(samples))
+}
+
+/// How far the stages move frequencies; a tone at `f` in the source ends up at `f + offset`.
+pub fn frequency_offset(stages: &[Operation]) -> f64 {
+    stages
+        .iter()
+        .map(|stage| match stage {
+            Operation::Shift { frequency } => *frequency as f64,
+            _ => 0.,
+        })
+        .sum()
+}
+
+/// The stage editor; returns whether anything changed.
+pub fn edit(ui: &mut egui::Ui, stages: &mut Vec<Operation>) -> bool {
+    let mut changed = false;
+    let mut swap = None;
+    let mut remove = None;
+    let count = stages.len();
+
+    for (i, stage) in stages.iter_mut().enumerate() {
+        egui::Frame::group(ui.style()).show(ui, |ui| {
+            ui.horizontal(|ui| {
+                ui.strong(name(stage));
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    if ui.small_button("🗑").on_hover_text("remove").clicked() {
+                        remove = Some(i);
+                    }
+                    if ui
+                        .add_enabled(i + 1 < count, egui::Button::new("⏷").small())
+                        .on_hover_text("move down")
+                        .clicked()
+                    {
+                        swap = Some(i);
+                    }
+                    if ui
+                        .add_enabled(i > 0, egui::Button::new("⏶").small())
+                        .on_hover_text("move up")
+                        .clicked()
+                    {
+                        swap = Some(i - 1);
+                    }
+                });
+            });
+
+            changed |= edit_stage(ui, stage);
+        });
+    }
+
+    if let Some(i) = swap {
+        stages.swap(i, i + 1);
+        changed = true;
+    }
+
+    if let Some(i) = remove {
+        stages.remove(i);
+        changed = true;
+    }
+
+    ui.horizontal(|ui| {
+        if ui.button("+ shift").clicked() {
+            stages.push(Operation::Shift { frequency: 0 });
+            changed = true;
+        }
+        if ui.button("+ lowpass").clicked() {
+            stages.push(Operation::LowPass {
+                size: 40,
+                decimate: 8,
+                frequency: 10_000,
+            });
+            changed = true;
+        }
+        if ui.button("+ resample").clicked() {
+            stages.push(Operation::Resample { rate: 48_000 });
+            changed = true;
+        }
+    });
+
+    changed
+}
+
+fn name(stage: &Operation) -> &'static str {
+    match stage {
+        Operation::Shift { .. } => "shift",
+        Operation::LowPass { .. } => "lowpass",
+        Operation::Resample { .. } => "resample",
+        _ => "other",
+    }
+}
+
+fn edit_stage(ui: &mut egui::Ui, stage: &mut Operation) -> bool {
+    let mut changed = false;
+    match stage {
+        Operation::Shift { frequency } => {
+            changed |= ui
+                .add(egui::DragValue::new(frequency).speed(100).suffix(" Hz"))
+                .changed();
+        }
+        Operation::LowPass {
+            size,
+            decimate,
+            frequency,
+        } => {
+            egui::Grid::new(ui.next_auto_id()).show(ui, |ui| {
+                ui.label("frequency");
+                changed |= ui
+                    .add(egui::DragValue::new(frequency).speed(100).suffix(" Hz"))
+                    .changed();
+                ui.end_row();
+
+                ui.label("decimate");
+                changed |= ui
+                    .add(egui::DragValue::new(decimate).range(1..=u64::MAX))
+                    .changed();
+                ui.end_row();
+
+                // as with `lowpass -power`, the taps are always even
+                ui.label("power");
+                let mut power = *size / 2;
+                if ui
+                    .add(egui::DragValue::new(&mut power).range(1..=usize::MAX / 2))
+                    .changed()
+                {
+                    *size = power * 2;
+                    changed = true;
+                }
+                ui.end_row();
+            });
+        }
+        Operation::Resample { rate } => {
+            changed |= ui
+                .add(
+                    egui::DragValue::new(rate)
+                        .speed(100)
+                        .range(1..=u64::MAX)
+                        .suffix(" Hz"),
+                )
+                .changed();
+        }
+        _ => {
+            ui.label("not editable here");
+        }
+    }
+    changed
+}
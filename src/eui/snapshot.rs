@@ -0,0 +1,353 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::{anyhow, ensure, Context, Result};
+use image::imageops::{self, FilterType};
+use image::Rgb;
+use poll_promise::Promise;
+
+use super::tiles::Colouring;
+use super::view::View;
+use super::waterfall::{FftSettings, Waterfall};
+use super::ManageApp;
+use crate::render::{self, Axes, Labeller};
+use crate::Samples;
+
+const ANNOTATION_COLOUR: Rgb<u8> = Rgb([80, 220, 255]);
+
+/// Fft widths beyond this are very slow, for little gain in a picture.
+const MAX_FFT_WIDTH: usize = 1 << 16;
+
+pub struct SnapshotSettings {
+    pub show: bool,
+    /// Of the waterfall itself, in pixels, without the axes and so on.
+    width: u32,
+    height: u32,
+    /// `None` to generate one from the source.
+    filename: Option<String>,
+    /// `None` for the file and pipeline.
+    caption: Option<String>,
+    annotations: bool,
+
+    running: Option<Promise<Result<String, String>>>,
+    outcome: Option<Result<String, String>>,
+}
+
+impl Default for SnapshotSettings {
+    fn default() -> Self {
+        SnapshotSettings {
+            show: false,
+            width: 1600,
+            height: 1000,
+            filename: None,
+            caption: None,
+            annotations: true,
+            running: None,
+            outcome: None,
+        }
+    }
+}
+
+/// Everything needed to draw the picture, away from the ui.
+struct Job {
+    samples: Arc<dyn Samples>,
+    view: View,
+    fft: FftSettings,
+    colouring: Colouring,
+    size: (u32, u32),
+    /// What to add to (relative) frequencies for the axis.
+    centre: f64,
+    boxes: Vec<(View, String)>,
+    caption: Vec<String>,
+    filename: String,
+}
+
+/// The fft width which gives about one bin per pixel across the view's band.
+fn fft_width(sample_rate: u64, view: &View, pixels: u32) -> usize {
+    let wanted = f64::from(pixels) * sample_rate as f64 / (view.high - view.low).max(1.);
+    (wanted.min(MAX_FFT_WIDTH as f64) as usize)
+        .next_power_of_two()
+        .clamp(16, MAX_FFT_WIDTH)
+}
+
+fn render(job: &Job) -> Result<()> {
+    let (width, height) = job.size;
+    ensure!(width > 0 && height > 0, "the image must have some size");
+
+    let settings = FftSettings {
+        width: fft_width(job.samples.sample_rate(), &job.view, width),
+        ..job.fft
+    };
+    let waterfall = Waterfall::render(job.samples.as_ref(), &job.view, &settings, height as usize)
+        .map_err(|e| anyhow!(e))?;
+
+    let colouring = &job.colouring;
+    let full = render::waterfall(
+        &waterfall.fft,
+        colouring.scale,
+        colouring.colormap,
+        colouring.range,
+    );
+
+    // only the bins in the view, stretched to the size asked for
+    let (left, right) = waterfall.band();
+    let bins = full.width();
+    let bin_width = (right - left) / f64::from(bins);
+    let first = (((job.view.low - left) / bin_width).floor().max(0.) as u32).min(bins - 1);
+    let last = (((job.view.high - left) / bin_width).ceil() as u32).clamp(first + 1, bins);
+    let cropped = imageops::crop_imm(&full, first, 0, last - first, full.height()).to_image();
+    let mut image = imageops::resize(&cropped, width, height, FilterType::Nearest);
+
+    let frequencies = (
+        left + f64::from(first) * bin_width,
+        left + f64::from(last) * bin_width,
+    );
+    let times = waterfall.times();
+
+    let labeller = Labeller::new()?;
+    let x_of = |frequency: f64| {
+        ((frequency - frequencies.0) / (frequencies.1 - frequencies.0) * f64::from(width)) as i64
+    };
+    let y_of = |time: f64| ((time - times.0) / (times.1 - times.0) * f64::from(height)) as i64;
+    for (area, label) in &job.boxes {
+        let (x, y) = (x_of(area.low), y_of(area.start));
+        render::outline(
+            &mut image,
+            (x, y, x_of(area.high), y_of(area.end)),
+            ANNOTATION_COLOUR,
+        );
+        labeller.draw_coloured(
+            &mut image,
+            (x.max(0) + 3) as f32,
+            (y.max(0) + 15) as f32,
+            label,
+            ANNOTATION_COLOUR,
+        );
+    }
+
+    let axes = Axes {
+        frequencies: (frequencies.0 + job.centre, frequencies.1 + job.centre),
+        times: Some(times),
+    };
+    let image = axes.draw(&image, &labeller);
+    let image = render::draw_colour_bar(
+        &image,
+        colouring.colormap,
+        colouring.scale,
+        colouring.range,
+        &labeller,
+    );
+    let image = render::draw_caption(&image, &job.caption, &labeller);
+
+    image
+        .save(&job.filename)
+        .with_context(|| anyhow!("writing {:?}", job.filename))
+}
+
+/// A name for the picture, beside the source.
+fn default_filename(source: &str, view: &View) -> String {
+    let path = Path::new(source);
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_else(|| "quadrs".to_string());
+    let name = format!("{}.{:.3}s.png", stem, view.start);
+    match path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        Some(dir) => dir.join(name).to_string_lossy().to_string(),
+        None => name,
+    }
+}
+
+impl ManageApp {
+    /// The picture's size, name and caption, and a button to save it.
+    pub(super) fn snapshot_ui(&mut self, ctx: &egui::Context, samples: &Arc<dyn Samples>) {
+        if !self.snapshot.show {
+            return;
+        }
+
+        if let Some(running) = self.snapshot.running.as_ref() {
+            if running.ready().is_some() {
+                self.snapshot.outcome =
+                    self.snapshot.running.take().and_then(|p| p.try_take().ok());
+            }
+        }
+
+        let source_name = self
+            .source
+            .as_ref()
+            .map(|source| source.name.clone())
+            .unwrap_or_default();
+        let filename = self
+            .snapshot
+            .filename
+            .clone()
+            .unwrap_or_else(|| default_filename(&source_name, &self.view));
+        let caption =
+            self.snapshot.caption.clone().unwrap_or_else(|| {
+                format!("{}\n{}", source_name, self.pipeline_commands().join(" "))
+            });
+
+        let mut open = true;
+        let mut save = false;
+        egui::Window::new("Save image")
+            .open(&mut open)
+            .default_width(420.)
+            .show(ctx, |ui| {
+                egui::Grid::new("snapshot").num_columns(2).show(ui, |ui| {
+                    let settings = &mut self.snapshot;
+                    ui.label("size");
+                    ui.horizontal(|ui| {
+                        ui.add(egui::DragValue::new(&mut settings.width).range(16..=16_384));
+                        ui.label("×");
+                        ui.add(egui::DragValue::new(&mut settings.height).range(16..=16_384));
+                    });
+                    ui.end_row();
+
+                    ui.label("filename");
+                    ui.horizontal(|ui| {
+                        let mut edited = filename.clone();
+                        if ui.text_edit_singleline(&mut edited).changed() {
+                            settings.filename = Some(edited);
+                        }
+                        if ui
+                            .add_enabled(settings.filename.is_some(), egui::Button::new("⟲"))
+                            .on_hover_text("generate from the source")
+                            .clicked()
+                        {
+                            settings.filename = None;
+                        }
+                    });
+                    ui.end_row();
+
+                    ui.label("caption");
+                    ui.horizontal(|ui| {
+                        let mut edited = caption.clone();
+                        if ui
+                            .add(egui::TextEdit::multiline(&mut edited).desired_rows(2))
+                            .changed()
+                        {
+                            settings.caption = Some(edited);
+                        }
+                        if ui
+                            .add_enabled(settings.caption.is_some(), egui::Button::new("⟲"))
+                            .on_hover_text("the file and pipeline")
+                            .clicked()
+                        {
+                            settings.caption = None;
+                        }
+                    });
+                    ui.end_row();
+
+                    ui.label("");
+                    ui.checkbox(&mut settings.annotations, "annotations");
+                    ui.end_row();
+                });
+
+                ui.horizontal(|ui| {
+                    let running = self.snapshot.running.is_some();
+                    save = ui
+                        .add_enabled(!running, egui::Button::new("Save"))
+                        .on_hover_text("the current view, with the current fft and colours")
+                        .clicked();
+                    if running {
+                        ui.spinner();
+                    }
+                });
+
+                match &self.snapshot.outcome {
+                    Some(Ok(filename)) => {
+                        ui.label(format!("wrote {}", filename));
+                    }
+                    Some(Err(e)) => {
+                        ui.colored_label(ui.visuals().error_fg_color, e);
+                    }
+                    None => (),
+                }
+            });
+        self.snapshot.show = open;
+
+        if save {
+            let boxes = if self.snapshot.annotations {
+                self.annotations
+                    .list
+                    .iter()
+                    .map(|annotation| (self.annotation_box(annotation), annotation.label.clone()))
+                    .collect()
+            } else {
+                Vec::new()
+            };
+            let job = Job {
+                samples: Arc::clone(samples),
+                view: self.view,
+                fft: self.fft,
+                colouring: Colouring {
+                    colormap: self.colormap,
+                    scale: self.scale,
+                    range: (self.floor, self.ceiling),
+                },
+                size: (self.snapshot.width, self.snapshot.height),
+                centre: samples
+                    .centre_frequency()
+                    .filter(|_| self.absolute)
+                    .unwrap_or(0) as f64,
+                boxes,
+                caption: caption.lines().map(str::to_string).collect(),
+                filename,
+            };
+            self.snapshot.outcome = None;
+            self.snapshot.running = Some(Promise::spawn_thread("snapshot", move || {
+                render(&job)
+                    .map(|()| job.filename.clone())
+                    .map_err(|e| format!("{:#}", e))
+            }));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::{fft_width, render, Job};
+    use crate::colormap::Colormap;
+    use crate::eui::state::Settings;
+    use crate::eui::tiles::Colouring;
+    use crate::eui::view::View;
+    use crate::ffts::Scale;
+    use crate::gen::Gen;
+
+    #[test]
+    fn picture() {
+        let view = View {
+            start: 0.25,
+            end: 0.75,
+            low: 0.,
+            high: 2_000.,
+        };
+        assert_eq!(1_024, fft_width(8_000, &view, 200));
+
+        let filename =
+            std::env::temp_dir().join(format!("quadrs-snapshot-{}.png", std::process::id()));
+        let job = Job {
+            samples: Arc::new(Gen::new(vec![1_000], 8_000, 1.).unwrap()),
+            view,
+            fft: Settings::default().fft,
+            colouring: Colouring {
+                colormap: Colormap::Viridis,
+                scale: Scale::Decibels,
+                range: (-100., 0.),
+            },
+            size: (200, 100),
+            centre: 0.,
+            boxes: vec![(view, "tone".to_string())],
+            caption: vec!["a caption".to_string()],
+            filename: filename.to_string_lossy().to_string(),
+        };
+        render(&job).unwrap();
+
+        let image = image::open(&filename).unwrap();
+        // the waterfall, and then some
+        assert!(image.width() > 200 && image.height() > 100);
+        std::fs::remove_file(&filename).unwrap();
+    }
+}
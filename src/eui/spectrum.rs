@@ -0,0 +1,228 @@
+use egui::{Align2, Color32, FontId, Pos2, Rect, Sense, Shape, Stroke};
+
+use super::axes::Geometry;
+use super::tiles::TileKey;
+use super::view::View;
+use super::waterfall::Waterfall;
+use super::ManageApp;
+use crate::axis::{format_si, ticks};
+use crate::ffts::Scale;
+
+/// Height of the plot under the waterfall.
+pub const HEIGHT: f32 = 120.;
+
+/// Room above and below the trace, in dB.
+const HEADROOM_DB: f32 = 3.;
+
+const MEAN_COLOUR: Color32 = Color32::from_rgb(120, 200, 255);
+const MAX_COLOUR: Color32 = Color32::from_rgb(255, 170, 60);
+
+/// The mean and max-hold power of each bin, over some rows of ffts.
+#[derive(Debug, Default)]
+pub struct Trace {
+    /// Relative to the centre, of the middle of each bin.
+    pub frequencies: Vec<f64>,
+    /// dB, as in the waterfall.
+    pub mean: Vec<f32>,
+    pub max: Vec<f32>,
+}
+
+/// The view, and the tiles the trace was made from.
+pub type Key = (View, Vec<TileKey>);
+
+/// Combine the rows of the waterfalls which are inside the view's times.
+pub fn trace<'w>(waterfalls: impl IntoIterator<Item = &'w Waterfall>, view: &View) -> Trace {
+    let mut power: Vec<f64> = Vec::new();
+    let mut max: Vec<f32> = Vec::new();
+    let mut rows = 0usize;
+    let mut band = (0., 0.);
+    let mut full_scale = 1.;
+
+    for waterfall in waterfalls {
+        let fft = &waterfall.fft;
+        full_scale = fft.full_scale();
+        let width = fft.width();
+        if power.len() != width {
+            power = vec![0.; width];
+            max = vec![0.; width];
+            rows = 0;
+        }
+        band = waterfall.band();
+        let (top, bottom) = waterfall.times();
+        let per_row = (bottom - top) / fft.output_len() as f64;
+        for row in 0..fft.output_len() {
+            let time = top + (row as f64 + 0.5) * per_row;
+            if time < view.start || time > view.end {
+                continue;
+            }
+            for ((total, peak), &norm) in power.iter_mut().zip(max.iter_mut()).zip(fft.get(row)) {
+                *total += f64::from(norm) * f64::from(norm);
+                *peak = peak.max(norm);
+            }
+            rows += 1;
+        }
+    }
+
+    if 0 == rows {
+        return Trace::default();
+    }
+
+    let width = power.len();
+    let bin = (band.1 - band.0) / width as f64;
+    Trace {
+        frequencies: (0..width)
+            .map(|i| band.0 + (i as f64 + 0.5) * bin)
+            .collect(),
+        mean: power
+            .iter()
+            .map(|total| (total / rows as f64).sqrt() as f32)
+            .map(|norm| Scale::Decibels.apply(norm, full_scale))
+            .collect(),
+        max: max
+            .iter()
+            .map(|&norm| Scale::Decibels.apply(norm, full_scale))
+            .collect(),
+    }
+}
+
+impl ManageApp {
+    /// The mean and max-hold spectrum of what's on screen, lined up under the waterfall.
+    pub(super) fn spectrum_ui(&mut self, ui: &egui::Ui, outer: Rect, geometry: &Geometry) {
+        let key: Key = (self.view, self.tiles.finest(&self.view));
+        if self.spectrum.as_ref().map(|(k, _)| k) != Some(&key) {
+            let trace = trace(
+                key.1
+                    .iter()
+                    .filter_map(|key| self.tiles.waterfall(key))
+                    .map(|waterfall| waterfall.as_ref()),
+                &self.view,
+            );
+            self.spectrum = Some((key, trace));
+        }
+        let trace = match &self.spectrum {
+            Some((_, trace)) if !trace.frequencies.is_empty() => trace,
+            _ => return,
+        };
+
+        let plot = Rect::from_x_y_ranges(geometry.plot.x_range(), outer.y_range());
+        let response = ui.interact(plot, ui.id().with("spectrum"), Sense::hover());
+        let painter = ui.painter_at(outer);
+        painter.rect_filled(plot, 0., Color32::BLACK);
+
+        // only the bins in view decide the scale
+        let (low, high) = geometry.frequencies;
+        let in_view = |i: &usize| (low..=high).contains(&trace.frequencies[*i]);
+        let (floor, ceiling) = (0..trace.frequencies.len())
+            .filter(in_view)
+            .fold((f32::INFINITY, f32::NEG_INFINITY), |(lo, hi), i| {
+                (lo.min(trace.mean[i]), hi.max(trace.max[i]))
+            });
+        if !floor.is_finite() || !ceiling.is_finite() {
+            return;
+        }
+        let (floor, ceiling) = (floor - HEADROOM_DB, ceiling + HEADROOM_DB);
+        let y_of = |db: f32| plot.bottom() - (db - floor) / (ceiling - floor) * plot.height();
+
+        let text = ui.visuals().text_color();
+        let font = FontId::proportional(11.);
+        let max_ticks = (plot.height() / 30.).max(2.) as usize;
+        for tick in ticks(f64::from(floor), f64::from(ceiling), max_ticks) {
+            let y = y_of(tick as f32);
+            painter.hline(plot.x_range(), y, Stroke::new(1., Color32::from_gray(48)));
+            painter.text(
+                Pos2::new(plot.left() - 6., y),
+                Align2::RIGHT_CENTER,
+                format!("{}dB", tick),
+                font.clone(),
+                text,
+            );
+        }
+
+        // one bin either side, so the lines run off the edges
+        let first = trace
+            .frequencies
+            .partition_point(|&f| f < low)
+            .saturating_sub(1);
+        let last = (trace.frequencies.partition_point(|&f| f <= high) + 1).min(trace.mean.len());
+        let clipped = painter.with_clip_rect(plot);
+        for (values, colour) in [(&trace.max, MAX_COLOUR), (&trace.mean, MEAN_COLOUR)] {
+            let points = (first..last)
+                .map(|i| Pos2::new(geometry.x_of(trace.frequencies[i]), y_of(values[i])))
+                .collect();
+            clipped.add(Shape::line(points, Stroke::new(1., colour)));
+        }
+
+        let mut legend = vec![
+            ("max hold".to_string(), MAX_COLOUR),
+            ("mean".to_string(), MEAN_COLOUR),
+        ];
+        if let Some(pos) = response.hover_pos() {
+            clipped.vline(
+                pos.x,
+                plot.y_range(),
+                Stroke::new(1., Color32::from_white_alpha(64)),
+            );
+            let frequency = geometry.frequency_at(pos.x);
+            let i = trace
+                .frequencies
+                .partition_point(|&f| f < frequency)
+                .min(trace.mean.len() - 1);
+            legend = vec![
+                (
+                    format!("{}Hz", format_si(geometry.display_frequency(frequency))),
+                    text,
+                ),
+                (format!("max hold {:.1}dB", trace.max[i]), MAX_COLOUR),
+                (format!("mean {:.1}dB", trace.mean[i]), MEAN_COLOUR),
+            ];
+        }
+        let mut corner = plot.right_top() + egui::vec2(-4., 2.);
+        for (label, colour) in legend {
+            let drawn = painter.text(corner, Align2::RIGHT_TOP, label, font.clone(), colour);
+            corner.y = drawn.bottom();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::trace;
+    use crate::eui::view::View;
+    use crate::eui::waterfall::{FftSettings, Waterfall};
+    use crate::ffts::{Combine, Windowing};
+    use crate::gen::Gen;
+
+    #[test]
+    fn full_scale_tone() {
+        let tone = Gen::new(vec![1_000], 8_000, 1.).unwrap();
+        let settings = FftSettings {
+            width: 64,
+            windowing: Windowing::Hann,
+            combine: Combine::First,
+            overlap: 0.5,
+        };
+        let waterfall = Waterfall::render_range(&tone, (0, 4_000), &settings, 32).unwrap();
+        let view = View {
+            start: 0.,
+            end: 1.,
+            low: -4_000.,
+            high: 4_000.,
+        };
+        let trace = trace([&waterfall], &view);
+        assert_eq!(64, trace.frequencies.len());
+
+        let peak = (0..64)
+            .max_by(|&a, &b| trace.mean[a].total_cmp(&trace.mean[b]))
+            .unwrap();
+        assert!(
+            (trace.frequencies[peak] - 1_000.).abs() < 8_000. / 64.,
+            "{:?}",
+            trace
+        );
+        // a steady tone: the mean is the max, and both are in the waterfall's terms
+        assert!((trace.mean[peak] - trace.max[peak]).abs() < 0.1);
+
+        // in a bin's centre, so the window loses nothing: 0dBFS
+        assert!(trace.mean[peak].abs() < 0.1, "{}", trace.mean[peak]);
+    }
+}
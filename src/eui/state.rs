@@ -0,0 +1,298 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use super::measure::Marker;
+use super::open::Recent;
+use super::view::View;
+use super::waterfall::FftSettings;
+use super::ManageApp;
+use crate::colormap::Colormap;
+use crate::ffts::{Combine, Scale, Windowing};
+use crate::Operation;
+
+const RECENT_KEY: &str = "recent";
+const FILES_KEY: &str = "files";
+const DEFAULTS_KEY: &str = "defaults";
+
+/// How many files to remember the views of.
+const MAX_FILES: usize = 100;
+
+/// How the waterfall is drawn; the same for every file, unless they've been changed.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Settings {
+    pub fft: FftSettings,
+    pub colormap: Colormap,
+    pub scale: Scale,
+    pub floor: f32,
+    pub ceiling: f32,
+    pub auto_levels: bool,
+    pub absolute: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            fft: FftSettings {
+                width: 512,
+                windowing: Windowing::BlackmanHarris,
+                combine: Combine::First,
+                overlap: 0.5,
+            },
+            colormap: Colormap::Viridis,
+            scale: Scale::Decibels,
+            floor: -100.,
+            ceiling: 0.,
+            auto_levels: true,
+            absolute: true,
+        }
+    }
+}
+
+/// The stages the pipeline editor can make, which are all that's worth keeping.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+enum Stage {
+    Shift {
+        frequency: i64,
+    },
+    LowPass {
+        size: usize,
+        decimate: u64,
+        frequency: u64,
+    },
+    Resample {
+        rate: u64,
+    },
+}
+
+impl Stage {
+    fn from_operation(op: &Operation) -> Option<Stage> {
+        Some(match *op {
+            Operation::Shift { frequency } => Stage::Shift { frequency },
+            Operation::LowPass {
+                size,
+                decimate,
+                frequency,
+            } => Stage::LowPass {
+                size,
+                decimate,
+                frequency,
+            },
+            Operation::Resample { rate } => Stage::Resample { rate },
+            _ => return None,
+        })
+    }
+
+    fn operation(&self) -> Operation {
+        match *self {
+            Stage::Shift { frequency } => Operation::Shift { frequency },
+            Stage::LowPass {
+                size,
+                decimate,
+                frequency,
+            } => Operation::LowPass {
+                size,
+                decimate,
+                frequency,
+            },
+            Stage::Resample { rate } => Operation::Resample { rate },
+        }
+    }
+}
+
+/// Where we were in a file, when it was last open.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileState {
+    path: PathBuf,
+    /// In bytes; a file of a different length is a different capture.
+    length: u64,
+    view: View,
+    settings: Settings,
+    stages: Vec<Stage>,
+    markers: Vec<Marker>,
+}
+
+/// Everything kept between runs, which is shared by all the tabs.
+#[derive(Default)]
+pub struct Memory {
+    pub recent: Vec<Recent>,
+    /// Where we were in each file, most recent first.
+    pub files: Vec<FileState>,
+    /// The settings for files we haven't seen before.
+    pub defaults: Settings,
+}
+
+impl Memory {
+    pub fn load(storage: Option<&dyn eframe::Storage>) -> Memory {
+        let storage = match storage {
+            Some(storage) => storage,
+            None => return Memory::default(),
+        };
+        Memory {
+            recent: eframe::get_value(storage, RECENT_KEY).unwrap_or_default(),
+            files: eframe::get_value(storage, FILES_KEY).unwrap_or_default(),
+            defaults: eframe::get_value(storage, DEFAULTS_KEY).unwrap_or_default(),
+        }
+    }
+
+    pub fn save(&self, storage: &mut dyn eframe::Storage) {
+        eframe::set_value(storage, RECENT_KEY, &self.recent);
+        eframe::set_value(storage, FILES_KEY, &self.files);
+        eframe::set_value(storage, DEFAULTS_KEY, &self.defaults);
+    }
+}
+
+/// The file, and its length, for looking it up in the states.
+fn identify(path: &Path) -> Option<(PathBuf, u64)> {
+    let path = fs::canonicalize(path).ok()?;
+    let length = fs::metadata(&path).ok()?.len();
+    Some((path, length))
+}
+
+impl ManageApp {
+    pub(super) fn settings(&self) -> Settings {
+        Settings {
+            fft: self.fft,
+            colormap: self.colormap,
+            scale: self.scale,
+            floor: self.floor,
+            ceiling: self.ceiling,
+            auto_levels: self.auto_levels,
+            absolute: self.absolute,
+        }
+    }
+
+    pub(super) fn apply_settings(&mut self, settings: Settings) {
+        if settings.fft != self.fft {
+            self.fft = settings.fft;
+            self.invalidate();
+        }
+        self.colormap = settings.colormap;
+        self.scale = settings.scale;
+        self.floor = settings.floor;
+        self.ceiling = settings.ceiling;
+        self.auto_levels = settings.auto_levels;
+        self.absolute = settings.absolute;
+        self.recolour = true;
+    }
+
+    /// Save where we are in the current file, if it is one, for the next time it's opened.
+    pub(super) fn remember_file(&self, files: &mut Vec<FileState>) {
+        let (path, length) = match self.annotations.capture.as_deref().and_then(identify) {
+            Some(identity) => identity,
+            None => return,
+        };
+        let state = FileState {
+            view: self.view,
+            settings: self.settings(),
+            stages: self
+                .stages
+                .iter()
+                .filter_map(Stage::from_operation)
+                .collect(),
+            markers: self.markers.list.clone(),
+            path,
+            length,
+        };
+        files.retain(|file| file.path != state.path);
+        files.insert(0, state);
+        files.truncate(MAX_FILES);
+    }
+
+    /// Put the settings and stages back as they were for this file, or to the defaults;
+    /// returning the view and markers, to restore once the pipeline is built.
+    pub(super) fn restore_file(
+        &mut self,
+        capture: &Path,
+        memory: &Memory,
+    ) -> Option<(View, Vec<Marker>)> {
+        let state = identify(capture).and_then(|(path, length)| {
+            memory
+                .files
+                .iter()
+                .find(|file| file.path == path && file.length == length)
+                .cloned()
+        });
+        match state {
+            Some(state) => {
+                self.apply_settings(state.settings);
+                self.stages = state.stages.iter().map(Stage::operation).collect();
+                Some((state.view, state.markers))
+            }
+            None => {
+                self.apply_settings(memory.defaults);
+                self.stages.clear();
+                None
+            }
+        }
+    }
+
+    /// Menu items to make the current settings the defaults, or go back to them.
+    pub(super) fn defaults_ui(&mut self, ui: &mut egui::Ui, defaults: &mut Settings) {
+        let current = self.settings();
+        if ui
+            .add_enabled(current != *defaults, egui::Button::new("Save as defaults"))
+            .on_hover_text("the fft, levels and colours for files which haven't been opened before")
+            .clicked()
+        {
+            *defaults = current;
+            ui.close_menu();
+        }
+        if ui
+            .add_enabled(current != *defaults, egui::Button::new("Restore defaults"))
+            .clicked()
+        {
+            self.apply_settings(*defaults);
+            ui.close_menu();
+        }
+        if ui
+            .button("Forget saved defaults")
+            .on_hover_text("go back to the built-in settings")
+            .clicked()
+        {
+            *defaults = Settings::default();
+            ui.close_menu();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FileState, Settings, Stage};
+    use crate::eui::view::View;
+    use crate::Operation;
+
+    #[test]
+    fn round_trip() {
+        let state = FileState {
+            path: "/tmp/fsk.sr2M.cu8".into(),
+            length: 4_000_000,
+            view: View {
+                start: 0.25,
+                end: 0.5,
+                low: -20_000.,
+                high: 20_000.,
+            },
+            settings: Settings::default(),
+            stages: [
+                Operation::Shift {
+                    frequency: -120_000,
+                },
+                Operation::Resample { rate: 48_000 },
+            ]
+            .iter()
+            .filter_map(Stage::from_operation)
+            .collect(),
+            markers: Vec::new(),
+        };
+        let json = serde_json::to_string(&state).unwrap();
+        let back: FileState = serde_json::from_str(&json).unwrap();
+        assert_eq!(state.view, back.view);
+        assert_eq!(state.settings, back.settings);
+        match back.stages[0].operation() {
+            Operation::Shift { frequency } => assert_eq!(-120_000, frequency),
+            other => panic!("unexpected: {:?}", other),
+        }
+    }
+}
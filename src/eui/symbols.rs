@@ -0,0 +1,282 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use egui_plot::{Line, Plot, PlotPoints, Points};
+use poll_promise::Promise;
+use rustfft::num_complex::Complex;
+
+use super::view::View;
+use super::ManageApp;
+use crate::{Operation, Samples};
+
+/// Samples per symbol, after resampling.
+const OVERSAMPLE: usize = 16;
+
+/// Plotting more than this many symbols doesn't add much.
+const MAX_SYMBOLS: usize = 4096;
+
+/// Each eye trace is this many symbols long.
+const EYE_SYMBOLS: usize = 2;
+const MAX_TRACES: usize = 256;
+
+/// The selection, symbol rate and carrier offset.
+type Key = (View, u64, i64);
+type Resampled = Result<Vec<Complex<f32>>, String>;
+
+pub struct SymbolSettings {
+    pub show: bool,
+    /// Symbols per second.
+    rate: u64,
+    /// Hz to remove, relative to the centre; usually the middle of the selection.
+    offset: i64,
+    /// Where, within each symbol, to take the constellation point, in `1/OVERSAMPLE`s.
+    phase: usize,
+    show_q: bool,
+
+    /// The resampled selection, and the parameters it was made with.
+    resampled: Option<(Key, Resampled)>,
+    /// The latest resampling, while it's running.
+    resampling: Option<(Key, Promise<Resampled>)>,
+}
+
+impl Default for SymbolSettings {
+    fn default() -> Self {
+        SymbolSettings {
+            show: false,
+            rate: 9_600,
+            offset: 0,
+            phase: 0,
+            show_q: false,
+            resampled: None,
+            resampling: None,
+        }
+    }
+}
+
+/// Shift the selection to zero, and resample it to `OVERSAMPLE` samples per symbol.
+fn resample(samples: &Arc<dyn Samples>, selection: &View, rate: u64, offset: i64) -> Resampled {
+    let wanted = rate * OVERSAMPLE as u64;
+    let end = selection
+        .end
+        .min(selection.start + MAX_SYMBOLS as f64 / rate as f64);
+    let operations = [
+        Operation::Slice {
+            start: Some(selection.start.max(0.)),
+            end: Some(end),
+        },
+        Operation::Shift { frequency: -offset },
+        Operation::Resample { rate: wanted },
+    ];
+
+    let mut output: Box<dyn Samples> = Box::new(Arc::clone(samples));
+    for op in &operations {
+        output = op
+            .exec(Some(output))
+            .map_err(|e| format!("{:#}", e))?
+            .ok_or_else(|| "no output".to_string())?;
+    }
+
+    let mut buf = vec![Complex::default(); output.len() as usize];
+    output
+        .read_exact_at(0, &mut buf)
+        .map_err(|e| format!("{:#}", e))?;
+    normalise(&mut buf);
+    Ok(buf)
+}
+
+/// Scale to unit RMS, so constellations of different captures look alike.
+fn normalise(samples: &mut [Complex<f32>]) {
+    let power = samples.iter().map(|s| s.norm_sqr()).sum::<f32>() / samples.len().max(1) as f32;
+    if power > 0. {
+        let scale = power.sqrt().recip();
+        samples.iter_mut().for_each(|s| *s *= scale);
+    }
+}
+
+/// One sample per symbol, `phase` samples into each.
+fn symbol_points(samples: &[Complex<f32>], phase: usize) -> Vec<Complex<f32>> {
+    samples
+        .iter()
+        .skip(phase)
+        .step_by(OVERSAMPLE)
+        .cloned()
+        .collect()
+}
+
+/// Cut into traces of `EYE_SYMBOLS` symbols, with x in symbols.
+fn eye_traces(values: &[f32]) -> Vec<Vec<[f64; 2]>> {
+    let per_trace = EYE_SYMBOLS * OVERSAMPLE;
+    values
+        // each trace shares an end point with the next, so the lines join up
+        .windows(per_trace + 1)
+        .step_by(per_trace)
+        .take(MAX_TRACES)
+        .map(|trace| {
+            trace
+                .iter()
+                .enumerate()
+                .map(|(i, v)| [i as f64 / OVERSAMPLE as f64, f64::from(*v)])
+                .collect()
+        })
+        .collect()
+}
+
+impl ManageApp {
+    /// Constellation and eye diagram of the selection.
+    pub(super) fn symbols_ui(&mut self, ctx: &egui::Context, samples: &Arc<dyn Samples>) {
+        if !self.symbols.show {
+            return;
+        }
+        let selection = match self.selection {
+            Some(selection) => selection,
+            None => return,
+        };
+
+        let mut open = true;
+        egui::Window::new("Symbols")
+            .open(&mut open)
+            .default_size([700., 420.])
+            .show(ctx, |ui| {
+                let settings = &mut self.symbols;
+                let half_rate = (samples.sample_rate() / 2) as i64;
+
+                ui.horizontal(|ui| {
+                    ui.label("symbol rate");
+                    ui.add(
+                        egui::DragValue::new(&mut settings.rate)
+                            .range(1..=samples.sample_rate())
+                            .speed(10)
+                            .suffix(" Bd"),
+                    );
+                    ui.label("carrier offset");
+                    ui.add(
+                        egui::DragValue::new(&mut settings.offset)
+                            .range(-half_rate + 1..=half_rate - 1)
+                            .speed(10)
+                            .suffix(" Hz"),
+                    );
+                    if ui
+                        .button("from selection")
+                        .on_hover_text("the middle of the selected band")
+                        .clicked()
+                    {
+                        settings.offset = ((selection.low + selection.high) / 2.).round() as i64;
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.add(
+                        egui::Slider::new(&mut settings.phase, 0..=OVERSAMPLE - 1)
+                            .text("sampling phase"),
+                    );
+                    ui.checkbox(&mut settings.show_q, "Q in the eye");
+                });
+
+                if let Some((key, promise)) = settings.resampling.take() {
+                    match promise.try_take() {
+                        Ok(resampled) => settings.resampled = Some((key, resampled)),
+                        Err(promise) => {
+                            settings.resampling = Some((key, promise));
+                            ui.ctx().request_repaint_after(Duration::from_millis(50));
+                        }
+                    }
+                }
+
+                // anything older than the latest request is thrown away when it's replaced
+                let key = (selection, settings.rate, settings.offset);
+                let wanted = |k: Option<&Key>| k == Some(&key);
+                if !wanted(settings.resampled.as_ref().map(|(k, _)| k))
+                    && !wanted(settings.resampling.as_ref().map(|(k, _)| k))
+                {
+                    let samples = Arc::clone(samples);
+                    let (selection, rate, offset) = key;
+                    settings.resampling = Some((
+                        key,
+                        Promise::spawn_thread("symbols", move || {
+                            resample(&samples, &selection, rate, offset)
+                        }),
+                    ));
+                }
+
+                let resampled = match &settings.resampled {
+                    Some((_, Ok(resampled))) => resampled,
+                    Some((_, Err(e))) => {
+                        ui.colored_label(ui.visuals().error_fg_color, e);
+                        return;
+                    }
+                    None => {
+                        ui.spinner();
+                        return;
+                    }
+                };
+
+                let size = (ui.available_height()).min(ui.available_width() / 2. - 8.);
+                ui.horizontal(|ui| {
+                    Plot::new("constellation")
+                        .width(size)
+                        .height(size)
+                        .data_aspect(1.)
+                        .show(ui, |plot| {
+                            let points: PlotPoints = symbol_points(resampled, settings.phase)
+                                .iter()
+                                .map(|s| [f64::from(s.re), f64::from(s.im)])
+                                .collect();
+                            plot.points(
+                                Points::new(points).radius(1.5).color(
+                                    egui::Color32::from_rgba_unmultiplied(100, 200, 255, 96),
+                                ),
+                            );
+                        });
+
+                    Plot::new("eye").height(size).show(ui, |plot| {
+                        let i: Vec<f32> = resampled.iter().map(|s| s.re).collect();
+                        let q: Vec<f32> = resampled.iter().map(|s| s.im).collect();
+                        let mut channels =
+                            vec![(i, egui::Color32::from_rgba_unmultiplied(100, 200, 255, 48))];
+                        if settings.show_q {
+                            channels
+                                .push((q, egui::Color32::from_rgba_unmultiplied(255, 160, 80, 48)));
+                        }
+                        for (values, colour) in channels {
+                            for trace in eye_traces(&values) {
+                                plot.line(Line::new(PlotPoints::from(trace)).color(colour));
+                            }
+                        }
+                        let phase = settings.phase as f64 / OVERSAMPLE as f64;
+                        for symbol in 0..EYE_SYMBOLS {
+                            plot.vline(egui_plot::VLine::new(symbol as f64 + phase));
+                        }
+                    });
+                });
+            });
+
+        if !open {
+            self.symbols.show = false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rustfft::num_complex::Complex;
+
+    use super::{eye_traces, normalise, symbol_points, OVERSAMPLE};
+
+    #[test]
+    fn folding() {
+        let values: Vec<f32> = (0..OVERSAMPLE * 5).map(|i| i as f32).collect();
+        let traces = eye_traces(&values);
+        assert_eq!(2, traces.len());
+        assert_eq!(2 * OVERSAMPLE + 1, traces[0].len());
+        assert_eq!([0., 2. * OVERSAMPLE as f64], traces[1][0]);
+        assert_eq!([2., 4. * OVERSAMPLE as f64], traces[1][2 * OVERSAMPLE]);
+
+        let mut samples: Vec<Complex<f32>> = values.iter().map(|v| Complex::new(*v, 0.)).collect();
+        let points = symbol_points(&samples, 3);
+        assert_eq!(5, points.len());
+        assert_eq!(Complex::new((OVERSAMPLE + 3) as f32, 0.), points[1]);
+
+        normalise(&mut samples);
+        let power: f32 = samples.iter().map(|s| s.norm_sqr()).sum::<f32>() / samples.len() as f32;
+        assert!((power - 1.).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,413 @@
+//! The waterfall, cut into tiles of rows at power-of-two zoom levels, which are rendered
+//! on a pool of threads, and kept around until memory is needed for something else.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use egui::{ColorImage, TextureHandle};
+
+use super::view::View;
+use super::waterfall::{FftSettings, Waterfall};
+use crate::colormap::Colormap;
+use crate::ffts::{FftResult, Scale};
+use crate::Samples;
+
+/// Rows in each tile; fewer for the very end of the samples.
+const TILE_ROWS: u64 = 256;
+
+/// Each row is at least two samples apart, as `take_fft` needs more samples than rows.
+const MIN_LEVEL: u32 = 1;
+
+/// The first pass is this many levels coarser, so there's something on screen quickly.
+const COARSE_LEVELS: u32 = 2;
+
+/// Finished tiles which aren't on screen are forgotten, oldest first, beyond this.
+const MAX_CACHED_BYTES: usize = 512 << 20;
+
+/// Tiles are only reused for the same samples and settings; i.e. the same generation.
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
+pub struct TileKey {
+    generation: u64,
+    /// There are `2^level` samples between rows.
+    level: u32,
+    /// Counting from the start of the samples, in tiles of this level.
+    index: u64,
+}
+
+impl TileKey {
+    fn samples_per_row(&self) -> u64 {
+        1 << self.level
+    }
+
+    /// The samples the tile covers, and how many rows that is, if it has any.
+    fn range(&self, samples: &dyn Samples, fft_width: usize) -> Option<((u64, u64), usize)> {
+        let start = self.index * TILE_ROWS * self.samples_per_row();
+        let last = samples.len().checked_sub(fft_width as u64 + 1)?;
+        let rows = ((last.saturating_sub(start)) / self.samples_per_row()).min(TILE_ROWS);
+        if 0 == rows {
+            return None;
+        }
+        let end = start + rows * self.samples_per_row();
+        Some(((start, end), rows as usize))
+    }
+}
+
+/// What a texture was coloured with, so it can be redone if any of that changes.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Colouring {
+    pub colormap: Colormap,
+    pub scale: Scale,
+    pub range: (f32, f32),
+}
+
+pub struct Tile {
+    pub waterfall: Arc<Waterfall>,
+    texture: Option<(Colouring, TextureHandle)>,
+    /// When it was last on screen, in calls to `request`.
+    used: u64,
+}
+
+struct Job {
+    key: TileKey,
+    samples: Arc<dyn Samples>,
+    settings: FftSettings,
+}
+
+/// A render, or `None` if the tile was no longer wanted by the time a worker got to it.
+type Outcome = (TileKey, Option<Result<Waterfall, String>>);
+
+pub struct Tiles {
+    jobs: Sender<Job>,
+    outcomes: Receiver<Outcome>,
+    /// The workers skip anything which isn't in here.
+    wanted: Arc<Mutex<HashSet<TileKey>>>,
+
+    cache: HashMap<TileKey, Tile>,
+    /// Sent to the workers, and not heard back about yet.
+    pending: HashSet<TileKey>,
+    generation: u64,
+    frame: u64,
+    /// The level of the most recent request; anything finer isn't needed.
+    level: u32,
+
+    /// From the most recent render to fail.
+    pub error: Option<String>,
+}
+
+impl Tiles {
+    pub fn new(ctx: &egui::Context) -> Tiles {
+        let (jobs, job_receiver) = mpsc::channel::<Job>();
+        let (outcome_sender, outcomes) = mpsc::channel();
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+        let wanted = Arc::new(Mutex::new(HashSet::new()));
+
+        // leave a core for the ui
+        let workers = thread::available_parallelism()
+            .map(|n| n.get().saturating_sub(1))
+            .unwrap_or(1)
+            .clamp(1, 8);
+        for i in 0..workers {
+            let job_receiver = Arc::clone(&job_receiver);
+            let outcome_sender = outcome_sender.clone();
+            let wanted = Arc::clone(&wanted);
+            let ctx = ctx.clone();
+            thread::Builder::new()
+                .name(format!("tiles {}", i))
+                .spawn(move || work(&job_receiver, &outcome_sender, &wanted, &ctx))
+                .expect("starting a thread");
+        }
+
+        Tiles {
+            jobs,
+            outcomes,
+            wanted,
+            cache: HashMap::new(),
+            pending: HashSet::new(),
+            generation: 0,
+            frame: 0,
+            level: MIN_LEVEL,
+            error: None,
+        }
+    }
+
+    /// Forget everything; the samples or the settings have changed.
+    pub fn reset(&mut self) {
+        self.generation += 1;
+        self.cache.clear();
+        self.wanted.lock().expect("poisoned").clear();
+        self.error = None;
+    }
+
+    /// Queue the tiles for the view which aren't already done, and cancel any which aren't.
+    pub fn request(
+        &mut self,
+        samples: &Arc<dyn Samples>,
+        settings: &FftSettings,
+        view: &View,
+        rows: usize,
+    ) {
+        self.frame += 1;
+        let sample_rate = samples.sample_rate() as f64;
+        let start = (view.start.max(0.) * sample_rate) as u64;
+        let end = (view.end.max(0.) * sample_rate) as u64;
+        let level = level_for(end.saturating_sub(start), rows);
+        self.level = level;
+
+        let generation = self.generation;
+        let keys = move |level: u32, prefetch: u64| {
+            let (first, last) = covering(level, start, end);
+            (first.saturating_sub(prefetch)..=last + prefetch).map(move |index| TileKey {
+                generation,
+                level,
+                index,
+            })
+        };
+
+        // in order: something quickly, then what's on screen, then either side of it
+        let mut wanted = Vec::new();
+        wanted.extend(keys(level + COARSE_LEVELS, 0));
+        wanted.extend(keys(level, 0));
+        wanted.extend(keys(level, 1));
+        let wanted: Vec<TileKey> = wanted
+            .into_iter()
+            .filter(|key| key.range(samples.as_ref(), settings.width).is_some())
+            .fold(Vec::new(), |mut unique, key| {
+                if !unique.contains(&key) {
+                    unique.push(key);
+                }
+                unique
+            });
+
+        for key in &wanted {
+            if let Some(tile) = self.cache.get_mut(key) {
+                tile.used = self.frame;
+            }
+        }
+
+        {
+            let mut shared = self.wanted.lock().expect("poisoned");
+            shared.clear();
+            shared.extend(wanted.iter().cloned());
+        }
+
+        for key in wanted {
+            if self.cache.contains_key(&key) || self.pending.contains(&key) {
+                continue;
+            }
+            let job = Job {
+                key,
+                samples: Arc::clone(samples),
+                settings: *settings,
+            };
+            if self.jobs.send(job).is_ok() {
+                self.pending.insert(key);
+            }
+        }
+    }
+
+    /// Collect anything the workers have finished; returns whether there was anything new.
+    pub fn poll(&mut self) -> bool {
+        let mut arrived = false;
+        while let Ok((key, outcome)) = self.outcomes.try_recv() {
+            self.pending.remove(&key);
+            match outcome {
+                Some(Ok(waterfall)) if key.generation == self.generation => {
+                    self.cache.insert(
+                        key,
+                        Tile {
+                            waterfall: Arc::new(waterfall),
+                            texture: None,
+                            used: self.frame,
+                        },
+                    );
+                    arrived = true;
+                }
+                Some(Err(e)) if key.generation == self.generation => self.error = Some(e),
+                _ => (),
+            }
+        }
+        if arrived {
+            self.evict();
+        }
+        arrived
+    }
+
+    /// There are renders which are wanted, and not yet done.
+    pub fn busy(&self) -> bool {
+        let wanted = self.wanted.lock().expect("poisoned");
+        self.pending.iter().any(|key| wanted.contains(key))
+    }
+
+    fn evict(&mut self) {
+        let bytes = |tile: &Tile| tile.waterfall.fft.output_len() * tile.waterfall.fft.width() * 4;
+        let mut total: usize = self.cache.values().map(bytes).sum();
+        if total <= MAX_CACHED_BYTES {
+            return;
+        }
+
+        let mut by_age: Vec<(u64, TileKey)> = self
+            .cache
+            .iter()
+            .filter(|(_, tile)| tile.used != self.frame)
+            .map(|(key, tile)| (tile.used, *key))
+            .collect();
+        by_age.sort_unstable_by_key(|(used, _)| *used);
+        for (_, key) in by_age {
+            if total <= MAX_CACHED_BYTES {
+                break;
+            }
+            if let Some(tile) = self.cache.remove(&key) {
+                total -= bytes(&tile);
+            }
+        }
+    }
+
+    /// The finished tiles overlapping the times of the view, coarsest first.
+    pub fn visible(&self, view: &View) -> Vec<TileKey> {
+        let mut keys: Vec<TileKey> = self
+            .cache
+            .iter()
+            .filter(|(key, _)| key.level >= self.level)
+            .filter(|(_, tile)| {
+                let (top, bottom) = tile.waterfall.times();
+                top < view.end && bottom > view.start
+            })
+            .map(|(key, _)| *key)
+            .collect();
+        keys.sort_unstable_by_key(|key| (std::cmp::Reverse(key.level), key.index));
+        keys
+    }
+
+    /// The visible tiles of the finest level there are any of, which don't overlap each other.
+    pub fn finest(&self, view: &View) -> Vec<TileKey> {
+        let keys = self.visible(view);
+        let finest = keys.last().map(|key| key.level);
+        keys.into_iter()
+            .filter(|key| Some(key.level) == finest)
+            .collect()
+    }
+
+    pub fn waterfall(&self, key: &TileKey) -> Option<&Arc<Waterfall>> {
+        self.cache.get(key).map(|tile| &tile.waterfall)
+    }
+
+    /// The tile's texture, coloured in as asked.
+    pub fn texture(
+        &mut self,
+        ctx: &egui::Context,
+        key: &TileKey,
+        colouring: Colouring,
+        colour: impl Fn(&FftResult) -> ColorImage,
+    ) -> Option<TextureHandle> {
+        let tile = self.cache.get_mut(key)?;
+        match &tile.texture {
+            Some((coloured, texture)) if *coloured == colouring => Some(texture.clone()),
+            _ => {
+                let image = colour(&tile.waterfall.fft);
+                let texture = ctx.load_texture(format!("{:?}", key), image, Default::default());
+                tile.texture = Some((colouring, texture.clone()));
+                Some(texture)
+            }
+        }
+    }
+}
+
+fn work(
+    jobs: &Mutex<Receiver<Job>>,
+    outcomes: &Sender<Outcome>,
+    wanted: &Mutex<HashSet<TileKey>>,
+    ctx: &egui::Context,
+) {
+    loop {
+        // the channel closes when the app goes away
+        let job = match jobs.lock().expect("poisoned").recv() {
+            Ok(job) => job,
+            Err(_) => return,
+        };
+
+        let still_wanted = wanted.lock().expect("poisoned").contains(&job.key);
+        let outcome =
+            still_wanted.then(
+                || match job.key.range(job.samples.as_ref(), job.settings.width) {
+                    Some((range, rows)) => {
+                        Waterfall::render_range(job.samples.as_ref(), range, &job.settings, rows)
+                    }
+                    None => Err("nothing to show here".to_string()),
+                },
+            );
+
+        if outcomes.send((job.key, outcome)).is_err() {
+            return;
+        }
+        ctx.request_repaint();
+    }
+}
+
+/// The zoom level with about `rows` rows across this many samples; never fewer.
+fn level_for(samples: u64, rows: usize) -> u32 {
+    let per_row = samples / (rows.max(1) as u64);
+    per_row.max(1).ilog2().max(MIN_LEVEL)
+}
+
+/// The first and last indexes of the tiles at `level` which cover these samples.
+fn covering(level: u32, start: u64, end: u64) -> (u64, u64) {
+    let tile = TILE_ROWS << level;
+    (start / tile, end.saturating_sub(1).max(start) / tile)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    use super::{covering, level_for, Tiles, TILE_ROWS};
+    use crate::eui::view::View;
+    use crate::eui::waterfall::FftSettings;
+    use crate::ffts::{Combine, Windowing};
+    use crate::gen::Gen;
+    use crate::Samples;
+
+    #[test]
+    fn levels_and_covering() {
+        // a million samples on a thousand pixels: 512 samples a row, so at least a row a pixel
+        assert_eq!(9, level_for(1_000_000, 1_000));
+        assert_eq!(1, level_for(100, 1_000));
+
+        let tile = TILE_ROWS << 9;
+        assert_eq!((0, 0), covering(9, 0, tile));
+        assert_eq!((0, 1), covering(9, tile - 1, tile + 1));
+        assert_eq!((3, 7), covering(9, 3 * tile, 7 * tile + 5));
+    }
+
+    #[test]
+    fn coarse_then_fine() {
+        let samples: Arc<dyn Samples> = Arc::new(Gen::new(vec![1_000], 100_000, 10.).unwrap());
+        let settings = FftSettings {
+            width: 64,
+            windowing: Windowing::Hann,
+            combine: Combine::First,
+            overlap: 0.,
+        };
+        let view = View::whole(samples.as_ref());
+        let mut tiles = Tiles::new(&egui::Context::default());
+
+        let deadline = Instant::now() + Duration::from_secs(30);
+        while tiles.busy() || tiles.visible(&view).is_empty() {
+            assert!(Instant::now() < deadline, "tiles never arrived");
+            tiles.request(&samples, &settings, &view, 500);
+            tiles.poll();
+            std::thread::sleep(Duration::from_millis(5));
+        }
+
+        // a million samples on 500 rows is level 10, and the coarse pass is 12
+        let levels: Vec<u32> = tiles.visible(&view).iter().map(|key| key.level).collect();
+        assert_eq!(12, levels[0]);
+        assert_eq!(Some(&10), levels.last());
+        assert!(tiles.error.is_none());
+
+        tiles.reset();
+        assert!(tiles.visible(&view).is_empty());
+    }
+}
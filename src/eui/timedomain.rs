@@ -0,0 +1,229 @@
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+use egui_plot::{Legend, Line, Plot, PlotPoints};
+use rustfft::num_complex::Complex;
+
+use super::view::View;
+use super::ManageApp;
+use crate::Samples;
+
+/// More than this is slow to plot, and impossible to read anyway.
+const MAX_SAMPLES: usize = 32_768;
+
+/// The samples of a selection, ready for plotting.
+pub struct TimeDomain {
+    /// Seconds from the start of the samples, of the first one.
+    start: f64,
+    sample_rate: f64,
+    pub samples: Vec<Complex<f32>>,
+    /// The selection was longer than we're willing to show.
+    truncated: bool,
+}
+
+impl TimeDomain {
+    pub fn read(samples: &dyn Samples, selection: &View) -> Result<TimeDomain, String> {
+        let sample_rate = samples.sample_rate() as f64;
+        let start = (selection.start * sample_rate) as u64;
+        let end = ((selection.end * sample_rate) as u64).min(samples.len());
+        if end <= start {
+            return Err("the selection is empty".to_string());
+        }
+
+        let wanted = (end - start) as usize;
+        let mut buf = vec![Complex::default(); wanted.min(MAX_SAMPLES)];
+        samples
+            .read_exact_at(start, &mut buf)
+            .map_err(|e| format!("{:#}", e))?;
+
+        Ok(TimeDomain {
+            start: start as f64 / sample_rate,
+            sample_rate,
+            samples: buf,
+            truncated: wanted > MAX_SAMPLES,
+        })
+    }
+
+    fn times(&self) -> impl Iterator<Item = f64> + '_ {
+        (0..self.samples.len()).map(move |i| self.start + i as f64 / self.sample_rate)
+    }
+
+    fn line(&self, values: impl Iterator<Item = f64>) -> PlotPoints<'static> {
+        self.times().zip(values).map(|(t, v)| [t, v]).collect()
+    }
+}
+
+/// `|z|`, optionally in dB (where full-scale is zero).
+pub fn magnitudes(samples: &[Complex<f32>], decibels: bool) -> Vec<f64> {
+    samples
+        .iter()
+        .map(|s| f64::from(s.norm()))
+        .map(|m| {
+            if decibels {
+                20. * m.max(1e-10).log10()
+            } else {
+                m
+            }
+        })
+        .collect()
+}
+
+/// The angle of each sample, in radians, without the jumps at ±π.
+pub fn unwrapped_phase(samples: &[Complex<f32>]) -> Vec<f64> {
+    let mut offset = 0.;
+    let mut previous: Option<f64> = None;
+    samples
+        .iter()
+        .map(|s| {
+            let phase = f64::from(s.arg());
+            if let Some(previous) = previous {
+                let jump = phase - previous;
+                if jump > PI {
+                    offset -= 2. * PI;
+                } else if jump < -PI {
+                    offset += 2. * PI;
+                }
+            }
+            previous = Some(phase);
+            phase + offset
+        })
+        .collect()
+}
+
+/// The rate of change of the phase between each pair of samples, in Hz.
+pub fn instantaneous_frequency(samples: &[Complex<f32>], sample_rate: f64) -> Vec<f64> {
+    samples
+        .windows(2)
+        .map(|pair| f64::from((pair[1] * pair[0].conj()).arg()) * sample_rate / (2. * PI))
+        .collect()
+}
+
+impl ManageApp {
+    /// Linked plots of the selected samples.
+    pub(super) fn time_domain_ui(&mut self, ctx: &egui::Context, samples: &Arc<dyn Samples>) {
+        if !self.show_time_domain {
+            return;
+        }
+
+        let selection = match self.selection {
+            Some(selection) => selection,
+            None => {
+                self.time_domain = None;
+                return;
+            }
+        };
+
+        if self.time_domain.as_ref().map(|(read, _)| *read) != Some(selection) {
+            self.time_domain = Some((selection, TimeDomain::read(samples.as_ref(), &selection)));
+        }
+
+        let mut open = true;
+        egui::Window::new("Time domain")
+            .open(&mut open)
+            .default_size([600., 600.])
+            .show(ctx, |ui| {
+                let time_domain = match &self.time_domain {
+                    Some((_, Ok(time_domain))) => time_domain,
+                    Some((_, Err(e))) => {
+                        ui.colored_label(ui.visuals().error_fg_color, e);
+                        return;
+                    }
+                    None => return,
+                };
+
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut self.magnitude_db, "magnitude in dB");
+                    if time_domain.truncated {
+                        ui.colored_label(
+                            ui.visuals().warn_fg_color,
+                            format!(
+                                "only showing the first {} samples of the selection",
+                                MAX_SAMPLES
+                            ),
+                        );
+                    }
+                });
+
+                let iq = &time_domain.samples;
+                let height = (ui.available_height() / 4. - 6.).max(80.);
+                let plot = |id: &str, label: &str| {
+                    Plot::new(id)
+                        .height(height)
+                        .link_axis("time domain", [true, false])
+                        .link_cursor("time domain", [true, false])
+                        .y_axis_label(label)
+                        .legend(Legend::default())
+                };
+
+                plot("iq", "amplitude").show(ui, |plot| {
+                    plot.line(
+                        Line::new(time_domain.line(iq.iter().map(|s| f64::from(s.re)))).name("I"),
+                    );
+                    plot.line(
+                        Line::new(time_domain.line(iq.iter().map(|s| f64::from(s.im)))).name("Q"),
+                    );
+                });
+
+                let magnitude_label = if self.magnitude_db {
+                    "dBFS"
+                } else {
+                    "magnitude"
+                };
+                plot("magnitude", magnitude_label).show(ui, |plot| {
+                    let values = magnitudes(iq, self.magnitude_db);
+                    plot.line(Line::new(time_domain.line(values.into_iter())).name("|z|"));
+                });
+
+                plot("phase", "radians").show(ui, |plot| {
+                    let values = unwrapped_phase(iq);
+                    plot.line(Line::new(time_domain.line(values.into_iter())).name("phase"));
+                });
+
+                plot("frequency", "Hz").show(ui, |plot| {
+                    let values = instantaneous_frequency(iq, time_domain.sample_rate);
+                    // each value is between two samples
+                    let half = 0.5 / time_domain.sample_rate;
+                    let points: PlotPoints = time_domain
+                        .times()
+                        .zip(values)
+                        .map(|(t, v)| [t + half, v])
+                        .collect();
+                    plot.line(Line::new(points).name("frequency"));
+                });
+            });
+
+        if !open {
+            self.show_time_domain = false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rustfft::num_complex::Complex;
+
+    #[test]
+    fn phase_and_frequency() {
+        use super::{instantaneous_frequency, unwrapped_phase};
+
+        // a quarter turn per sample: 250Hz at 1kHz
+        let samples: Vec<Complex<f32>> = (0..9)
+            .map(|i| Complex::from_polar(1., i as f32 * std::f32::consts::FRAC_PI_2))
+            .collect();
+
+        let phase = unwrapped_phase(&samples);
+        assert!(
+            (phase[8] - 4. * std::f64::consts::PI).abs() < 1e-5,
+            "{:?}",
+            phase
+        );
+
+        let frequency = instantaneous_frequency(&samples, 1_000.);
+        assert_eq!(8, frequency.len());
+        assert!(
+            frequency.iter().all(|f| (f - 250.).abs() < 1e-3),
+            "{:?}",
+            frequency
+        );
+    }
+}
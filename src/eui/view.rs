@@ -0,0 +1,126 @@
+use serde::{Deserialize, Serialize};
+
+use crate::Samples;
+
+/// Never zoom in further than this many samples, or this fraction of the band.
+const MIN_SAMPLES: f64 = 64.;
+const MIN_BAND_FRACTION: f64 = 1. / 65536.;
+
+/// The part of the samples on screen.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct View {
+    /// Seconds from the start of the samples.
+    pub start: f64,
+    pub end: f64,
+
+    /// Hz, relative to the centre, i.e. from `-sample_rate/2` to `sample_rate/2`.
+    pub low: f64,
+    pub high: f64,
+}
+
+impl View {
+    pub fn whole(samples: &dyn Samples) -> View {
+        let sample_rate = samples.sample_rate() as f64;
+        View {
+            start: 0.,
+            end: samples.len() as f64 / sample_rate,
+            low: -sample_rate / 2.,
+            high: sample_rate / 2.,
+        }
+    }
+
+    /// Scale the time span by `factor` (below one is zooming in), keeping `about` still.
+    pub fn zoom_time(&mut self, factor: f64, about: f64) {
+        self.start = about - (about - self.start) * factor;
+        self.end = about + (self.end - about) * factor;
+    }
+
+    /// Scale the band by `factor` (below one is zooming in), keeping `about` still.
+    pub fn zoom_frequency(&mut self, factor: f64, about: f64) {
+        self.low = about - (about - self.low) * factor;
+        self.high = about + (self.high - about) * factor;
+    }
+
+    pub fn pan(&mut self, seconds: f64, hz: f64) {
+        self.start += seconds;
+        self.end += seconds;
+        self.low += hz;
+        self.high += hz;
+    }
+
+    /// Centre the view on a point, keeping its size.
+    pub fn centre_on(&mut self, time: f64, frequency: f64) {
+        self.pan(
+            time - (self.start + self.end) / 2.,
+            frequency - (self.low + self.high) / 2.,
+        );
+    }
+
+    /// Keep within the samples, without zooming in further than is meaningful.
+    pub fn clamp(&mut self, samples: &dyn Samples) {
+        let whole = View::whole(samples);
+        let sample_rate = samples.sample_rate() as f64;
+
+        let (start, end) = clamp_range(
+            (self.start, self.end),
+            (whole.start, whole.end),
+            MIN_SAMPLES / sample_rate,
+        );
+        self.start = start;
+        self.end = end;
+
+        let (low, high) = clamp_range(
+            (self.low, self.high),
+            (whole.low, whole.high),
+            sample_rate * MIN_BAND_FRACTION,
+        );
+        self.low = low;
+        self.high = high;
+    }
+}
+
+/// Fit `(lo, hi)` inside `limits`, moving it if possible, and shrinking it if not.
+fn clamp_range((lo, hi): (f64, f64), limits: (f64, f64), min_span: f64) -> (f64, f64) {
+    let available = limits.1 - limits.0;
+    let span = if (hi - lo).is_finite() {
+        hi - lo
+    } else {
+        available
+    };
+    let span = span.max(min_span).min(available);
+    let lo = if lo.is_finite() { lo } else { limits.0 };
+    let lo = lo.min(limits.1 - span).max(limits.0);
+    (lo, lo + span)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::View;
+    use crate::gen::Gen;
+
+    #[test]
+    fn zoom_and_clamp() {
+        let gen = Gen::new(vec![0], 1_000, 10.).unwrap();
+        let mut view = View::whole(&gen);
+        assert_eq!(
+            (0., 10., -500., 500.),
+            (view.start, view.end, view.low, view.high)
+        );
+
+        view.zoom_time(0.5, 8.);
+        view.zoom_frequency(0.125, 100.);
+        assert_eq!((4., 9.), (view.start, view.end));
+        assert_eq!((25., 150.), (view.low, view.high));
+
+        // off the end: slides back into range, keeping the size
+        view.pan(3., 400.);
+        view.clamp(&gen);
+        assert_eq!((5., 10.), (view.start, view.end));
+        assert_eq!((375., 500.), (view.low, view.high));
+
+        // zoomed out too far: the whole thing
+        view.zoom_time(100., 0.);
+        view.clamp(&gen);
+        assert_eq!((0., 10.), (view.start, view.end));
+    }
+}
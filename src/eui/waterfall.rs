@@ -0,0 +1,464 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use egui::{Color32, Pos2, Rect, Sense, Stroke};
+use serde::{Deserialize, Serialize};
+
+use super::axes::{self, Geometry};
+use super::colour_image;
+use super::spectrum::HEIGHT as SPECTRUM_HEIGHT;
+use super::tiles::Colouring;
+use super::view::View;
+use super::ManageApp;
+use crate::axis::{format_seconds, format_si, format_si_exact};
+use crate::ffts::{take_fft_combined, Combine, FftConfig, FftResult, Scale, Windowing};
+use crate::render;
+use crate::Samples;
+
+/// Scroll events closer together than this are one zoom, for the history.
+const GESTURE_GAP: Duration = Duration::from_millis(500);
+const HISTORY_LIMIT: usize = 100;
+
+/// Width of the whole-file strip, beside the waterfall.
+const OVERVIEW_WIDTH: f32 = 96.;
+
+/// How to take the ffts for the waterfall.
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
+pub struct FftSettings {
+    /// Always a power of two.
+    pub width: usize,
+    pub windowing: Windowing,
+    pub combine: Combine,
+    /// Between the ffts in a row, when they're being combined; a fraction.
+    pub overlap: f64,
+}
+
+/// An fft of some samples, and where it came from.
+pub struct Waterfall {
+    pub fft: FftResult,
+    range: (u64, u64),
+    sample_rate: u64,
+}
+
+impl Waterfall {
+    /// The fft of the samples in the view's time range, across the whole band.
+    pub fn render(
+        samples: &dyn Samples,
+        view: &View,
+        settings: &FftSettings,
+        rows: usize,
+    ) -> Result<Waterfall, String> {
+        let sample_rate = samples.sample_rate();
+        let start = (view.start * sample_rate as f64) as u64;
+        let end = ((view.end * sample_rate as f64) as u64)
+            .min(samples.len().saturating_sub(settings.width as u64 + 1));
+        if end <= start + 1 {
+            return Err("nothing to show: too close to the end, or too short".to_string());
+        }
+        let rows = rows.min((end - start - 1) as usize).max(1);
+        Waterfall::render_range(samples, (start, end), settings, rows)
+    }
+
+    /// The fft of these samples, which must leave room for a whole fft at the end.
+    pub fn render_range(
+        samples: &dyn Samples,
+        (start, end): (u64, u64),
+        settings: &FftSettings,
+        rows: usize,
+    ) -> Result<Waterfall, String> {
+        let fft = take_fft_combined(
+            samples,
+            Some((start, end)),
+            &FftConfig {
+                width: settings.width,
+                windowing: settings.windowing,
+            },
+            rows,
+            settings.combine,
+            settings.overlap,
+        )
+        .map_err(|e| format!("{:#}", e))?;
+
+        Ok(Waterfall {
+            fft,
+            range: (start, end),
+            sample_rate: samples.sample_rate(),
+        })
+    }
+
+    /// Frequencies at the left and right edges of the image.
+    pub fn band(&self) -> (f64, f64) {
+        render::Axes::for_band(self.sample_rate as f64, 0., self.fft.get(0).len()).frequencies
+    }
+
+    /// Seconds at the top and bottom edges of the image.
+    pub fn times(&self) -> (f64, f64) {
+        let sample_rate = self.sample_rate as f64;
+        (
+            self.range.0 as f64 / sample_rate,
+            self.range.1 as f64 / sample_rate,
+        )
+    }
+
+    /// Where the image goes, which may be well outside the geometry's plot.
+    fn rect(&self, geometry: &Geometry) -> Rect {
+        let (left, right) = self.band();
+        let (top, bottom) = self.times();
+        Rect::from_min_max(
+            Pos2::new(geometry.x_of(left), geometry.y_of(top)),
+            Pos2::new(geometry.x_of(right), geometry.y_of(bottom)),
+        )
+    }
+
+    /// The magnitude at a point, if it is covered.
+    pub fn value_at(&self, time: f64, frequency: f64) -> Option<f32> {
+        let (left, right) = self.band();
+        let (top, bottom) = self.times();
+        let column = (frequency - left) / (right - left);
+        let row = (time - top) / (bottom - top);
+        if !(0. ..1.).contains(&column) || !(0. ..1.).contains(&row) {
+            return None;
+        }
+        let width = self.fft.get(0).len();
+        let row = (row * self.fft.output_len() as f64) as usize;
+        Some(self.fft.get(row)[(column * width as f64) as usize])
+    }
+
+    fn paint(&self, painter: &egui::Painter, geometry: &Geometry, texture: &egui::TextureHandle) {
+        painter.image(
+            texture.id(),
+            self.rect(geometry),
+            Rect::from_min_max(Pos2::new(0., 0.), Pos2::new(1., 1.)),
+            Color32::WHITE,
+        );
+    }
+}
+
+impl ManageApp {
+    /// The waterfall for the current view, as best we have it, and the overview beside it.
+    pub(super) fn waterfall_ui(&mut self, ui: &mut egui::Ui, samples: &Arc<dyn Samples>) {
+        let size = egui::vec2(ui.available_width(), ui.available_height() - 30.);
+        let (outer, response) = ui.allocate_exact_size(size, Sense::click_and_drag());
+        let (main, overview) = outer.split_left_right_at_x(outer.right() - OVERVIEW_WIDTH - 8.);
+        let overview = overview.with_min_x(overview.left() + 8.);
+        let (main, spectrum) = if self.show_spectrum {
+            let (main, spectrum) = main.split_top_bottom_at_y(main.bottom() - SPECTRUM_HEIGHT);
+            (main, Some(spectrum.with_min_y(spectrum.top() + 8.)))
+        } else {
+            (main, None)
+        };
+
+        let centre = samples
+            .centre_frequency()
+            .filter(|_| self.absolute)
+            .map(|centre| centre as f64);
+        let geometry = Geometry {
+            plot: Geometry::plot_area(main),
+            frequencies: (self.view.low, self.view.high),
+            times: (self.view.start, self.view.end),
+            centre,
+        };
+
+        // the texture is wanted in physical pixels, not points
+        let rows = (geometry.plot.height() * ui.ctx().pixels_per_point()).clamp(16., 2048.);
+        self.rows = rows as usize;
+        self.tiles
+            .request(samples, &self.fft, &self.view, self.rows);
+
+        let painter = ui.painter_at(main);
+        let plot_painter = painter.with_clip_rect(geometry.plot);
+        plot_painter.rect_filled(geometry.plot, 0., Color32::BLACK);
+        // coarse tiles first, so the fine ones are drawn over them as they arrive
+        let colouring = Colouring {
+            colormap: self.colormap,
+            scale: self.scale,
+            range: (self.floor, self.ceiling),
+        };
+        for key in self.tiles.visible(&self.view) {
+            let texture = self.tiles.texture(ui.ctx(), &key, colouring, |fft| {
+                colour_image(fft, colouring)
+            });
+            if let (Some(waterfall), Some(texture)) = (self.tiles.waterfall(&key), texture) {
+                waterfall.paint(&plot_painter, &geometry, &texture);
+            }
+        }
+        self.paint_annotations(&plot_painter, &geometry);
+        self.paint_markers(&plot_painter, &geometry);
+        if let Some(selection) = &self.selection {
+            let rect = Rect::from_min_max(
+                Pos2::new(geometry.x_of(selection.low), geometry.y_of(selection.start)),
+                Pos2::new(geometry.x_of(selection.high), geometry.y_of(selection.end)),
+            );
+            plot_painter.rect(
+                rect,
+                0.,
+                Color32::from_white_alpha(24),
+                Stroke::new(1.5, Color32::YELLOW),
+                egui::StrokeKind::Middle,
+            );
+        }
+        geometry.paint(&painter, ui.visuals().text_color());
+
+        if let Some(spectrum) = spectrum {
+            self.spectrum_ui(ui, spectrum, &geometry);
+        }
+
+        self.navigate(ui, &response, &geometry, samples);
+        self.overview_ui(ui, overview, samples);
+
+        let hovered = response
+            .hover_pos()
+            .filter(|pos| geometry.plot.contains(*pos));
+
+        let readout = match hovered {
+            Some(pos) => {
+                let stroke = Stroke::new(1., Color32::from_white_alpha(64));
+                plot_painter.hline(geometry.plot.x_range(), pos.y, stroke);
+                plot_painter.vline(pos.x, geometry.plot.y_range(), stroke);
+                self.readout(&geometry, pos)
+            }
+            None => "scroll: zoom time, shift+scroll: zoom frequency, drag: pan, \
+                 shift+drag: select, click: copy a shift, ctrl+click: place a marker, ?: keys"
+                .to_string(),
+        };
+
+        if let Some(pos) = hovered.filter(|_| response.clicked()) {
+            let frequency = geometry.frequency_at(pos.x);
+            if ui.input(|i| i.modifiers.command) {
+                self.place_marker(geometry.time_at(pos.y), frequency);
+            } else {
+                ui.ctx().copy_text(axes::shift_argument(frequency));
+            }
+        }
+
+        if let Some(pos) = hovered.filter(|_| response.secondary_clicked()) {
+            self.clicked_at = Some((geometry.time_at(pos.y), geometry.frequency_at(pos.x)));
+        }
+
+        response.context_menu(|ui| {
+            let (time, frequency) = match self.clicked_at {
+                Some(clicked) => clicked,
+                None => return,
+            };
+            let resolution = geometry.time_resolution();
+            let copies = [
+                axes::shift_argument(frequency),
+                axes::slice_argument("start", time, resolution),
+                axes::slice_argument("end", time, resolution),
+                format_si_exact(geometry.display_frequency(frequency).round() as i64),
+            ];
+            for text in copies {
+                if ui.button(format!("Copy: {}", text)).clicked() {
+                    ui.ctx().copy_text(text);
+                    ui.close_menu();
+                }
+            }
+            ui.separator();
+            if ui.button("Place marker").clicked() {
+                self.place_marker(time, frequency);
+                ui.close_menu();
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.add_enabled_ui(samples.centre_frequency().is_some(), |ui| {
+                ui.checkbox(&mut self.absolute, "absolute frequencies")
+                    .on_disabled_hover_text("the centre frequency is unknown");
+            });
+            if ui
+                .add_enabled(!self.history.is_empty(), egui::Button::new("⏴ back"))
+                .on_hover_text("undo the last zoom or pan")
+                .clicked()
+            {
+                if let Some(previous) = self.history.pop() {
+                    self.set_view(previous, samples);
+                }
+            }
+            if ui.button("whole file").clicked() {
+                self.remember_view(true);
+                self.set_view(View::whole(samples.as_ref()), samples);
+            }
+            if self.tiles.busy() {
+                ui.spinner();
+            }
+            ui.monospace(readout);
+        });
+    }
+
+    /// Scroll to zoom, drag to pan.
+    fn navigate(
+        &mut self,
+        ui: &egui::Ui,
+        response: &egui::Response,
+        geometry: &Geometry,
+        samples: &Arc<dyn Samples>,
+    ) {
+        let mut view = self.view;
+
+        if let Some(pos) = response
+            .hover_pos()
+            .filter(|pos| geometry.plot.contains(*pos))
+        {
+            let scroll = ui.input(|i| i.smooth_scroll_delta);
+            if scroll != egui::Vec2::ZERO {
+                self.remember_view(false);
+                view.zoom_time(f64::from((-scroll.y / 200.).exp()), geometry.time_at(pos.y));
+                view.zoom_frequency(
+                    f64::from((-scroll.x / 200.).exp()),
+                    geometry.frequency_at(pos.x),
+                );
+            }
+        }
+
+        if response.drag_started() {
+            let shift = ui.input(|i| i.modifiers.shift);
+            let origin = ui.input(|i| i.pointer.press_origin());
+            match origin.filter(|pos| shift && geometry.plot.contains(*pos)) {
+                Some(pos) => {
+                    self.selecting = Some((geometry.time_at(pos.y), geometry.frequency_at(pos.x)))
+                }
+                None => self.remember_view(true),
+            }
+        }
+
+        if response.drag_stopped() {
+            self.selecting = None;
+        }
+
+        if let Some((time, frequency)) = self.selecting {
+            if let Some(pos) = response.interact_pointer_pos() {
+                let (other_time, other_frequency) = (
+                    geometry.time_at(pos.y.clamp(geometry.plot.top(), geometry.plot.bottom())),
+                    geometry.frequency_at(pos.x.clamp(geometry.plot.left(), geometry.plot.right())),
+                );
+                self.selection = Some(View {
+                    start: time.min(other_time),
+                    end: time.max(other_time),
+                    low: frequency.min(other_frequency),
+                    high: frequency.max(other_frequency),
+                });
+                self.export.outcome = None;
+            }
+        } else if response.dragged() {
+            let delta = response.drag_delta();
+            let (left, right) = geometry.frequencies;
+            let (top, bottom) = geometry.times;
+            view.pan(
+                -f64::from(delta.y / geometry.plot.height()) * (bottom - top),
+                -f64::from(delta.x / geometry.plot.width()) * (right - left),
+            );
+        }
+
+        if view != self.view {
+            self.set_view(view, samples);
+        }
+    }
+
+    /// The whole of the samples, with the current view outlined. Click to go somewhere.
+    fn overview_ui(&mut self, ui: &egui::Ui, outer: Rect, samples: &Arc<dyn Samples>) {
+        let whole = View::whole(samples.as_ref());
+        let geometry = Geometry {
+            // lined up with the waterfall, below its frequency labels
+            plot: outer.with_min_y(Geometry::plot_area(outer).top()),
+            frequencies: (whole.low, whole.high),
+            times: (whole.start, whole.end),
+            centre: None,
+        };
+
+        let response = ui.interact(
+            geometry.plot,
+            ui.id().with("overview"),
+            Sense::click_and_drag(),
+        );
+        let painter = ui.painter_at(geometry.plot);
+        painter.rect_filled(geometry.plot, 0., Color32::BLACK);
+        if let (Some(overview), Some(texture)) = (&self.overview, &self.overview_texture) {
+            overview.paint(&painter, &geometry, texture);
+        }
+
+        let viewport = Rect::from_min_max(
+            Pos2::new(geometry.x_of(self.view.low), geometry.y_of(self.view.start)),
+            Pos2::new(geometry.x_of(self.view.high), geometry.y_of(self.view.end)),
+        );
+        painter.rect_stroke(
+            viewport.expand(1.),
+            0.,
+            Stroke::new(1.5, Color32::WHITE),
+            egui::StrokeKind::Outside,
+        );
+
+        if response.drag_started() || response.clicked() {
+            self.remember_view(true);
+        }
+
+        if let Some(pos) = response
+            .interact_pointer_pos()
+            .filter(|_| response.dragged() || response.clicked())
+        {
+            let mut view = self.view;
+            view.centre_on(geometry.time_at(pos.y), geometry.frequency_at(pos.x));
+            self.set_view(view, samples);
+        }
+
+        response.on_hover_text("the whole file; click or drag to move the view");
+    }
+
+    /// Save the current view for "back", unless we're in the middle of a gesture.
+    pub(super) fn remember_view(&mut self, new_gesture: bool) {
+        let now = Instant::now();
+        let continuing = self
+            .gesture_at
+            .map(|at| now.duration_since(at) < GESTURE_GAP)
+            .unwrap_or(false);
+        self.gesture_at = Some(now);
+        if continuing && !new_gesture {
+            return;
+        }
+        if self.history.last() != Some(&self.view) {
+            self.history.push(self.view);
+        }
+        if self.history.len() > HISTORY_LIMIT {
+            self.history.remove(0);
+        }
+    }
+
+    pub(super) fn set_view(&mut self, mut view: View, samples: &Arc<dyn Samples>) {
+        view.clamp(samples.as_ref());
+        self.view = view;
+    }
+
+    /// Time, frequency and power under the cursor.
+    fn readout(&self, geometry: &Geometry, pos: Pos2) -> String {
+        let time = geometry.time_at(pos.y);
+        let frequency = geometry.frequency_at(pos.x);
+
+        // from the finest tile there is
+        let power = self
+            .tiles
+            .visible(&self.view)
+            .iter()
+            .rev()
+            .filter_map(|key| self.tiles.waterfall(key))
+            .find_map(|waterfall| {
+                let value = waterfall.value_at(time, frequency)?;
+                Some(self.scale.apply(value, waterfall.fft.full_scale()))
+            })
+            .map(|power| match self.scale {
+                Scale::Decibels => format!("{:>8.1}dB", power),
+                Scale::Linear => format!("{:>8.3}", power),
+            })
+            .unwrap_or_default();
+
+        let mut readout = format!(
+            "{:>14} {:>10}Hz {}",
+            format_seconds(time, geometry.time_resolution()),
+            format_si(geometry.display_frequency(frequency)),
+            power,
+        );
+        if let Some(delta) = self.marker_delta(time, frequency) {
+            readout.push_str("  ");
+            readout.push_str(&delta);
+        }
+        readout
+    }
+}
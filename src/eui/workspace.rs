@@ -0,0 +1,575 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use rustfft::num_complex::Complex;
+
+use super::keys::{self, Action};
+use super::open::{self, Choice, DetailsDialog, FileBrowser};
+use super::pipeline::Source;
+use super::state::Memory;
+use super::view::View;
+use super::{Layout, ManageApp};
+use crate::args::guess_details;
+use crate::samples::SampleFile;
+use crate::{FileDetails, Operation, Samples};
+
+/// One capture minus another, sample by sample, for as long as they both last.
+struct Difference {
+    a: Arc<dyn Samples>,
+    b: Arc<dyn Samples>,
+}
+
+impl Samples for Difference {
+    fn len(&self) -> u64 {
+        self.a.len().min(self.b.len())
+    }
+
+    fn sample_rate(&self) -> u64 {
+        self.a.sample_rate()
+    }
+
+    fn centre_frequency(&self) -> Option<u64> {
+        self.a
+            .centre_frequency()
+            .filter(|&centre| Some(centre) == self.b.centre_frequency())
+    }
+
+    fn read_at(&self, off: u64, buf: &mut [Complex<f32>]) -> usize {
+        let wanted = buf.len().min(self.len().saturating_sub(off) as usize);
+        let got = self.a.read_at(off, &mut buf[..wanted]);
+        let mut other = vec![Complex::default(); got];
+        let got = self.b.read_at(off, &mut other);
+        for (a, b) in buf.iter_mut().zip(&other[..got]) {
+            *a -= b;
+        }
+        got
+    }
+}
+
+/// Two different tabs, both mutably.
+fn pair_mut(tabs: &mut [ManageApp], a: usize, b: usize) -> (&mut ManageApp, &mut ManageApp) {
+    assert_ne!(a, b);
+    if a < b {
+        let (left, right) = tabs.split_at_mut(b);
+        (&mut left[a], &mut right[0])
+    } else {
+        let (left, right) = tabs.split_at_mut(a);
+        (&mut right[0], &mut left[b])
+    }
+}
+
+/// All the open tabs, and how to open more.
+pub struct Workspace {
+    tabs: Vec<ManageApp>,
+    active: usize,
+    /// Shown beside the active tab, in a split view.
+    beside: Option<usize>,
+
+    /// Moving around one tab moves all the others to the same times, or (relative) frequencies.
+    lock_time: bool,
+    lock_frequency: bool,
+
+    /// The list of key bindings.
+    show_keys: bool,
+
+    memory: Memory,
+    browser: Option<FileBrowser>,
+    details_dialog: Option<DetailsDialog>,
+    error: Option<String>,
+}
+
+impl Workspace {
+    pub fn new(
+        cc: &eframe::CreationContext<'_>,
+        samples: Option<Box<dyn Samples>>,
+        filename: Option<PathBuf>,
+    ) -> Self {
+        let mut us = Workspace {
+            tabs: Vec::new(),
+            active: 0,
+            beside: None,
+            lock_time: false,
+            lock_frequency: false,
+            show_keys: false,
+            memory: Memory::load(cc.storage),
+            browser: None,
+            details_dialog: None,
+            error: None,
+        };
+
+        if let Some(samples) = samples {
+            us.add_tab(
+                &cc.egui_ctx,
+                Source {
+                    samples: Arc::from(samples),
+                    name: "pipeline".to_string(),
+                    from: None,
+                },
+            );
+        }
+
+        if let Some(filename) = filename {
+            us.open(&cc.egui_ctx, &filename);
+        }
+
+        us
+    }
+
+    fn add_tab(&mut self, ctx: &egui::Context, source: Source) {
+        self.tabs.push(ManageApp::new(ctx, source, &self.memory));
+        self.active = self.tabs.len() - 1;
+    }
+
+    fn close_tab(&mut self, index: usize) {
+        self.tabs[index].remember_file(&mut self.memory.files);
+        self.tabs.remove(index);
+
+        let shift = |i: usize| if i > index { i - 1 } else { i };
+        self.beside = self.beside.filter(|&b| b != index).map(shift);
+        self.active = shift(self.active).min(self.tabs.len().saturating_sub(1));
+        if self.beside == Some(self.active) {
+            self.beside = None;
+        }
+    }
+
+    /// Open a file, asking for its details if they can't be guessed from the name.
+    fn open(&mut self, ctx: &egui::Context, path: &Path) {
+        match guess_details(&path.to_string_lossy(), None, None, None) {
+            Ok(details) => self.load(ctx, path, &details),
+            Err(e) => {
+                self.details_dialog = Some(DetailsDialog::new(path.to_path_buf(), e.to_string()))
+            }
+        }
+    }
+
+    /// Show the file in a new tab, or the tab it's already in.
+    fn load(&mut self, ctx: &egui::Context, path: &Path, details: &FileDetails) {
+        let name = path.to_string_lossy().to_string();
+        if let Some(existing) = self.tabs.iter().position(|tab| {
+            tab.source
+                .as_ref()
+                .map(|source| source.name == name)
+                .unwrap_or(false)
+        }) {
+            self.select(existing);
+            return;
+        }
+
+        let file = match fs::File::open(path) {
+            Ok(file) => file,
+            Err(e) => {
+                self.error = Some(format!("opening {:?}: {}", path, e));
+                return;
+            }
+        };
+
+        let file = SampleFile::new(
+            file,
+            details.format,
+            details.sample_rate,
+            details.centre_frequency,
+        );
+        self.error = None;
+        open::remember(&mut self.memory.recent, path, details);
+        self.add_tab(
+            ctx,
+            Source {
+                samples: Arc::new(file),
+                name: name.clone(),
+                from: Some(Operation::From {
+                    details: details.clone(),
+                    filename: name,
+                }),
+            },
+        );
+    }
+
+    /// Make a tab the active one; if it was beside the active one, they swap places.
+    fn select(&mut self, index: usize) {
+        if Some(index) == self.beside {
+            self.beside = Some(self.active);
+        }
+        self.active = index;
+    }
+
+    /// Do what was pressed: to the workspace, or to the active tab.
+    fn keyboard(&mut self, ctx: &egui::Context) {
+        for action in keys::pressed(ctx) {
+            match action {
+                Action::Help => self.show_keys = !self.show_keys,
+                Action::ClearSelection if self.show_keys => self.show_keys = false,
+                Action::NextTab | Action::PreviousTab if !self.tabs.is_empty() => {
+                    let count = self.tabs.len();
+                    let step = if Action::NextTab == action {
+                        1
+                    } else {
+                        count - 1
+                    };
+                    self.select((self.active + step) % count);
+                }
+                _ => {
+                    if let Some(tab) = self.tabs.get_mut(self.active) {
+                        if let Some(samples) = tab.samples.clone() {
+                            tab.act(action, &samples);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// The directory the file browser starts in.
+    fn browse_from(&self) -> PathBuf {
+        self.memory
+            .recent
+            .first()
+            .and_then(|recent| recent.path.parent())
+            .map(Path::to_path_buf)
+            .or_else(|| std::env::current_dir().ok())
+            .unwrap_or_else(|| PathBuf::from("/"))
+    }
+
+    fn dialogs(&mut self, ctx: &egui::Context) {
+        if let Some(browser) = self.browser.as_mut() {
+            match browser.show(ctx) {
+                Choice::Pending => (),
+                Choice::Cancelled => self.browser = None,
+                Choice::Chosen(path) => {
+                    self.browser = None;
+                    self.open(ctx, &path);
+                }
+            }
+        }
+
+        if let Some(dialog) = self.details_dialog.as_mut() {
+            match dialog.show(ctx) {
+                Choice::Pending => (),
+                Choice::Cancelled => self.details_dialog = None,
+                Choice::Chosen(details) => {
+                    let path = dialog.path().to_path_buf();
+                    self.details_dialog = None;
+                    self.load(ctx, &path, &details);
+                }
+            }
+        }
+
+        let dropped: Vec<PathBuf> = ctx.input(|i| {
+            i.raw
+                .dropped_files
+                .iter()
+                .filter_map(|file| file.path.clone())
+                .collect()
+        });
+        for path in dropped {
+            self.open(ctx, &path);
+        }
+    }
+
+    /// The tab beside the active one, if there is one, and it's a different one.
+    fn beside(&self) -> Option<usize> {
+        self.beside
+            .filter(|&beside| beside != self.active && beside < self.tabs.len())
+    }
+
+    /// The active tab minus the one beside it, if they can be subtracted.
+    fn difference(&self) -> Result<Source, String> {
+        let beside = self.beside().ok_or("show another tab beside this one")?;
+        let (a, b) = (&self.tabs[self.active], &self.tabs[beside]);
+        let (a_samples, b_samples) = match (&a.samples, &b.samples) {
+            (Some(a), Some(b)) => (a, b),
+            _ => return Err("nothing to subtract".to_string()),
+        };
+        if a_samples.sample_rate() != b_samples.sample_rate() {
+            return Err(format!(
+                "the sample rates differ: {} and {}",
+                a_samples.sample_rate(),
+                b_samples.sample_rate()
+            ));
+        }
+        Ok(Source {
+            samples: Arc::new(Difference {
+                a: Arc::clone(a_samples),
+                b: Arc::clone(b_samples),
+            }),
+            name: format!("{} − {}", a.title(), b.title()),
+            from: None,
+        })
+    }
+
+    fn tabs_ui(&mut self, ui: &mut egui::Ui) {
+        let mut select = None;
+        let mut close = None;
+        let mut subtract = None;
+        ui.horizontal_wrapped(|ui| {
+            for (i, tab) in self.tabs.iter().enumerate() {
+                let name = tab
+                    .source
+                    .as_ref()
+                    .map(|source| source.name.clone())
+                    .unwrap_or_default();
+                if ui
+                    .selectable_label(i == self.active, tab.title())
+                    .on_hover_text(name)
+                    .clicked()
+                {
+                    select = Some(i);
+                }
+                if ui.small_button("✖").on_hover_text("close").clicked() {
+                    close = Some(i);
+                }
+                ui.separator();
+            }
+
+            let beside_title = self
+                .beside()
+                .map(|beside| self.tabs[beside].title())
+                .unwrap_or_else(|| "nothing".to_string());
+            egui::ComboBox::from_label("beside")
+                .selected_text(beside_title)
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut self.beside, None, "nothing");
+                    for (i, tab) in self.tabs.iter().enumerate() {
+                        if i != self.active {
+                            ui.selectable_value(&mut self.beside, Some(i), tab.title());
+                        }
+                    }
+                });
+
+            ui.add_enabled_ui(self.tabs.len() > 1, |ui| {
+                ui.checkbox(&mut self.lock_time, "lock time");
+                ui.checkbox(&mut self.lock_frequency, "lock frequency")
+                    .on_hover_text("relative to each centre");
+            });
+
+            let difference = self.difference();
+            let hover = match &difference {
+                Ok(source) => format!("a new tab of {}, sample by sample", source.name),
+                Err(e) => e.clone(),
+            };
+            if ui
+                .add_enabled(difference.is_ok(), egui::Button::new("difference"))
+                .on_hover_text(&hover)
+                .on_disabled_hover_text(&hover)
+                .clicked()
+            {
+                subtract = difference.ok();
+            }
+        });
+
+        if let Some(i) = select {
+            self.select(i);
+        }
+        if let Some(i) = close {
+            self.close_tab(i);
+        }
+        if let Some(source) = subtract {
+            self.add_tab(ui.ctx(), source);
+        }
+    }
+
+    /// Move the other tabs to follow the one which moved (or was selected in), as far as they're locked.
+    fn follow(&mut self, before: &[(View, Option<View>)]) {
+        let moved = |tab: &ManageApp, (view, selection): &(View, Option<View>)| {
+            tab.view != *view || tab.selection != *selection
+        };
+        let changed = match self
+            .tabs
+            .iter()
+            .zip(before)
+            .position(|(tab, before)| moved(tab, before))
+        {
+            Some(changed) => changed,
+            None => return,
+        };
+
+        // whatever's being worked on gets the side panels and windows
+        if Some(changed) == self.beside() {
+            self.select(changed);
+        }
+
+        let target = self.tabs[changed].view;
+        for (i, tab) in self.tabs.iter_mut().enumerate() {
+            if i == changed {
+                continue;
+            }
+            let mut view = tab.view;
+            if self.lock_time {
+                view.start = target.start;
+                view.end = target.end;
+            }
+            if self.lock_frequency {
+                view.low = target.low;
+                view.high = target.high;
+            }
+            if let Some(samples) = tab.samples.clone().filter(|_| view != tab.view) {
+                tab.set_view(view, &samples);
+            }
+        }
+    }
+}
+
+impl eframe::App for Workspace {
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        for tab in &self.tabs {
+            tab.remember_file(&mut self.memory.files);
+        }
+        self.memory.save(storage);
+    }
+
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
+            egui::menu::bar(ui, |ui| {
+                ui.menu_button("File", |ui| {
+                    if ui.button("Open…").clicked() {
+                        self.browser = Some(FileBrowser::new(self.browse_from()));
+                        ui.close_menu();
+                    }
+                    ui.add_enabled_ui(!self.memory.recent.is_empty(), |ui| {
+                        ui.menu_button("Open recent", |ui| {
+                            let mut chosen = None;
+                            for recent in &self.memory.recent {
+                                if ui.button(recent.path.to_string_lossy()).clicked() {
+                                    chosen = Some(recent.clone());
+                                }
+                            }
+                            if let Some(recent) = chosen {
+                                self.load(ctx, &recent.path, &recent.details);
+                                ui.close_menu();
+                            }
+                        });
+                    });
+                    let active = self.tabs.get_mut(self.active);
+                    let open = active.is_some();
+                    if ui
+                        .add_enabled(open, egui::Button::new("Save image…"))
+                        .clicked()
+                    {
+                        if let Some(tab) = active {
+                            tab.snapshot.show = true;
+                        }
+                        ui.close_menu();
+                    }
+                    if ui.add_enabled(open, egui::Button::new("Close")).clicked() {
+                        self.close_tab(self.active);
+                        ui.close_menu();
+                    }
+                    ui.separator();
+                    if ui.button("Quit").clicked() {
+                        ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+                    }
+                });
+                if let Some(tab) = self.tabs.get_mut(self.active) {
+                    ui.menu_button("View", |ui| {
+                        ui.radio_value(&mut tab.layout, Layout::Waterfall, "Waterfall");
+                        ui.radio_value(&mut tab.layout, Layout::Wrapped, "Wrapped rows")
+                            .on_hover_text("an fft at every sample, for symbol timing");
+                        ui.separator();
+                        ui.checkbox(&mut tab.show_annotations, "Annotations");
+                        ui.checkbox(&mut tab.markers.show, "Markers");
+                        ui.checkbox(&mut tab.show_spectrum, "Spectrum");
+                        ui.separator();
+                        tab.defaults_ui(ui, &mut self.memory.defaults);
+                    });
+                }
+                ui.menu_button("Help", |ui| {
+                    if ui
+                        .add(egui::Button::new("Keyboard").shortcut_text("?"))
+                        .clicked()
+                    {
+                        self.show_keys = true;
+                        ui.close_menu();
+                    }
+                });
+                ui.add_space(16.0);
+
+                egui::widgets::global_theme_preference_buttons(ui);
+            });
+        });
+
+        self.dialogs(ctx);
+
+        if !self.tabs.is_empty() {
+            egui::TopBottomPanel::top("tabs").show(ctx, |ui| self.tabs_ui(ui));
+        }
+
+        let before: Vec<(View, Option<View>)> = self
+            .tabs
+            .iter()
+            .map(|tab| (tab.view, tab.selection))
+            .collect();
+
+        self.keyboard(ctx);
+
+        if let Some(tab) = self.tabs.get_mut(self.active) {
+            tab.side_panels(ctx);
+        }
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            if let Some(error) = &self.error {
+                ui.colored_label(ui.visuals().error_fg_color, error);
+            }
+
+            if self.tabs.is_empty() {
+                ui.centered_and_justified(|ui| {
+                    ui.label("Open a capture with File → Open…, or drop one here.");
+                });
+                return;
+            }
+
+            match self.beside() {
+                Some(beside) => {
+                    let (active, beside) = pair_mut(&mut self.tabs, self.active, beside);
+                    ui.columns(2, |columns| {
+                        columns[0].strong(active.title());
+                        active.central_ui(&mut columns[0]);
+                        columns[1].strong(beside.title());
+                        beside.central_ui(&mut columns[1]);
+                    });
+                }
+                None => self.tabs[self.active].central_ui(ui),
+            }
+
+            ui.separator();
+
+            ui.with_layout(egui::Layout::bottom_up(egui::Align::LEFT), |ui| {
+                egui::warn_if_debug_build(ui);
+            });
+        });
+
+        if before.len() == self.tabs.len() {
+            self.follow(&before);
+        }
+
+        if let Some(tab) = self.tabs.get_mut(self.active) {
+            tab.windows(ctx);
+        }
+        keys::help_ui(ctx, &mut self.show_keys);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use rustfft::num_complex::Complex;
+
+    use super::Difference;
+    use crate::gen::Gen;
+    use crate::Samples;
+
+    #[test]
+    fn difference() {
+        let tone: Arc<dyn Samples> = Arc::new(Gen::new(vec![1_000], 8_000, 1.).unwrap());
+        let shorter: Arc<dyn Samples> = Arc::new(Gen::new(vec![1_000], 8_000, 0.5).unwrap());
+        let difference = Difference {
+            a: Arc::clone(&tone),
+            b: shorter,
+        };
+        assert_eq!(4_000, difference.len());
+
+        let mut buf = vec![Complex::new(1., 1.); 100];
+        assert_eq!(50, difference.read_at(3_950, &mut buf));
+        assert!(buf[..50].iter().all(|s| s.norm() < 1e-6), "{:?}", buf);
+    }
+}
@@ -0,0 +1,379 @@
+//! An fft at every sample, in columns wrapped into rows across the screen, for eyeballing
+//! symbol timing; the layout of the old conrod ui.
+
+use std::ops::RangeInclusive;
+use std::sync::Arc;
+use std::time::Duration;
+
+use egui::{Color32, ColorImage, Pos2, Rect, Sense, TextureHandle};
+use poll_promise::Promise;
+
+use super::axes;
+use super::tiles::Colouring;
+use super::ManageApp;
+use crate::axis::{format_seconds, format_si};
+use crate::ffts::{take_fft_consecutive, FftConfig, FftResult, Scale, Windowing};
+use crate::Samples;
+
+/// Between the rows, in pixels.
+const GAP: usize = 16;
+
+/// The fft widths on offer, as powers of two; these are for seeing symbols, not detail.
+pub(super) const FFT_POWERS: RangeInclusive<u32> = 1..=10;
+
+/// How the columns are laid out.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct WrappedSettings {
+    /// Always a power of two.
+    pub fft_width: usize,
+    /// Pixels per bin, vertically.
+    pub stretch: usize,
+    /// A marker every this many samples; one for none.
+    pub stride: u64,
+}
+
+impl Default for WrappedSettings {
+    fn default() -> Self {
+        WrappedSettings {
+            fft_width: 8,
+            stretch: 4,
+            stride: 1,
+        }
+    }
+}
+
+impl WrappedSettings {
+    fn row_height(&self) -> usize {
+        self.stretch * self.fft_width + GAP
+    }
+
+    /// How many rows fit in this many pixels; at least one, even if it's cut off.
+    fn rows_in(&self, height: usize) -> usize {
+        ((height + GAP) / self.row_height()).max(1)
+    }
+}
+
+/// What a render is of: the first sample, and the shape on screen.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+struct Key {
+    start: u64,
+    settings: WrappedSettings,
+    /// Pixels across.
+    columns: usize,
+    /// Of ffts, which may not fill the last row.
+    count: usize,
+}
+
+#[derive(Default)]
+pub struct Wrapped {
+    pub settings: WrappedSettings,
+    rendered: Option<(Key, Result<Arc<FftResult>, String>)>,
+    rendering: Option<(Key, Promise<Result<FftResult, String>>)>,
+    texture: Option<(Key, Colouring, TextureHandle)>,
+}
+
+/// The ffts, one per sample from `first`, left to right and wrapping every `columns`,
+/// with the positive frequencies at the top of each row.
+fn layout(
+    fft: &FftResult,
+    first: u64,
+    settings: &WrappedSettings,
+    columns: usize,
+    colouring: Colouring,
+) -> ColorImage {
+    let width = fft.width();
+    let rows = fft.output_len().div_ceil(columns);
+    let row_height = settings.row_height();
+    let mut image = ColorImage::new([columns, rows * row_height - GAP], Color32::BLACK);
+    let (min, max) = colouring.range;
+    for i in 0..fft.output_len() {
+        if settings.stride > 1 && (first + i as u64).is_multiple_of(settings.stride) {
+            continue;
+        }
+        let (row, x) = (i / columns, i % columns);
+        for (bin, &norm) in fft.get(i).iter().enumerate() {
+            let value = colouring.scale.apply(norm, fft.full_scale());
+            let [r, g, b] = colouring.colormap.rgb_in(value, min, max);
+            let top = row * row_height + (width - 1 - bin) * settings.stretch;
+            for y in top..top + settings.stretch {
+                image[(x, y)] = Color32::from_rgb(r, g, b);
+            }
+        }
+    }
+    image
+}
+
+/// The sample and the bin at a pixel of the layout, if there's one there.
+fn at_pixel(key: &Key, (x, y): (usize, usize)) -> Option<(usize, usize)> {
+    let settings = &key.settings;
+    let (row, within) = (y / settings.row_height(), y % settings.row_height());
+    let bin = settings
+        .fft_width
+        .checked_sub(within / settings.stretch + 1)?;
+    let i = row * key.columns + x;
+    Some((i, bin)).filter(|_| x < key.columns && i < key.count)
+}
+
+impl ManageApp {
+    /// The fft width, stretch and stride.
+    pub(super) fn wrapped_settings_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            let settings = &mut self.wrapped.settings;
+            egui::ComboBox::from_label("fft")
+                .selected_text(settings.fft_width.to_string())
+                .show_ui(ui, |ui| {
+                    for power in FFT_POWERS {
+                        let width = 1 << power;
+                        ui.selectable_value(&mut settings.fft_width, width, width.to_string());
+                    }
+                });
+            ui.add(
+                egui::DragValue::new(&mut settings.stretch)
+                    .range(1..=64)
+                    .prefix("stretch "),
+            )
+            .on_hover_text("pixels per bin");
+            ui.add(
+                egui::DragValue::new(&mut settings.stride)
+                    .range(1..=1 << 20)
+                    .prefix("stride "),
+            )
+            .on_hover_text("a marker every this many samples; one for none");
+        });
+    }
+
+    /// Set the floor and ceiling from the wrapped rows, rather than the waterfall.
+    pub(super) fn wrapped_auto_level(&mut self) {
+        if let Some((_, Ok(fft))) = &self.wrapped.rendered {
+            (self.floor, self.ceiling) = fft.auto_range(self.scale);
+        }
+    }
+
+    /// The samples from the start of the view, as many as fit; scroll or drag to move a row.
+    pub(super) fn wrapped_ui(&mut self, ui: &mut egui::Ui, samples: &Arc<dyn Samples>) {
+        if self
+            .wrapped
+            .rendering
+            .as_ref()
+            .is_some_and(|(_, promise)| promise.ready().is_some())
+        {
+            if let Some((key, promise)) = self.wrapped.rendering.take() {
+                self.wrapped.rendered = Some((key, promise.block_and_take().map(Arc::new)));
+                if self.auto_levels {
+                    self.wrapped_auto_level();
+                }
+            }
+        } else if self.wrapped.rendering.is_some() {
+            ui.ctx().request_repaint_after(Duration::from_millis(50));
+        }
+
+        let size = egui::vec2(ui.available_width(), ui.available_height() - 30.);
+        let (rect, response) = ui.allocate_exact_size(size, Sense::click_and_drag());
+        let pixels_per_point = ui.ctx().pixels_per_point();
+
+        let settings = self.wrapped.settings;
+        let sample_rate = samples.sample_rate() as f64;
+        let columns = ((rect.width() * pixels_per_point) as usize).max(1);
+        let rows = settings.rows_in((rect.height() * pixels_per_point) as usize);
+        let last = samples.len().saturating_sub(settings.fft_width as u64 + 1);
+        let start = ((self.view.start * sample_rate) as u64).min(last.saturating_sub(1));
+        let count = ((columns * rows) as u64).min(last.saturating_sub(start)) as usize;
+        if 0 == count {
+            ui.colored_label(ui.visuals().error_fg_color, "too short for this fft");
+            return;
+        }
+
+        // the view is what's on screen, so the other tabs and the waterfall can follow it
+        let shown = count as f64 / sample_rate;
+        if (self.view.end - self.view.start - shown).abs() > 0.5 / sample_rate {
+            let mut view = self.view;
+            view.end = view.start + shown;
+            self.set_view(view, samples);
+        }
+
+        let key = Key {
+            start,
+            settings,
+            columns,
+            count,
+        };
+        let wanted = |k: Option<&Key>| k == Some(&key);
+        if !wanted(self.wrapped.rendered.as_ref().map(|(k, _)| k))
+            && !wanted(self.wrapped.rendering.as_ref().map(|(k, _)| k))
+        {
+            let samples = Arc::clone(samples);
+            let config = FftConfig {
+                width: settings.fft_width,
+                windowing: Windowing::Rectangular,
+            };
+            self.wrapped.rendering = Some((
+                key,
+                Promise::spawn_thread("wrapped", move || {
+                    take_fft_consecutive(samples.as_ref(), start, count, &config)
+                        .map_err(|e| format!("{:#}", e))
+                }),
+            ));
+        }
+
+        let colouring = Colouring {
+            colormap: self.colormap,
+            scale: self.scale,
+            range: (self.floor, self.ceiling),
+        };
+        if let Some((key, Ok(fft))) = &self.wrapped.rendered {
+            let current = self
+                .wrapped
+                .texture
+                .as_ref()
+                .is_some_and(|(k, c, _)| k == key && *c == colouring);
+            if !current {
+                let image = layout(fft, key.start, &key.settings, key.columns, colouring);
+                let texture =
+                    ui.ctx()
+                        .load_texture("wrapped", image, egui::TextureOptions::NEAREST);
+                self.wrapped.texture = Some((*key, colouring, texture));
+            }
+        }
+
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(rect, 0., Color32::BLACK);
+        if let Some((_, _, texture)) = &self.wrapped.texture {
+            painter.image(
+                texture.id(),
+                Rect::from_min_size(rect.min, texture.size_vec2() / pixels_per_point),
+                Rect::from_min_max(Pos2::new(0., 0.), Pos2::new(1., 1.)),
+                Color32::WHITE,
+            );
+        }
+        if let Some((_, Err(e))) = &self.wrapped.rendered {
+            painter.text(
+                rect.left_top(),
+                egui::Align2::LEFT_TOP,
+                e,
+                egui::FontId::proportional(14.),
+                ui.visuals().error_fg_color,
+            );
+        }
+
+        // move by whole rows' worth of samples
+        let row_seconds = columns as f64 / sample_rate;
+        let row_points = settings.row_height() as f32 / pixels_per_point;
+        let mut moved = 0.;
+        if response.hovered() {
+            moved -= ui.input(|i| i.smooth_scroll_delta.y) / row_points;
+        }
+        if response.drag_started() {
+            self.remember_view(true);
+        }
+        if response.dragged() {
+            moved -= response.drag_delta().y / row_points;
+        }
+        if moved != 0. {
+            self.remember_view(false);
+            let mut view = self.view;
+            view.pan(f64::from(moved) * row_seconds, 0.);
+            self.set_view(view, samples);
+        }
+
+        // of what's drawn, which may be a little behind
+        let drawn = self.wrapped.texture.as_ref().map(|(key, _, _)| *key);
+        let hovered = response.hover_pos().zip(drawn).and_then(|(pos, key)| {
+            let pixel = (pos - rect.min) * pixels_per_point;
+            let (i, bin) = at_pixel(&key, (pixel.x as usize, pixel.y as usize))?;
+            let width = key.settings.fft_width as f64;
+            let time = (key.start + i as u64) as f64 / sample_rate;
+            let frequency = (bin as f64 - width / 2.) * sample_rate / width;
+            Some((key, i, bin, time, frequency))
+        });
+
+        let readout = match hovered {
+            Some((key, i, bin, time, frequency)) => {
+                if response.clicked() {
+                    ui.ctx()
+                        .copy_text(axes::slice_argument("start", time, 1. / sample_rate));
+                }
+                let centre = samples
+                    .centre_frequency()
+                    .filter(|_| self.absolute)
+                    .unwrap_or(0) as f64;
+                let power = match &self.wrapped.rendered {
+                    Some((rendered, Ok(fft))) if *rendered == key => {
+                        let power = self.scale.apply(fft.get(i)[bin], fft.full_scale());
+                        match self.scale {
+                            Scale::Decibels => format!("{:>8.1}dB", power),
+                            Scale::Linear => format!("{:>8.3}", power),
+                        }
+                    }
+                    _ => String::new(),
+                };
+                format!(
+                    "{:>14} sample {:>10} {:>10}Hz {}",
+                    format_seconds(time, 1. / sample_rate),
+                    key.start + i as u64,
+                    format_si(frequency + centre),
+                    power,
+                )
+            }
+            None => "scroll or drag: move a row at a time, click: copy the start of a sample"
+                .to_string(),
+        };
+
+        ui.horizontal(|ui| {
+            if self.wrapped.rendering.is_some() {
+                ui.spinner();
+            }
+            ui.monospace(readout);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{at_pixel, layout, Key, WrappedSettings};
+    use crate::colormap::Colormap;
+    use crate::eui::tiles::Colouring;
+    use crate::ffts::{take_fft_consecutive, FftConfig, Scale, Windowing};
+    use crate::gen::Gen;
+
+    #[test]
+    fn wrapping() {
+        let tone = Gen::new(vec![1_000], 8_000, 1.).unwrap();
+        let settings = WrappedSettings {
+            fft_width: 8,
+            stretch: 2,
+            stride: 5,
+        };
+        let config = FftConfig {
+            width: 8,
+            windowing: Windowing::Rectangular,
+        };
+        let fft = take_fft_consecutive(&tone, 0, 25, &config).unwrap();
+        let colouring = Colouring {
+            colormap: Colormap::Grayscale,
+            scale: Scale::Decibels,
+            range: (-100., 0.),
+        };
+        let image = layout(&fft, 0, &settings, 10, colouring);
+        // three rows, the last partly filled, with gaps between
+        assert_eq!([10, 3 * (8 * 2 + 16) - 16], image.size);
+
+        // 1kHz of 8kHz is one bin above the middle, which is the third from the top
+        let key = Key {
+            start: 0,
+            settings,
+            columns: 10,
+            count: 25,
+        };
+        let tone_y = 2 * 2;
+        assert_eq!(Some((11, 5)), at_pixel(&key, (1, 32 + tone_y)));
+        let bright = image[(1, 32 + tone_y)];
+        let dark = image[(1, 32 + tone_y + 2)];
+        assert!(bright.r() > dark.r(), "{:?} {:?}", bright, dark);
+
+        // every fifth sample is a marker
+        assert_eq!(egui::Color32::BLACK, image[(5, 32 + tone_y)]);
+        // past the end, and in the gap
+        assert_eq!(None, at_pixel(&key, (5, 64 + tone_y)));
+        assert_eq!(None, at_pixel(&key, (1, 8 * 2 + 1)));
+    }
+}
@@ -1,73 +1,147 @@
+use anyhow::anyhow;
+use anyhow::ensure;
 use anyhow::Error;
 use num_traits::identities::Zero;
 use rustfft::algorithm::Radix4;
 use rustfft::num_complex::Complex;
 use rustfft::{Fft, FftDirection};
 
+use crate::axis::{format_seconds, text_header, FrequencyReference, TimeUnit};
+use crate::colormap::Colormap;
+use crate::ffts::{take_fft, FftConfig, FftReader, Scale, Windowing};
 use crate::samples::Samples;
 
 use crate::u64_from;
 use crate::usize_from;
 
-pub fn spark_fft(
-    samples: &mut dyn Samples,
-    fft_width: usize,
-    stride: u64,
-    min: Option<f32>,
-    max: Option<f32>,
-) -> Result<(), Error> {
+/// How many rows to sample when guessing the range; enough to find short bursts.
+const AUTO_RANGE_ROWS: u64 = 4096;
+
+#[derive(Debug, Clone)]
+pub struct SparkFft {
+    pub width: usize,
+    pub stride: u64,
+    pub window: Windowing,
+    pub scale: Scale,
+    pub min: Option<f32>,
+    pub max: Option<f32>,
+
+    /// Print a header line with labelled frequencies.
+    pub axis: Option<FrequencyReference>,
+
+    /// Prefix each row with its offset into the input.
+    pub time: Option<TimeUnit>,
+
+    /// Use 24-bit ANSI colour, instead of block characters.
+    pub colormap: Option<Colormap>,
+}
+
+pub fn spark_fft(samples: &mut dyn Samples, spark: &SparkFft) -> Result<(), Error> {
     println!("sparkfft sample_rate={}", samples.sample_rate());
 
-    // TODO: super dumb:
-    let min = min.unwrap_or(0.08);
-    let max = max.unwrap_or(1.);
+    let fft_width = spark.width;
+    let stride = spark.stride;
+    let scale = spark.scale;
 
-    let fft = Radix4::new(fft_width, FftDirection::Forward);
+    let config = FftConfig {
+        width: fft_width,
+        windowing: spark.window,
+    };
 
-    let mut i = 0;
-    while i < (samples.len() - fft_width as u64) {
-        let mut inp = vec![Complex::zero(); fft_width];
-        samples.read_exact_at(i, &mut inp)?;
+    let available = samples.len().saturating_sub(u64_from(fft_width));
+    let rows = available / stride;
+    if 0 == rows {
+        return Ok(());
+    }
 
-        fft.process(&mut inp);
+    let (min, max) = match (spark.min, spark.max) {
+        (Some(min), Some(max)) => (min, max),
+        _ => {
+            let (floor, peak) = auto_range(&*samples, &config, scale, rows)?;
+            (spark.min.unwrap_or(floor), spark.max.unwrap_or(peak))
+        }
+    };
+
+    let sample_rate = samples.sample_rate() as f64;
+    let time_label = |offset: u64| match spark.time {
+        Some(TimeUnit::Seconds) => format!(
+            "{:>14} ",
+            format_seconds(offset as f64 / sample_rate, stride as f64 / sample_rate)
+        ),
+        Some(TimeUnit::Samples) => format!("{:>14} ", offset),
+        None => String::new(),
+    };
+
+    if let Some(reference) = spark.axis {
+        let offset = match reference {
+            FrequencyReference::Relative => 0.,
+            FrequencyReference::Absolute => samples.centre_frequency().ok_or_else(|| {
+                anyhow!("absolute frequencies require a centre frequency, e.g. from -centre")
+            })? as f64,
+        };
+        let bin_width = sample_rate / fft_width as f64;
+        let first = offset - (fft_width / 2) as f64 * bin_width;
+        println!(
+            "{:width$} {}",
+            "",
+            text_header(fft_width, first, first + (fft_width - 1) as f64 * bin_width),
+            width = time_label(0).chars().count()
+        );
+    }
 
-        let top = '█';
-        let bot = ' ';
-        let graph: Vec<char> = "▁▂▃▄▅▆▇".chars().collect();
+    let top = '█';
+    let bot = ' ';
+    let graph: Vec<char> = "▁▂▃▄▅▆▇".chars().collect();
+    let distinction = (max - min) / (graph.len() as f32);
 
-        #[cfg(feature = "never")]
-        let max = out
-            .iter()
-            .map(|x| x.norm())
-            .max_by(|x, y| x.partial_cmp(y).unwrap())
-            .unwrap();
+    let mut reader = FftReader::new(&config);
+    let full_scale = config.windowing.gain(fft_width);
 
-        let distinction = (max - min) / (graph.len() as f32);
+    for row in 0..rows {
+        let offset = row * stride;
         let mut buf = String::with_capacity(fft_width);
 
-        for val in inp
-            .iter()
-            .skip(fft_width / 2)
-            .chain(inp.iter().take(fft_width / 2))
-        {
-            let norm = val.norm();
-            if norm < min {
+        for norm in reader.magnitudes_at(&*samples, offset)? {
+            let val = scale.apply(norm, full_scale);
+            if let Some(colormap) = spark.colormap {
+                let [r, g, b] = colormap.rgb_in(val, min, max);
+                buf.push_str(&format!("\x1b[48;2;{};{};{}m ", r, g, b));
+            } else if val < min {
                 buf.push(bot);
-            } else if norm >= max {
+            } else if val >= max {
                 buf.push(top);
             } else {
-                buf.push(graph[((norm - min) / distinction) as usize]);
+                buf.push(graph[((val - min) / distinction) as usize]);
             }
         }
 
-        println!("│{}│", buf);
+        if spark.colormap.is_some() {
+            buf.push_str("\x1b[0m");
+        }
 
-        i += stride;
+        println!("{}│{}│", time_label(offset), buf);
     }
 
     Ok(())
 }
 
+/// Estimate the noise floor and peak of the whole input, from a subset of the rows.
+fn auto_range(
+    samples: &dyn Samples,
+    config: &FftConfig,
+    scale: Scale,
+    rows: u64,
+) -> Result<(f32, f32), Error> {
+    let output_len = rows
+        .min(AUTO_RANGE_ROWS)
+        .min(samples.len() - u64_from(config.width) - 1);
+    ensure!(
+        output_len > 0,
+        "input is too short to guess a range, please specify -range"
+    );
+    Ok(take_fft(samples, None, config, usize_from(output_len))?.auto_range(scale))
+}
+
 #[derive(Debug, Clone)]
 pub struct Levels {
     pub vals: Vec<usize>,
@@ -2,17 +2,173 @@ use crate::{u64_from, Samples};
 use anyhow::{ensure, Result};
 use num_traits::Zero;
 use rustfft::num_complex::Complex;
+use rustfft::Fft;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
 
-#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+/// Percentile of all the values which is considered to be the noise floor, when guessing a range.
+const AUTO_FLOOR_PERCENTILE: f32 = 50.;
+
+/// Percentile of all the values which is considered to be the peak, when guessing a range.
+const AUTO_PEAK_PERCENTILE: f32 = 99.9;
+
+/// Combining more FFTs than this into one row is slow, and doesn't change the picture.
+const MAX_FFTS_PER_ROW: u64 = 256;
+
+#[derive(Debug, PartialEq, Copy, Clone)]
 pub struct FftConfig {
     pub width: usize,
     pub windowing: Windowing,
 }
 
-#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
 pub enum Windowing {
     Rectangular,
     BlackmanHarris,
+    Hann,
+    Hamming,
+    /// Larger `beta`s trade a wider main lobe for lower side lobes.
+    Kaiser {
+        beta: f32,
+    },
+    /// Very wide, but accurate for the amplitude of tones between bins.
+    FlatTop,
+}
+
+impl Windowing {
+    /// Every window, with the Kaiser's `beta` at a reasonable default.
+    pub const ALL: [Windowing; 6] = [
+        Windowing::BlackmanHarris,
+        Windowing::Hann,
+        Windowing::Hamming,
+        Windowing::Kaiser { beta: 8.6 },
+        Windowing::FlatTop,
+        Windowing::Rectangular,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Windowing::Rectangular => "rectangular",
+            Windowing::BlackmanHarris => "blackman-harris",
+            Windowing::Hann => "hann",
+            Windowing::Hamming => "hamming",
+            Windowing::Kaiser { .. } => "kaiser",
+            Windowing::FlatTop => "flat-top",
+        }
+    }
+
+    /// The coefficients to multiply the input by, or `None` if it would be a no-op.
+    pub fn coefficients(&self, n: usize) -> Option<Vec<f32>> {
+        match *self {
+            Windowing::Rectangular => None,
+            Windowing::BlackmanHarris => {
+                Some(cosine_sum_window(n, &[0.35875, 0.48829, 0.14128, 0.01168]))
+            }
+            Windowing::Hann => Some(cosine_sum_window(n, &[0.5, 0.5])),
+            Windowing::Hamming => Some(cosine_sum_window(n, &[0.54, 0.46])),
+            Windowing::Kaiser { beta } => Some(kaiser_window(n, beta)),
+            Windowing::FlatTop => Some(cosine_sum_window(
+                n,
+                &[0.21557895, 0.41663158, 0.27726316, 0.083578947, 0.006947368],
+            )),
+        }
+    }
+
+    /// How many bins wide a bin is, for noise: `n Σw² / (Σw)²`.
+    pub fn noise_bandwidth(&self, n: usize) -> f32 {
+        match self.coefficients(n) {
+            Some(coefficients) => {
+                let sum: f32 = coefficients.iter().sum();
+                let squares: f32 = coefficients.iter().map(|c| c * c).sum();
+                n as f32 * squares / (sum * sum)
+            }
+            None => 1.,
+        }
+    }
+
+    /// The sum of the coefficients, i.e. the magnitude of a full-scale tone after the FFT.
+    pub fn gain(&self, n: usize) -> f32 {
+        match self.coefficients(n) {
+            Some(coefficients) => coefficients.iter().sum(),
+            None => n as f32,
+        }
+    }
+}
+
+/// How to present the magnitude of an FFT bin.
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Serialize, Deserialize)]
+pub enum Scale {
+    /// The raw magnitude, as returned by the FFT.
+    Linear,
+
+    /// Decibels relative to a full-scale tone, i.e. `20 log10(norm / full_scale)`.
+    Decibels,
+}
+
+impl Scale {
+    /// `full_scale` is the magnitude of a full-scale tone, from `Windowing::gain`.
+    pub fn apply(&self, norm: f32, full_scale: f32) -> f32 {
+        match *self {
+            Scale::Linear => norm,
+            Scale::Decibels => 20. * (norm / full_scale).max(1e-10).log10(),
+        }
+    }
+}
+
+/// Reusable state for repeatedly taking an FFT of a `Samples` at different offsets.
+pub struct FftReader {
+    fft: Arc<dyn Fft<f32>>,
+    window: Option<Vec<f32>>,
+    buf: Vec<Complex<f32>>,
+    scratch: Vec<Complex<f32>>,
+}
+
+impl FftReader {
+    pub fn new(config: &FftConfig) -> FftReader {
+        let fft = rustfft::FftPlanner::<f32>::new().plan_fft_forward(config.width);
+        let scratch = vec![Complex::zero(); fft.get_inplace_scratch_len()];
+        FftReader {
+            window: config.windowing.coefficients(config.width),
+            buf: vec![Complex::zero(); config.width],
+            scratch,
+            fft,
+        }
+    }
+
+    /// The FFT of the `width` samples at `offset`, with zero frequency in the middle.
+    pub fn magnitudes_at<'s>(
+        &'s mut self,
+        samples: &dyn Samples,
+        offset: u64,
+    ) -> Result<impl Iterator<Item = f32> + 's> {
+        samples.read_exact_at(offset, &mut self.buf)?;
+
+        if let Some(ref w) = self.window {
+            for (sample, &w_val) in self.buf.iter_mut().zip(w.iter()) {
+                *sample *= w_val;
+            }
+        }
+
+        self.fft
+            .process_with_scratch(&mut self.buf, &mut self.scratch);
+
+        let half = self.buf.len() / 2;
+        Ok(self.buf[half..]
+            .iter()
+            .chain(self.buf[..half].iter())
+            .map(|val| val.norm()))
+    }
+}
+
+/// How to turn the FFTs within the span of an output row into that row.
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
+pub enum Combine {
+    /// Just the FFT at the start of the row; everything else is ignored.
+    First,
+    /// The mean power of the FFTs across the row.
+    Mean,
+    /// The loudest each bin was, anywhere in the row.
+    Max,
 }
 
 pub fn take_fft(
@@ -20,9 +176,26 @@ pub fn take_fft(
     slice: Option<(u64, u64)>,
     config: &FftConfig,
     output_len: usize,
+) -> Result<FftResult> {
+    take_fft_combined(samples, slice, config, output_len, Combine::First, 0.)
+}
+
+/// As `take_fft`, but each row is made of every FFT in its span, which overlap by `overlap`.
+pub fn take_fft_combined(
+    samples: &dyn Samples,
+    slice: Option<(u64, u64)>,
+    config: &FftConfig,
+    output_len: usize,
+    combine: Combine,
+    overlap: f64,
 ) -> Result<FftResult> {
     let fft_width = config.width;
-    let fft = rustfft::FftPlanner::<f32>::new().plan_fft_forward(fft_width);
+
+    ensure!(
+        (0. ..1.).contains(&overlap),
+        "overlap must be at least zero, and less than one: {}",
+        overlap
+    );
 
     let (start_sample, end_sample) = match slice {
         Some((start, end)) => (start, end),
@@ -48,44 +221,80 @@ pub fn take_fft(
     );
 
     let step = visible_samples as f64 / output_len as f64;
-    let mut complex_buf = vec![Complex::zero(); fft_width];
-    let mut scratch = vec![Complex::zero(); fft.get_inplace_scratch_len()];
+    let mut reader = FftReader::new(config);
 
-    let window = match config.windowing {
-        Windowing::BlackmanHarris => Some(generate_blackman_harris_window(fft_width)),
-        Windowing::Rectangular => None,
-    };
+    let hop = ((fft_width as f64 * (1. - overlap)).round() as u64)
+        .max(1)
+        .max((step / MAX_FFTS_PER_ROW as f64).ceil() as u64);
+    let mut row = vec![0f32; fft_width];
 
     for i in 0..output_len {
         let sample_index = start_sample + (step * i as f64).round() as u64;
+        if Combine::First == combine {
+            buf.extend(reader.magnitudes_at(samples, sample_index)?);
+            continue;
+        }
 
-        samples.read_exact_at(sample_index, &mut complex_buf)?;
-
-        if let Some(ref w) = window {
-            for (sample, &w_val) in complex_buf.iter_mut().zip(w.iter()) {
-                *sample *= w_val;
+        let row_end = (start_sample + (step * (i + 1) as f64).round() as u64).min(end_sample + 1);
+        row.iter_mut().for_each(|v| *v = 0.);
+        let mut count = 0;
+        for offset in (sample_index..row_end.max(sample_index + 1)).step_by(hop as usize) {
+            let magnitudes = reader.magnitudes_at(samples, offset)?;
+            for (acc, m) in row.iter_mut().zip(magnitudes) {
+                match combine {
+                    Combine::Mean => *acc += m * m,
+                    Combine::Max | Combine::First => *acc = acc.max(m),
+                }
             }
+            count += 1;
         }
 
-        fft.process_with_scratch(&mut complex_buf, &mut scratch);
-
-        for val in complex_buf
-            .iter()
-            .skip(fft_width / 2)
-            .chain(complex_buf.iter().take(fft_width / 2))
-        {
-            buf.push(val.norm());
+        if Combine::Mean == combine {
+            row.iter_mut().for_each(|v| *v = (*v / count as f32).sqrt());
         }
+        buf.extend_from_slice(&row);
     }
 
     Ok(FftResult {
         inner: buf.into_boxed_slice(),
         fft_width,
+        full_scale: config.windowing.gain(fft_width),
+    })
+}
+
+/// An FFT at each of `count` consecutive samples from `start`, for seeing symbols one sample
+/// at a time, which would need a row per sample from `take_fft`.
+pub fn take_fft_consecutive(
+    samples: &dyn Samples,
+    start: u64,
+    count: usize,
+    config: &FftConfig,
+) -> Result<FftResult> {
+    ensure!(count > 0, "no ffts asked for");
+    ensure!(
+        start + u64_from(count) + u64_from(config.width) <= samples.len(),
+        "{} ffts from {} would run off the end of the samples ({})",
+        count,
+        start,
+        samples.len()
+    );
+
+    let mut reader = FftReader::new(config);
+    let mut buf: Vec<f32> = Vec::with_capacity(count * config.width);
+    for offset in start..start + u64_from(count) {
+        buf.extend(reader.magnitudes_at(samples, offset)?);
+    }
+
+    Ok(FftResult {
+        inner: buf.into_boxed_slice(),
+        fft_width: config.width,
+        full_scale: config.windowing.gain(config.width),
     })
 }
 pub struct FftResult {
     inner: Box<[f32]>,
     fft_width: usize,
+    full_scale: f32,
 }
 
 impl FftResult {
@@ -94,6 +303,15 @@ impl FftResult {
         &self.inner[index * self.fft_width..(index + 1) * self.fft_width]
     }
 
+    pub fn width(&self) -> usize {
+        self.fft_width
+    }
+
+    /// The magnitude of a full-scale tone, for `Scale::apply`.
+    pub fn full_scale(&self) -> f32 {
+        self.full_scale
+    }
+
     pub fn output_len(&self) -> usize {
         self.inner.len() / self.fft_width
     }
@@ -105,15 +323,139 @@ impl FftResult {
     pub fn min(&self) -> f32 {
         self.inner.iter().cloned().fold(f32::INFINITY, f32::min)
     }
+
+    /// Every value, in order, after applying the `scale`.
+    pub fn scaled(&self, scale: Scale) -> Vec<f32> {
+        self.inner
+            .iter()
+            .map(|&v| scale.apply(v, self.full_scale))
+            .collect()
+    }
+
+    /// A guess at a useful `(min, max)` for display, from the noise floor and the peaks.
+    pub fn auto_range(&self, scale: Scale) -> (f32, f32) {
+        auto_range(&mut self.scaled(scale))
+    }
+}
+
+/// As `FftResult::auto_range`, for values which have already been scaled, and will be reordered.
+pub fn auto_range(vals: &mut [f32]) -> (f32, f32) {
+    let floor = percentile(vals, AUTO_FLOOR_PERCENTILE);
+    let peak = percentile(vals, AUTO_PEAK_PERCENTILE);
+
+    // a totally flat input would otherwise draw nothing, or divide by zero
+    if peak <= floor {
+        return (floor, floor + f32::EPSILON.max(floor.abs() * 0.01));
+    }
+
+    (floor, peak)
+}
+
+/// The value `p` percent of the way through `values`, which will be reordered.
+pub fn percentile(values: &mut [f32], p: f32) -> f32 {
+    assert!(!values.is_empty(), "percentile of nothing");
+    let index = ((values.len() - 1) as f32 * p / 100.).round() as usize;
+    *values
+        .select_nth_unstable_by(index, |a, b| a.total_cmp(b))
+        .1
+}
+
+/// `a0 - a1 cos(x) + a2 cos(2x) - ...`, the family of Hann, Hamming, Blackman-Harris, ...
+fn cosine_sum_window(n: usize, coefficients: &[f32]) -> Vec<f32> {
+    if n < 2 {
+        return vec![1.; n];
+    }
+    (0..n)
+        .map(|i| {
+            let x = std::f32::consts::TAU * i as f32 / (n - 1) as f32;
+            coefficients
+                .iter()
+                .enumerate()
+                .map(|(k, a)| {
+                    let sign = if k % 2 == 0 { 1. } else { -1. };
+                    sign * a * (k as f32 * x).cos()
+                })
+                .sum()
+        })
+        .collect()
+}
+
+fn kaiser_window(n: usize, beta: f32) -> Vec<f32> {
+    if n < 2 {
+        return vec![1.; n];
+    }
+    let beta = f64::from(beta);
+    let denominator = bessel_i0(beta);
+    (0..n)
+        .map(|i| {
+            let x = 2. * i as f64 / (n - 1) as f64 - 1.;
+            (bessel_i0(beta * (1. - x * x).max(0.).sqrt()) / denominator) as f32
+        })
+        .collect()
 }
 
-fn generate_blackman_harris_window(n: usize) -> Vec<f32> {
-    let mut window = Vec::with_capacity(n);
-    for i in 0..n {
-        let x = std::f32::consts::TAU * i as f32 / (n - 1) as f32;
-        let value =
-            0.35875 - 0.48829 * x.cos() + 0.14128 * (2.0 * x).cos() - 0.01168 * (3.0 * x).cos();
-        window.push(value);
+/// The zeroth-order modified Bessel function of the first kind, by its power series.
+fn bessel_i0(x: f64) -> f64 {
+    let quarter_square = x * x / 4.;
+    let mut term = 1.;
+    let mut sum = 1.;
+    for k in 1..100 {
+        term *= quarter_square / (k * k) as f64;
+        sum += term;
+        if term < sum * 1e-12 {
+            break;
+        }
+    }
+    sum
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn percentile() {
+        use super::percentile;
+        let mut vals = [5., 1., 4., 2., 3.];
+        assert_eq!(1., percentile(&mut vals, 0.));
+        assert_eq!(3., percentile(&mut vals, 50.));
+        assert_eq!(5., percentile(&mut vals, 100.));
+    }
+
+    #[test]
+    fn windows() {
+        use super::Windowing;
+        for window in Windowing::ALL
+            .iter()
+            .filter(|w| w.coefficients(2).is_some())
+        {
+            let coefficients = window.coefficients(65).unwrap();
+            assert!((coefficients[32] - 1.).abs() < 1e-3, "{:?}", window);
+            assert!(
+                (coefficients[0] - coefficients[64]).abs() < 1e-5,
+                "{:?}",
+                window
+            );
+            assert!(coefficients[0] < 0.1, "{:?}", window);
+        }
+    }
+
+    #[test]
+    fn combined() {
+        use super::{take_fft_combined, Combine, FftConfig, Windowing};
+        use crate::gen::Gen;
+
+        let samples = Gen::new(vec![1_000], 8_000, 1.).unwrap();
+        let config = FftConfig {
+            width: 64,
+            windowing: Windowing::Hann,
+        };
+        for combine in [Combine::First, Combine::Mean, Combine::Max] {
+            let result =
+                take_fft_combined(&samples, Some((0, 7_000)), &config, 10, combine, 0.5).unwrap();
+            assert_eq!(10, result.output_len());
+            let row = result.get(9);
+            let peak = (0..64).max_by(|&a, &b| row[a].total_cmp(&row[b])).unwrap();
+            // 1kHz of 8kHz is eight bins above the middle
+            assert_eq!(32 + 8, peak, "{:?}", combine);
+        }
     }
-    window
 }
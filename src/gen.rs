@@ -33,14 +33,14 @@ impl Samples for Gen {
     }
 
     fn read_at(&self, off: u64, buf: &mut [Complex<f32>]) -> usize {
-        for i in 0..buf.len() {
+        for (i, sample) in buf.iter_mut().enumerate() {
             let base = (off + (i as u64)) as f64 * TAU / self.sample_rate as f64;
             let mut val = Complex::zero();
             for freq in &self.cos {
                 let f = (*freq as f64) * base;
                 val += Complex::new(f.cos() as f32, f.sin() as f32);
             }
-            buf[i] = val;
+            *sample = val;
         }
 
         buf.len()
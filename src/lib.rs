@@ -1,22 +1,32 @@
+pub mod annotations;
 pub mod args;
+pub mod axis;
 pub mod bits;
+pub mod colormap;
+pub mod detect;
 pub mod eui;
-mod fft;
-mod ffts;
+pub mod fft;
+pub mod ffts;
 mod filter;
 mod gen;
+pub mod psd;
+pub mod render;
+mod resample;
 mod samples;
 mod shift;
-pub mod ui;
+mod slice;
+pub mod tui;
 
 use std::f64::consts::PI;
 use std::fs;
 
 use anyhow::anyhow;
+use anyhow::ensure;
 use anyhow::Error;
 use byteorder::ByteOrder;
 use num_traits::identities::Zero;
 use rustfft::num_complex::Complex;
+use serde::{Deserialize, Serialize};
 
 pub use crate::samples::Samples;
 
@@ -31,17 +41,25 @@ pub enum Operation {
     Shift {
         frequency: i64,
     },
+    Slice {
+        start: Option<f64>,
+        end: Option<f64>,
+    },
     LowPass {
         size: usize,
         decimate: u64,
         frequency: u64,
     },
-    SparkFft {
-        width: usize,
-        stride: u64,
-        min: Option<f32>,
-        max: Option<f32>,
+    Resample {
+        rate: u64,
     },
+    Extract(annotations::Annotation),
+    Annotations(Vec<annotations::Annotation>),
+    SparkFft(fft::SparkFft),
+    Png(render::Png),
+    Psd(psd::Psd),
+    Signals(detect::Signals),
+    Bursts(detect::Bursts),
     Bucket {
         fft_width: usize,
         stride: u64,
@@ -50,6 +68,7 @@ pub enum Operation {
     Write {
         overwrite: bool,
         prefix: String,
+        format: FileFormat,
     },
     Gen {
         seconds: f64,
@@ -58,7 +77,7 @@ pub enum Operation {
     },
 }
 
-#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Serialize, Deserialize)]
 pub enum FileFormat {
     /// GNU-Radio
     ComplexFloat32,
@@ -73,10 +92,11 @@ pub enum FileFormat {
     ComplexInt16,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FileDetails {
     pub format: FileFormat,
     pub sample_rate: u64,
+    pub centre_frequency: Option<u64>,
 }
 
 impl Operation {
@@ -93,6 +113,7 @@ impl Operation {
                 fs::File::open(filename)?,
                 details.format,
                 details.sample_rate,
+                details.centre_frequency,
             ))),
             Gen {
                 sample_rate,
@@ -104,6 +125,11 @@ impl Operation {
                 let sample_rate = orig.sample_rate();
                 Some(Box::new(shift::Shift::new(orig, frequency, sample_rate)))
             }
+            Slice { start, end } => {
+                let orig = samples.ok_or_else(|| anyhow!("slice requires an input"))?;
+                let (start, end) = slice::seconds_to_range(&orig, start, end)?;
+                Some(Box::new(slice::Slice::new(orig, start, end)?))
+            }
             LowPass {
                 size,
                 decimate,
@@ -111,6 +137,14 @@ impl Operation {
             } => {
                 let orig = samples.ok_or_else(|| anyhow!("lowpass requires an input"))?;
                 let original_sample_rate = orig.sample_rate();
+                ensure!(decimate > 0, "lowpass decimation must be at least one");
+                ensure!(size > 0, "lowpass filter must have some size");
+                ensure!(
+                    orig.len() >= u64_from(size),
+                    "lowpass filter ({}) is longer than the input ({})",
+                    size,
+                    orig.len()
+                );
                 Some(Box::new(filter::LowPass::new(
                     orig,
                     frequency,
@@ -119,20 +153,71 @@ impl Operation {
                     size,
                 )))
             }
-            SparkFft {
-                width,
-                stride,
-                min,
-                max,
-            } => {
+            Resample { rate } => {
+                let orig = samples.ok_or_else(|| anyhow!("resample requires an input"))?;
+                ensure!(rate > 0, "resample rate must be positive");
+                Some(Box::new(resample::Resample::new(orig, rate)))
+            }
+            Extract(ref annotation) => {
+                let mut samples = samples.ok_or_else(|| anyhow!("extract requires an input"))?;
+                for op in annotation.operations(samples.sample_rate()) {
+                    samples = op
+                        .exec(Some(samples))?
+                        .ok_or_else(|| anyhow!("extracting produced no output"))?;
+                }
+                Some(samples)
+            }
+            Annotations(ref annotations) => {
+                annotations::print_annotations(
+                    samples
+                        .as_ref()
+                        .ok_or_else(|| anyhow!("annotations requires an input"))?,
+                    annotations,
+                );
+                samples
+            }
+            SparkFft(ref spark) => {
                 fft::spark_fft(
                     samples
                         .as_mut()
                         .ok_or_else(|| anyhow!("sparkfft requires an input"))?,
-                    width,
-                    stride,
-                    min,
-                    max,
+                    spark,
+                )?;
+                samples
+            }
+            Png(ref png) => {
+                render::write_png(
+                    samples
+                        .as_mut()
+                        .ok_or_else(|| anyhow!("png requires an input"))?,
+                    png,
+                )?;
+                samples
+            }
+            Psd(ref psd) => {
+                psd::write_psd(
+                    samples
+                        .as_mut()
+                        .ok_or_else(|| anyhow!("psd requires an input"))?,
+                    psd,
+                )?;
+                samples
+            }
+            Signals(ref signals) => {
+                detect::print_signals(
+                    samples
+                        .as_mut()
+                        .ok_or_else(|| anyhow!("signals requires an input"))?,
+                    signals,
+                )?;
+                samples
+            }
+            Bursts(ref bursts) => {
+                detect::print_bursts(
+                    samples
+                        .as_mut()
+                        .ok_or_else(|| anyhow!("bursts requires an input"))?,
+                    bursts,
                 )?;
                 samples
             }
@@ -161,6 +246,7 @@ impl Operation {
             Write {
                 overwrite,
                 ref prefix,
+                format,
             } => {
                 do_write(
                     samples
@@ -168,6 +254,7 @@ impl Operation {
                         .ok_or_else(|| anyhow!("write requires an input"))?,
                     overwrite,
                     prefix,
+                    format,
                 )?;
                 samples
             }
@@ -175,12 +262,17 @@ impl Operation {
     }
 }
 
-fn do_write(samples: &mut dyn Samples, overwrite: bool, prefix: &str) -> Result<(), Error> {
+/// Write all the samples to `{prefix}.sr{rate}.{extension}`, returning that name.
+fn do_write(
+    samples: &mut dyn Samples,
+    overwrite: bool,
+    prefix: &str,
+    format: FileFormat,
+) -> Result<String, Error> {
     if "-" == prefix {
         unimplemented!()
     }
 
-    use byteorder::WriteBytesExt;
     use std::io;
 
     let mut options = fs::OpenOptions::new();
@@ -191,10 +283,14 @@ fn do_write(samples: &mut dyn Samples, overwrite: bool, prefix: &str) -> Result<
         options.create_new(true);
     }
 
-    let filename = format!("{}.sr{}.cf32", prefix, samples.sample_rate());
+    let filename = format!(
+        "{}.sr{}.{}",
+        prefix,
+        samples.sample_rate(),
+        format.extension()
+    );
 
-    let mut file = io::BufWriter::new(options.open(filename)?);
-    use byteorder::LittleEndian;
+    let mut file = io::BufWriter::new(options.open(&filename)?);
 
     let mut off = 0;
     while off < samples.len() {
@@ -204,19 +300,36 @@ fn do_write(samples: &mut dyn Samples, overwrite: bool, prefix: &str) -> Result<
         off += read as u64;
 
         for sample in &buf[..read] {
-            file.write_f32::<LittleEndian>(sample.re)?;
-            file.write_f32::<LittleEndian>(sample.im)?;
+            format.write_cf32(&mut file, *sample)?;
         }
     }
 
-    Ok(())
+    Ok(filename)
 }
 
 impl FileFormat {
+    pub const ALL: [FileFormat; 4] = [
+        FileFormat::ComplexFloat32,
+        FileFormat::ComplexInt8,
+        FileFormat::ComplexUint8,
+        FileFormat::ComplexInt16,
+    ];
+
+    /// The conventional file extension, as accepted by `from -format`.
+    pub fn extension(self) -> &'static str {
+        use crate::FileFormat::*;
+        match self {
+            ComplexFloat32 => "cf32",
+            ComplexInt8 => "cs8",
+            ComplexUint8 => "cu8",
+            ComplexInt16 => "cs16",
+        }
+    }
+
     #[inline]
-    const fn type_bytes(&self) -> u64 {
+    const fn type_bytes(self) -> u64 {
         use crate::FileFormat::*;
-        match *self {
+        match self {
             ComplexFloat32 => 4,
             ComplexInt8 | ComplexUint8 => 1,
             ComplexInt16 => 2,
@@ -224,11 +337,11 @@ impl FileFormat {
     }
 
     #[inline]
-    const fn pair_bytes(&self) -> u64 {
+    const fn pair_bytes(self) -> u64 {
         self.type_bytes() * 2
     }
 
-    fn to_cf32(&self, buf: &[u8]) -> Complex<f32> {
+    fn to_cf32(self, buf: &[u8]) -> Complex<f32> {
         assert_eq!(self.pair_bytes(), buf.len() as u64);
         let type_bytes = self.type_bytes() as usize;
         Complex::new(
@@ -238,33 +351,141 @@ impl FileFormat {
     }
 
     #[inline]
-    fn to_f32(&self, buf: &[u8]) -> f32 {
+    fn to_f32(self, buf: &[u8]) -> f32 {
         use crate::FileFormat::*;
         use byteorder::LittleEndian;
 
         assert_eq!(self.type_bytes(), buf.len() as u64);
 
-        match *self {
+        match self {
             ComplexFloat32 => LittleEndian::read_f32(buf),
 
-            // TODO: all guesses
-            ComplexInt8 => f32::from(buf[0] as i8) / 127.0,
-            ComplexUint8 => f32::from(buf[0]) / 255.0 - (255.0 / 2.0),
-            ComplexInt16 => f32::from(LittleEndian::read_i16(buf)) / 65535.0 - (65535.0 / 2.0),
+            // integers are scaled so full-scale is (about) ±1
+            ComplexInt8 => f32::from(buf[0] as i8) / I8_SCALE,
+            ComplexUint8 => (f32::from(buf[0]) - U8_OFFSET) / U8_OFFSET,
+            ComplexInt16 => f32::from(LittleEndian::read_i16(buf)) / I16_SCALE,
         }
     }
+
+    fn write_cf32<W: std::io::Write>(self, to: &mut W, sample: Complex<f32>) -> Result<(), Error> {
+        self.write_f32(to, sample.re)?;
+        self.write_f32(to, sample.im)?;
+        Ok(())
+    }
+
+    /// The inverse of `to_f32`, saturating values which don't fit.
+    #[inline]
+    fn write_f32<W: std::io::Write>(self, to: &mut W, val: f32) -> Result<(), Error> {
+        use crate::FileFormat::*;
+        use byteorder::{LittleEndian, WriteBytesExt};
+
+        match self {
+            ComplexFloat32 => to.write_f32::<LittleEndian>(val)?,
+            ComplexInt8 => to.write_i8((val * I8_SCALE).round() as i8)?,
+            ComplexUint8 => to.write_u8((val * U8_OFFSET + U8_OFFSET).round() as u8)?,
+            ComplexInt16 => to.write_i16::<LittleEndian>((val * I16_SCALE).round() as i16)?,
+        }
+        Ok(())
+    }
 }
 
+const I8_SCALE: f32 = 127.;
+const U8_OFFSET: f32 = 127.5;
+const I16_SCALE: f32 = 32767.;
+
 // clippy
-#[allow(unknown_lints, absurd_extreme_comparisons)]
+#[allow(unknown_lints, clippy::absurd_extreme_comparisons)]
 fn usize_from(val: u64) -> usize {
     assert!(val <= usize::MAX as u64);
     val as usize
 }
 
 // clippy
-#[allow(unknown_lints, absurd_extreme_comparisons)]
+#[allow(unknown_lints, clippy::absurd_extreme_comparisons)]
 fn u64_from(val: usize) -> u64 {
     assert!((val as u64) <= u64::MAX);
     val as u64
 }
+
+#[cfg(test)]
+mod tests {
+    use rustfft::num_complex::Complex;
+
+    use crate::FileFormat;
+
+    #[test]
+    fn formats_round_trip() {
+        let samples = [
+            Complex::new(0., 0.),
+            Complex::new(0.5, -0.25),
+            Complex::new(-1., 1.),
+        ];
+        for format in FileFormat::ALL {
+            let mut buf = Vec::new();
+            for sample in samples {
+                format.write_cf32(&mut buf, sample).unwrap();
+            }
+            assert_eq!(3 * format.pair_bytes() as usize, buf.len());
+
+            for (i, expected) in samples.iter().enumerate() {
+                let pair = format.pair_bytes() as usize;
+                let read = format.to_cf32(&buf[i * pair..(i + 1) * pair]);
+                assert!((read - expected).norm() < 0.01, "{:?}: {}", format, read);
+            }
+        }
+
+        // too loud: clipped, not wrapped
+        let mut buf = Vec::new();
+        FileFormat::ComplexInt8
+            .write_cf32(&mut buf, Complex::new(2., -2.))
+            .unwrap();
+        assert_eq!(vec![0x7f, 0x80], buf);
+    }
+
+    #[test]
+    fn reads_cu8() {
+        use crate::samples::SampleFile;
+        use crate::Samples;
+
+        let path = std::env::temp_dir().join(format!("quadrs-{}.sr8k.cu8", std::process::id()));
+        std::fs::write(&path, [0u8, 255, 128, 127]).unwrap();
+        let file = SampleFile::new(
+            std::fs::File::open(&path).unwrap(),
+            FileFormat::ComplexUint8,
+            8_000,
+            None,
+        );
+        let mut buf = [Complex::new(0., 0.); 2];
+        file.read_exact_at(0, &mut buf).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        // full scale is ±1, and the middle codes straddle zero
+        assert_eq!(2, file.len());
+        assert_eq!(Complex::new(-1., 1.), buf[0]);
+        assert!((buf[1] - Complex::new(0.5 / 127.5, -0.5 / 127.5)).norm() < 1e-6);
+    }
+
+    #[test]
+    fn reads_cs16() {
+        use crate::samples::SampleFile;
+        use crate::Samples;
+
+        let path = std::env::temp_dir().join(format!("quadrs-{}.sr8k.cs16", std::process::id()));
+        // little-endian 32767, -32767, 0, -32768
+        std::fs::write(&path, [0xffu8, 0x7f, 0x01, 0x80, 0, 0, 0x00, 0x80]).unwrap();
+        let file = SampleFile::new(
+            std::fs::File::open(&path).unwrap(),
+            FileFormat::ComplexInt16,
+            8_000,
+            None,
+        );
+        let mut buf = [Complex::new(0., 0.); 2];
+        file.read_exact_at(0, &mut buf).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        // full scale is ±1, zero is zero, and the extra negative code is just past -1
+        assert_eq!(2, file.len());
+        assert_eq!(Complex::new(1., -1.), buf[0]);
+        assert_eq!(Complex::new(0., -32_768. / 32_767.), buf[1]);
+    }
+}
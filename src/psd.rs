@@ -0,0 +1,354 @@
+//! Power spectral density estimation, by averaging many FFTs of a whole input.
+
+use std::fs;
+use std::io::Write;
+
+use anyhow::{anyhow, ensure, Context, Result};
+use serde::Serialize;
+
+use crate::axis::{format_si, text_header};
+use crate::ffts::{FftConfig, FftReader, Windowing};
+use crate::samples::Samples;
+use crate::u64_from;
+
+#[derive(Debug, Clone)]
+pub struct Psd {
+    pub width: usize,
+
+    /// Fraction of each FFT which is shared with the previous one, in `0..1`.
+    pub overlap: f64,
+    pub window: Windowing,
+    pub format: PsdFormat,
+
+    /// Where to write the output, or `None` for standard out.
+    pub output: Option<String>,
+
+    /// Also draw the spectrum in the terminal.
+    pub plot: bool,
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum PsdFormat {
+    Csv,
+    Json,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Spectrum {
+    pub sample_rate: u64,
+    pub centre_frequency: Option<u64>,
+    pub segments: u64,
+    pub bins: Vec<Bin>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Bin {
+    /// Absolute if the centre frequency is known, otherwise relative to the centre.
+    pub frequency: f64,
+
+    /// Power relative to a full-scale tone in the centre of this bin.
+    pub dbfs: f32,
+}
+
+/// Welch's method: the mean power of overlapping, windowed FFTs, across the whole input.
+pub fn welch(samples: &dyn Samples, config: &FftConfig, overlap: f64) -> Result<Spectrum> {
+    ensure!(
+        (0. ..1.).contains(&overlap),
+        "overlap must be at least zero, and less than one: {}",
+        overlap
+    );
+
+    let width = u64_from(config.width);
+    ensure!(
+        samples.len() >= width,
+        "input ({} samples) is shorter than the fft width ({})",
+        samples.len(),
+        width
+    );
+
+    let step = ((width as f64 * (1. - overlap)).round() as u64).max(1);
+    let segments = (samples.len() - width) / step + 1;
+
+    let mut reader = FftReader::new(config);
+    let mut power = vec![0f64; config.width];
+    for segment in 0..segments {
+        for (total, norm) in power
+            .iter_mut()
+            .zip(reader.magnitudes_at(samples, segment * step)?)
+        {
+            *total += f64::from(norm) * f64::from(norm);
+        }
+    }
+
+    let full_scale = f64::from(config.windowing.gain(config.width)).powi(2);
+    let sample_rate = samples.sample_rate();
+    let centre = samples.centre_frequency().unwrap_or(0) as f64;
+    let bin_width = sample_rate as f64 / config.width as f64;
+
+    let bins = power
+        .into_iter()
+        .enumerate()
+        .map(|(i, total)| Bin {
+            frequency: centre + (i as f64 - (config.width / 2) as f64) * bin_width,
+            dbfs: (10. * (total / segments as f64 / full_scale).max(1e-20).log10()) as f32,
+        })
+        .collect();
+
+    Ok(Spectrum {
+        sample_rate,
+        centre_frequency: samples.centre_frequency(),
+        segments,
+        bins,
+    })
+}
+
+/// The power in part of a spectrum, and how it's spread.
+#[derive(Debug, Clone, Copy)]
+pub struct BandPower {
+    /// The total, relative to a full-scale tone.
+    pub dbfs: f64,
+    /// The band holding 99% of the power, with 0.5% left out at each edge, in Hz;
+    /// the occupied bandwidth as ITU-R SM.328 has it, not the narrowest such band.
+    pub occupied: f64,
+    /// The width of the band around the peak which is within 20dB of it, in Hz.
+    pub below_peak: f64,
+    pub peak: Bin,
+}
+
+/// Where `occupied` leaves out this much power, at each edge.
+const OCCUPIED_TAIL: f64 = 0.005;
+const BELOW_PEAK_DB: f32 = 20.;
+
+/// Measure the bins between `low` and `high` (in the same terms as the bins' frequencies).
+///
+/// `noise_bandwidth` is that of the window, in bins; each bin also catches some of its
+/// neighbours' power, and this many bins' worth in total.
+pub fn band_power(
+    spectrum: &Spectrum,
+    low: f64,
+    high: f64,
+    noise_bandwidth: f64,
+) -> Option<BandPower> {
+    let bins: Vec<Bin> = spectrum
+        .bins
+        .iter()
+        .filter(|bin| bin.frequency >= low && bin.frequency <= high)
+        .cloned()
+        .collect();
+    let bin_width = spectrum.sample_rate as f64 / spectrum.bins.len() as f64;
+
+    let powers: Vec<f64> = bins
+        .iter()
+        .map(|bin| 10f64.powf(f64::from(bin.dbfs) / 10.))
+        .collect();
+    let total: f64 = powers.iter().sum();
+    if bins.is_empty() || total <= 0. {
+        return None;
+    }
+
+    let mut running = 0.;
+    let cumulative: Vec<f64> = powers
+        .iter()
+        .map(|power| {
+            running += power;
+            running / total
+        })
+        .collect();
+    let first = cumulative
+        .iter()
+        .position(|&c| c >= OCCUPIED_TAIL)
+        .unwrap_or(0);
+    let last = cumulative
+        .iter()
+        .position(|&c| c >= 1. - OCCUPIED_TAIL)
+        .unwrap_or(bins.len() - 1);
+
+    let (peak_index, peak) = bins
+        .iter()
+        .enumerate()
+        .max_by(|(_, a), (_, b)| a.dbfs.total_cmp(&b.dbfs))
+        .expect("not empty");
+    let loud = |bin: &Bin| bin.dbfs >= peak.dbfs - BELOW_PEAK_DB;
+    let below = bins[..peak_index]
+        .iter()
+        .rev()
+        .take_while(|b| loud(b))
+        .count();
+    let above = bins[peak_index + 1..]
+        .iter()
+        .take_while(|b| loud(b))
+        .count();
+
+    Some(BandPower {
+        dbfs: 10. * (total / noise_bandwidth).log10(),
+        occupied: (last - first + 1) as f64 * bin_width,
+        below_peak: (below + above + 1) as f64 * bin_width,
+        peak: *peak,
+    })
+}
+
+pub fn write_psd(samples: &dyn Samples, psd: &Psd) -> Result<()> {
+    let spectrum = welch(
+        samples,
+        &FftConfig {
+            width: psd.width,
+            windowing: psd.window,
+        },
+        psd.overlap,
+    )?;
+
+    let mut out: Box<dyn Write> = match psd.output {
+        Some(ref filename) => Box::new(
+            fs::File::create(filename).with_context(|| anyhow!("creating {:?}", filename))?,
+        ),
+        None => Box::new(std::io::stdout()),
+    };
+
+    match psd.format {
+        PsdFormat::Csv => {
+            writeln!(out, "frequency,dbfs")?;
+            for bin in &spectrum.bins {
+                writeln!(out, "{},{:.2}", bin.frequency, bin.dbfs)?;
+            }
+        }
+        PsdFormat::Json => {
+            serde_json::to_writer(&mut out, &spectrum)?;
+            writeln!(out)?;
+        }
+    }
+
+    if psd.plot {
+        // don't interleave the plot with data being written to standard out
+        out.flush()?;
+        eprint!("{}", plot(&spectrum, terminal_columns(), 16));
+    }
+
+    Ok(())
+}
+
+/// The width of the terminal, if the shell has told us, or a guess.
+fn terminal_columns() -> usize {
+    std::env::var("COLUMNS")
+        .ok()
+        .and_then(|val| val.parse().ok())
+        .unwrap_or(80)
+}
+
+/// Width of the dB labels on the left of the plot.
+const PLOT_LABEL_WIDTH: usize = 8;
+
+/// A line plot of the spectrum, `columns` wide and `rows` high, with labelled axes.
+pub fn plot(spectrum: &Spectrum, columns: usize, rows: usize) -> String {
+    let columns = columns
+        .saturating_sub(PLOT_LABEL_WIDTH + 2)
+        .clamp(1, spectrum.bins.len());
+
+    // the peak of each group of bins, so narrow carriers don't vanish
+    let bins_per_column = spectrum.bins.len() as f64 / columns as f64;
+    let peaks: Vec<f32> = (0..columns)
+        .map(|column| {
+            let first = (column as f64 * bins_per_column) as usize;
+            let last = (((column + 1) as f64 * bins_per_column) as usize).max(first + 1);
+            spectrum.bins[first..last]
+                .iter()
+                .map(|bin| bin.dbfs)
+                .fold(f32::NEG_INFINITY, f32::max)
+        })
+        .collect();
+
+    let min = peaks.iter().cloned().fold(f32::INFINITY, f32::min);
+    let max = peaks.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+    let range = (max - min).max(f32::EPSILON);
+
+    // measured in eighths of a row, from the bottom
+    let eighths: Vec<usize> = peaks
+        .iter()
+        .map(|v| ((v - min) / range * (rows * 8 - 1) as f32).round() as usize)
+        .collect();
+
+    let graph: Vec<char> = "▁▂▃▄▅▆▇█".chars().collect();
+    let mut ret = String::new();
+    for row in (0..rows).rev() {
+        let label = if row == rows - 1 || 0 == row || row == rows / 2 {
+            format!("{:.1}", min + range * row as f32 / (rows - 1) as f32)
+        } else {
+            String::new()
+        };
+        ret.push_str(&format!("{:>width$} ┤", label, width = PLOT_LABEL_WIDTH));
+        for &level in &eighths {
+            ret.push(if level / 8 == row {
+                graph[level % 8]
+            } else {
+                ' '
+            });
+        }
+        ret.push('\n');
+    }
+
+    let first = spectrum.bins[0].frequency;
+    let last = spectrum.bins[spectrum.bins.len() - 1].frequency;
+    ret.push_str(&format!(
+        "{:>width$}  {}\n",
+        "dBFS",
+        text_header(columns, first, last),
+        width = PLOT_LABEL_WIDTH
+    ));
+    ret.push_str(&format!(
+        "{:>width$}  {} segments, {}Hz per bin\n",
+        "",
+        spectrum.segments,
+        format_si(spectrum.sample_rate as f64 / spectrum.bins.len() as f64),
+        width = PLOT_LABEL_WIDTH
+    ));
+
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn full_scale_tone() {
+        use super::welch;
+        use crate::ffts::{FftConfig, Windowing};
+        use crate::gen::Gen;
+
+        let tone = Gen::new(vec![1_000], 8_000, 1.).unwrap();
+        let config = FftConfig {
+            width: 64,
+            windowing: Windowing::BlackmanHarris,
+        };
+        let spectrum = welch(&tone, &config, 0.5).unwrap();
+        let peak = spectrum
+            .bins
+            .iter()
+            .max_by(|a, b| a.dbfs.total_cmp(&b.dbfs))
+            .unwrap();
+        assert_eq!(1_000., peak.frequency);
+        assert!(peak.dbfs.abs() < 0.01, "{:?}", peak);
+    }
+
+    #[test]
+    fn band_power() {
+        use super::{band_power, welch};
+        use crate::ffts::{FftConfig, Windowing};
+        use crate::gen::Gen;
+
+        // two full-scale tones: twice the power of one, i.e. 3dB
+        let tones = Gen::new(vec![1_000, 2_000], 8_000, 1.).unwrap();
+        let windowing = Windowing::Hann;
+        let config = FftConfig {
+            width: 256,
+            windowing,
+        };
+        let spectrum = welch(&tones, &config, 0.5).unwrap();
+        let noise_bandwidth = f64::from(windowing.noise_bandwidth(256));
+        let both = band_power(&spectrum, 500., 2_500., noise_bandwidth).unwrap();
+        assert!((both.dbfs - 3.01).abs() < 0.1, "{:?}", both);
+        assert!((both.occupied - 1_000.).abs() < 100., "{:?}", both);
+
+        let one = band_power(&spectrum, 500., 1_500., noise_bandwidth).unwrap();
+        assert!(one.dbfs.abs() < 0.1, "{:?}", one);
+        assert_eq!(1_000., one.peak.frequency);
+        assert!(one.below_peak < 200., "{:?}", one);
+    }
+}
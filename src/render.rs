@@ -0,0 +1,347 @@
+//! Drawing waterfalls into images, without needing a display.
+
+use anyhow::{anyhow, ensure, Context, Result};
+use image::{Rgb, RgbImage};
+use rusttype::{point, Font};
+
+use crate::axis::{format_seconds, format_si, ticks, FrequencyReference};
+use crate::colormap::Colormap;
+use crate::ffts::{take_fft, FftConfig, FftResult, Scale, Windowing};
+use crate::samples::Samples;
+use crate::{u64_from, usize_from};
+
+#[derive(Debug, Clone)]
+pub struct Png {
+    pub filename: String,
+
+    /// Width of the FFT, which is also the width of the waterfall, in pixels.
+    pub width: usize,
+    pub window: Windowing,
+    pub scale: Scale,
+    pub colormap: Colormap,
+    pub min: Option<f32>,
+    pub max: Option<f32>,
+
+    /// Seconds into the input to start and end at.
+    pub start: Option<f64>,
+    pub end: Option<f64>,
+
+    /// Height of the waterfall, in pixels.
+    pub height: usize,
+
+    /// Draw labelled frequency and time axes around the waterfall.
+    pub axis: Option<FrequencyReference>,
+}
+
+const BACKGROUND: Rgb<u8> = Rgb([255, 255, 255]);
+const FOREGROUND: Rgb<u8> = Rgb([0, 0, 0]);
+
+const LABEL_HEIGHT: f32 = 14.;
+const TICK_LENGTH: u32 = 4;
+const MARGIN_LEFT: u32 = 80;
+const MARGIN_TOP: u32 = 24;
+const MARGIN_RIGHT: u32 = 24;
+const MARGIN_BOTTOM: u32 = 8;
+
+const COLOUR_BAR_WIDTH: u32 = 16;
+const COLOUR_BAR_GAP: u32 = 8;
+const CAPTION_PADDING: u32 = 8;
+
+pub fn write_png(samples: &dyn Samples, png: &Png) -> Result<()> {
+    let sample_rate = samples.sample_rate() as f64;
+    let fft_width = u64_from(png.width);
+    ensure!(
+        samples.len() > fft_width + 2,
+        "input ({} samples) is too short for a {} wide fft",
+        samples.len(),
+        png.width
+    );
+
+    let last = samples.len() - fft_width - 1;
+    let to_sample = |seconds: f64| ((seconds * sample_rate).max(0.) as u64).min(last);
+    let start = png.start.map(to_sample).unwrap_or(0);
+    let end = png.end.map(to_sample).unwrap_or(last);
+    ensure!(end > start + 1, "-end must be after -start");
+
+    let rows = png.height.min(usize_from(end - start - 1));
+    let fft = take_fft(
+        samples,
+        Some((start, end)),
+        &FftConfig {
+            width: png.width,
+            windowing: png.window,
+        },
+        rows,
+    )?;
+
+    let (floor, peak) = fft.auto_range(png.scale);
+    let range = (png.min.unwrap_or(floor), png.max.unwrap_or(peak));
+    let mut image = waterfall(&fft, png.scale, png.colormap, range);
+
+    if let Some(reference) = png.axis {
+        let offset = match reference {
+            FrequencyReference::Relative => 0.,
+            FrequencyReference::Absolute => samples.centre_frequency().ok_or_else(|| {
+                anyhow!("absolute frequencies require a centre frequency, e.g. from -centre")
+            })? as f64,
+        };
+        let axes = Axes::for_band(sample_rate, offset, png.width)
+            .with_times(start as f64 / sample_rate, end as f64 / sample_rate);
+        image = axes.draw(&image, &Labeller::new()?);
+    }
+
+    image
+        .save(&png.filename)
+        .with_context(|| anyhow!("writing {:?}", png.filename))?;
+
+    Ok(())
+}
+
+/// One pixel per bin, one row per FFT, with the first FFT at the top.
+pub fn waterfall(fft: &FftResult, scale: Scale, colormap: Colormap, range: (f32, f32)) -> RgbImage {
+    let fft_width = fft.get(0).len();
+    let (min, max) = range;
+    let mut image = RgbImage::new(fft_width as u32, fft.output_len() as u32);
+    for (y, row) in (0..fft.output_len()).map(|y| (y, fft.get(y))) {
+        for (x, &norm) in row.iter().enumerate() {
+            let val = scale.apply(norm, fft.full_scale());
+            image.put_pixel(x as u32, y as u32, Rgb(colormap.rgb_in(val, min, max)));
+        }
+    }
+    image
+}
+
+/// What the edges of an image represent.
+#[derive(Debug, Clone, Copy)]
+pub struct Axes {
+    /// The frequencies at the left and right edges.
+    pub frequencies: (f64, f64),
+
+    /// The times, in seconds, at the top and bottom edges.
+    pub times: Option<(f64, f64)>,
+}
+
+impl Axes {
+    /// The band covered by an fft, where each bin's centre is at its frequency.
+    pub fn for_band(sample_rate: f64, offset: f64, fft_width: usize) -> Axes {
+        let bin_width = sample_rate / fft_width as f64;
+        let left = -((fft_width / 2) as f64 + 0.5) * bin_width + offset;
+        Axes {
+            frequencies: (left, left + sample_rate),
+            times: None,
+        }
+    }
+
+    pub fn with_times(self, start: f64, end: f64) -> Axes {
+        Axes {
+            times: Some((start, end)),
+            ..self
+        }
+    }
+
+    /// A copy of `image`, with margins containing labelled ticks.
+    pub fn draw(&self, image: &RgbImage, labeller: &Labeller) -> RgbImage {
+        let (width, height) = image.dimensions();
+        let mut canvas = RgbImage::from_pixel(
+            MARGIN_LEFT + width + MARGIN_RIGHT,
+            MARGIN_TOP + height + MARGIN_BOTTOM,
+            BACKGROUND,
+        );
+        image::imageops::replace(&mut canvas, image, MARGIN_LEFT.into(), MARGIN_TOP.into());
+
+        let (left, right) = self.frequencies;
+        let mut free_from = f32::MIN;
+        for tick in ticks(left, right, (width / 100).max(2) as usize) {
+            let x = MARGIN_LEFT + ((tick - left) / (right - left) * width as f64) as u32;
+            for y in MARGIN_TOP - TICK_LENGTH..MARGIN_TOP {
+                canvas.put_pixel(x, y, FOREGROUND);
+            }
+            let label = format_si(tick);
+            let label_width = labeller.width(&label);
+            let label_x = x as f32 - label_width / 2.;
+            if label_x < free_from {
+                continue;
+            }
+            free_from = label_x + label_width + LABEL_HEIGHT / 2.;
+            labeller.draw(
+                &mut canvas,
+                label_x,
+                (MARGIN_TOP - TICK_LENGTH - 2) as f32,
+                &label,
+            );
+        }
+
+        if let Some((top, bottom)) = self.times {
+            let ticks = ticks(top, bottom, (height / 60).max(2) as usize);
+            let resolution = match ticks.as_slice() {
+                [first, second, ..] => second - first,
+                _ => bottom - top,
+            };
+            for tick in ticks {
+                let y = MARGIN_TOP + ((tick - top) / (bottom - top) * height as f64) as u32;
+                let y = y.min(MARGIN_TOP + height - 1);
+                for x in MARGIN_LEFT - TICK_LENGTH..MARGIN_LEFT {
+                    canvas.put_pixel(x, y, FOREGROUND);
+                }
+                let label = format_seconds(tick, resolution);
+                let label_x = (MARGIN_LEFT - TICK_LENGTH - 2) as f32 - labeller.width(&label);
+                labeller.draw(&mut canvas, label_x, y as f32 + LABEL_HEIGHT / 3., &label);
+            }
+        }
+
+        canvas
+    }
+}
+
+/// A copy of `image`, as from `Axes::draw`, with a labelled colour bar beside the waterfall.
+pub fn draw_colour_bar(
+    image: &RgbImage,
+    colormap: Colormap,
+    scale: Scale,
+    (min, max): (f32, f32),
+    labeller: &Labeller,
+) -> RgbImage {
+    let (width, height) = image.dimensions();
+    let bar_height = height.saturating_sub(MARGIN_TOP + MARGIN_BOTTOM).max(1);
+    let unit = match scale {
+        Scale::Decibels => "dB",
+        Scale::Linear => "",
+    };
+    let ticks = ticks(
+        f64::from(min),
+        f64::from(max),
+        (bar_height / 40).max(2) as usize,
+    );
+    let labels: Vec<String> = ticks
+        .iter()
+        .map(|&tick| format!("{}{}", format_si(tick), unit))
+        .collect();
+    let label_width = labels
+        .iter()
+        .map(|label| labeller.width(label))
+        .fold(0., f32::max)
+        .ceil() as u32;
+
+    let left = width + COLOUR_BAR_GAP;
+    let mut canvas = RgbImage::from_pixel(
+        left + COLOUR_BAR_WIDTH + TICK_LENGTH + 2 + label_width + MARGIN_RIGHT,
+        height,
+        BACKGROUND,
+    );
+    image::imageops::replace(&mut canvas, image, 0, 0);
+
+    // the highest value at the top, as on a thermometer
+    for y in 0..bar_height {
+        let val = max - (max - min) * y as f32 / (bar_height - 1).max(1) as f32;
+        let colour = Rgb(colormap.rgb_in(val, min, max));
+        for x in left..left + COLOUR_BAR_WIDTH {
+            canvas.put_pixel(x, MARGIN_TOP + y, colour);
+        }
+    }
+
+    for (tick, label) in ticks.into_iter().zip(labels) {
+        let y = MARGIN_TOP
+            + ((f64::from(max) - tick) / f64::from(max - min) * f64::from(bar_height - 1)) as u32;
+        let right = left + COLOUR_BAR_WIDTH;
+        for x in right..right + TICK_LENGTH {
+            canvas.put_pixel(x, y, FOREGROUND);
+        }
+        labeller.draw(
+            &mut canvas,
+            (right + TICK_LENGTH + 2) as f32,
+            y as f32 + LABEL_HEIGHT / 3.,
+            &label,
+        );
+    }
+
+    canvas
+}
+
+/// A copy of `image`, with lines of text underneath.
+pub fn draw_caption(image: &RgbImage, lines: &[String], labeller: &Labeller) -> RgbImage {
+    let (width, height) = image.dimensions();
+    let line_height = (LABEL_HEIGHT * 1.4).ceil() as u32;
+    let mut canvas = RgbImage::from_pixel(
+        width,
+        height + line_height * lines.len() as u32 + CAPTION_PADDING,
+        BACKGROUND,
+    );
+    image::imageops::replace(&mut canvas, image, 0, 0);
+    for (i, line) in lines.iter().enumerate() {
+        let baseline = height + line_height * (i as u32 + 1);
+        labeller.draw(&mut canvas, CAPTION_PADDING as f32, baseline as f32, line);
+    }
+    canvas
+}
+
+/// Draw the edges of a box, which may be partly (or entirely) outside the image.
+pub fn outline(
+    image: &mut RgbImage,
+    (left, top, right, bottom): (i64, i64, i64, i64),
+    colour: Rgb<u8>,
+) {
+    let (width, height) = image.dimensions();
+    let mut put = |x: i64, y: i64| {
+        if (0..i64::from(width)).contains(&x) && (0..i64::from(height)).contains(&y) {
+            image.put_pixel(x as u32, y as u32, colour);
+        }
+    };
+    for x in left..=right {
+        put(x, top);
+        put(x, bottom);
+    }
+    for y in top..=bottom {
+        put(left, y);
+        put(right, y);
+    }
+}
+
+/// Renders text into images, using the bundled font.
+pub struct Labeller {
+    font: Font<'static>,
+    scale: rusttype::Scale,
+}
+
+impl Labeller {
+    pub fn new() -> Result<Labeller> {
+        Ok(Labeller {
+            font: Font::try_from_bytes(include_bytes!("../assets/NotoSans-Regular.ttf"))
+                .ok_or_else(|| anyhow!("bundled font is invalid"))?,
+            scale: rusttype::Scale::uniform(LABEL_HEIGHT),
+        })
+    }
+
+    pub fn width(&self, text: &str) -> f32 {
+        self.font
+            .layout(text, self.scale, point(0., 0.))
+            .map(|glyph| glyph.position().x + glyph.unpositioned().h_metrics().advance_width)
+            .fold(0., f32::max)
+    }
+
+    /// Draw `text` in the foreground colour, starting at `x`, with its baseline at `y`.
+    pub fn draw(&self, image: &mut RgbImage, x: f32, y: f32, text: &str) {
+        self.draw_coloured(image, x, y, text, FOREGROUND);
+    }
+
+    pub fn draw_coloured(&self, image: &mut RgbImage, x: f32, y: f32, text: &str, colour: Rgb<u8>) {
+        let (width, height) = image.dimensions();
+        for glyph in self.font.layout(text, self.scale, point(x, y)) {
+            let bounds = match glyph.pixel_bounding_box() {
+                Some(bounds) => bounds,
+                None => continue,
+            };
+            glyph.draw(|gx, gy, coverage| {
+                let px = bounds.min.x + gx as i32;
+                let py = bounds.min.y + gy as i32;
+                if px < 0 || py < 0 || px as u32 >= width || py as u32 >= height {
+                    return;
+                }
+                let pixel = image.get_pixel_mut(px as u32, py as u32);
+                for (channel, fg) in pixel.0.iter_mut().zip(colour.0.iter()) {
+                    let bg = f32::from(*channel);
+                    *channel = (bg + (f32::from(*fg) - bg) * coverage).round() as u8;
+                }
+            });
+        }
+    }
+}
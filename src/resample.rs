@@ -0,0 +1,152 @@
+//! Changing the sample rate by an arbitrary ratio, with windowed-sinc interpolation
+
+use std::f64::consts::PI;
+
+use rustfft::num_complex::Complex;
+use rustfft::num_traits::Zero;
+
+use crate::samples::Samples;
+use crate::{u64_from, usize_from};
+
+/// Zero crossings of the sinc each side of the centre, at the output's bandwidth.
+const LOBES: f64 = 16.;
+
+pub struct Resample<S> {
+    inner: S,
+    from: u64,
+    to: u64,
+
+    /// Fraction of the input's Nyquist frequency that survives; `1` when upsampling.
+    cutoff: f64,
+
+    /// Input samples each side of the interpolation point that contribute.
+    half_width: usize,
+}
+
+impl<S> Resample<S>
+where
+    S: Samples,
+{
+    pub fn new(inner: S, to: u64) -> Self {
+        let from = inner.sample_rate();
+        assert!(from > 0 && to > 0);
+        let cutoff = (to as f64 / from as f64).min(1.);
+        Resample {
+            inner,
+            from,
+            to,
+            cutoff,
+            half_width: (LOBES / cutoff).ceil() as usize,
+        }
+    }
+
+    /// The input position of output sample `n`: whole samples, and a fraction.
+    fn position(&self, n: u64) -> (u64, f64) {
+        let scaled = u128::from(n) * u128::from(self.from);
+        let to = u128::from(self.to);
+        ((scaled / to) as u64, (scaled % to) as f64 / to as f64)
+    }
+
+    fn kernel(&self, x: f64) -> f64 {
+        let half_width = self.half_width as f64;
+        if x.abs() >= half_width {
+            return 0.;
+        }
+
+        let y = x * self.cutoff;
+        let sinc = if 0. == y {
+            1.
+        } else {
+            (PI * y).sin() / (PI * y)
+        };
+
+        let phase = PI * x / half_width;
+        let blackman = 0.42 + 0.5 * phase.cos() + 0.08 * (2. * phase).cos();
+
+        self.cutoff * sinc * blackman
+    }
+}
+
+impl<S> Samples for Resample<S>
+where
+    S: Samples,
+{
+    fn len(&self) -> u64 {
+        (u128::from(self.inner.len()) * u128::from(self.to) / u128::from(self.from)) as u64
+    }
+
+    fn sample_rate(&self) -> u64 {
+        self.to
+    }
+
+    fn centre_frequency(&self) -> Option<u64> {
+        self.inner.centre_frequency()
+    }
+
+    fn read_at(&self, off: u64, buf: &mut [Complex<f32>]) -> usize {
+        let len = self.len();
+        if off >= len || buf.is_empty() {
+            return 0;
+        }
+        let wanted = usize_from((len - off).min(u64_from(buf.len())));
+
+        let half_width = u64_from(self.half_width);
+        let (first, _) = self.position(off);
+        let (last, _) = self.position(off + u64_from(wanted) - 1);
+        let read_start = first.saturating_sub(half_width);
+        let read_end = (last + half_width + 1).min(self.inner.len());
+
+        let mut raw = vec![Complex::zero(); usize_from(read_end - read_start)];
+        let valid = self.inner.read_at(read_start, &mut raw);
+        raw.truncate(valid);
+
+        for (i, out) in buf[..wanted].iter_mut().enumerate() {
+            let (whole, fraction) = self.position(off + u64_from(i));
+            let lo = (whole + 1).saturating_sub(half_width).max(read_start);
+            let hi = (whole + half_width + 1).min(read_start + u64_from(raw.len()));
+
+            let mut acc = Complex::<f64>::zero();
+            for k in lo..hi {
+                let x = (whole as f64 - k as f64) + fraction;
+                let sample = raw[usize_from(k - read_start)];
+                acc += Complex::new(f64::from(sample.re), f64::from(sample.im)) * self.kernel(x);
+            }
+            *out = Complex::new(acc.re as f32, acc.im as f32);
+        }
+
+        wanted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rustfft::num_complex::Complex;
+
+    use super::Resample;
+    use crate::gen::Gen;
+    use crate::Samples;
+
+    #[test]
+    fn tone_survives() {
+        let gen = Gen::new(vec![1_000], 48_000, 0.1).unwrap();
+        let resampled = Resample::new(&gen, 8_000);
+        assert_eq!(8_000, resampled.sample_rate());
+        assert_eq!(800, resampled.len());
+
+        // away from the edges, it's still the same tone, at the new rate
+        let mut buf = vec![Complex::default(); 100];
+        resampled.read_exact_at(300, &mut buf).unwrap();
+        let mut expected = vec![Complex::default(); 1];
+        for (i, sample) in buf.iter().enumerate() {
+            gen.read_exact_at((300 + i as u64) * 6, &mut expected)
+                .unwrap();
+            assert!(
+                (sample - expected[0]).norm() < 0.01,
+                "{}: {} {}",
+                i,
+                sample,
+                expected[0]
+            );
+        }
+    }
+}
@@ -1,6 +1,7 @@
 use std::fs::File;
 use std::io::Seek;
 use std::io::SeekFrom;
+use std::sync::Arc;
 
 use anyhow::ensure;
 use anyhow::Error;
@@ -10,8 +11,18 @@ use crate::usize_from;
 
 pub trait Samples: Sync + Send {
     fn len(&self) -> u64;
+
+    fn is_empty(&self) -> bool {
+        0 == self.len()
+    }
+
     fn sample_rate(&self) -> u64;
 
+    /// The real frequency that zero corresponds to, if it is known.
+    fn centre_frequency(&self) -> Option<u64> {
+        None
+    }
+
     fn read_at(&self, off: u64, buf: &mut [Complex<f32>]) -> usize;
 
     fn read_exact_at(&self, off: u64, buf: &mut [Complex<f32>]) -> Result<(), Error> {
@@ -36,6 +47,46 @@ impl<T: Samples + ?Sized> Samples for Box<T> {
         (**self).sample_rate()
     }
 
+    fn centre_frequency(&self) -> Option<u64> {
+        (**self).centre_frequency()
+    }
+
+    fn read_at(&self, off: u64, buf: &mut [Complex<f32>]) -> usize {
+        (**self).read_at(off, buf)
+    }
+}
+
+impl<T: Samples + ?Sized> Samples for &T {
+    fn len(&self) -> u64 {
+        (**self).len()
+    }
+
+    fn sample_rate(&self) -> u64 {
+        (**self).sample_rate()
+    }
+
+    fn centre_frequency(&self) -> Option<u64> {
+        (**self).centre_frequency()
+    }
+
+    fn read_at(&self, off: u64, buf: &mut [Complex<f32>]) -> usize {
+        (**self).read_at(off, buf)
+    }
+}
+
+impl<T: Samples + ?Sized> Samples for Arc<T> {
+    fn len(&self) -> u64 {
+        (**self).len()
+    }
+
+    fn sample_rate(&self) -> u64 {
+        (**self).sample_rate()
+    }
+
+    fn centre_frequency(&self) -> Option<u64> {
+        (**self).centre_frequency()
+    }
+
     fn read_at(&self, off: u64, buf: &mut [Complex<f32>]) -> usize {
         (**self).read_at(off, buf)
     }
@@ -46,16 +97,23 @@ pub struct SampleFile {
     file_len: u64,
     inner: File,
     sample_rate: u64,
+    centre_frequency: Option<u64>,
 }
 
 impl SampleFile {
-    pub fn new(mut inner: File, format: crate::FileFormat, sample_rate: u64) -> Self {
+    pub fn new(
+        mut inner: File,
+        format: crate::FileFormat,
+        sample_rate: u64,
+        centre_frequency: Option<u64>,
+    ) -> Self {
         let file_len = inner.seek(SeekFrom::End(0)).expect("seeking to end");
         SampleFile {
             inner,
             format,
             file_len,
             sample_rate,
+            centre_frequency,
         }
     }
 }
@@ -69,6 +127,10 @@ impl Samples for SampleFile {
         self.sample_rate
     }
 
+    fn centre_frequency(&self) -> Option<u64> {
+        self.centre_frequency
+    }
+
     fn read_at(&self, off: u64, into: &mut [Complex<f32>]) -> usize {
         use std::os::unix::fs::FileExt as _;
         assert!(off < self.len());
@@ -7,6 +7,7 @@ use crate::samples::Samples;
 pub struct Shift<S> {
     inner: S,
     ratio: f64,
+    frequency: i64,
     sample_rate: u64,
 }
 
@@ -26,6 +27,7 @@ where
         Shift {
             inner,
             ratio: TAU * (frequency as f64) / (sample_rate as f64),
+            frequency,
             sample_rate,
         }
     }
@@ -43,12 +45,19 @@ where
         self.sample_rate
     }
 
+    fn centre_frequency(&self) -> Option<u64> {
+        // moving everything up by `frequency` moves the zero point down
+        self.inner
+            .centre_frequency()
+            .and_then(|centre| centre.checked_add_signed(-self.frequency))
+    }
+
     fn read_at(&self, off: u64, buf: &mut [Complex<f32>]) -> usize {
         let valid = self.inner.read_at(off, buf);
-        for i in 0..valid {
+        for (i, sample) in buf[..valid].iter_mut().enumerate() {
             let place = (off + (i as u64)) as f64 * self.ratio;
             let mul = Complex::new(place.cos() as f32, place.sin() as f32);
-            buf[i] *= mul;
+            *sample *= mul;
         }
         valid
     }
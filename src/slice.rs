@@ -0,0 +1,77 @@
+use anyhow::ensure;
+use anyhow::Error;
+use rustfft::num_complex::Complex;
+
+use crate::samples::Samples;
+use crate::usize_from;
+
+/// A contiguous range of the samples from another source.
+pub struct Slice<S> {
+    inner: S,
+    start: u64,
+    end: u64,
+}
+
+impl<S> Slice<S>
+where
+    S: Samples,
+{
+    /// `start` and `end` are sample offsets into `inner`; `end` is exclusive.
+    pub fn new(inner: S, start: u64, end: u64) -> Result<Self, Error> {
+        ensure!(
+            start < end,
+            "slice must end ({}) after it starts ({})",
+            end,
+            start
+        );
+        ensure!(
+            end <= inner.len(),
+            "slice end ({}) is beyond the end of the input ({})",
+            end,
+            inner.len()
+        );
+        Ok(Slice { inner, start, end })
+    }
+}
+
+/// Convert `start` and `end` seconds into a sample range of `samples`, defaulting to all of it.
+pub fn seconds_to_range(
+    samples: &dyn Samples,
+    start: Option<f64>,
+    end: Option<f64>,
+) -> Result<(u64, u64), Error> {
+    let sample_rate = samples.sample_rate() as f64;
+    let to_sample = |seconds: f64| ((seconds * sample_rate).round() as u64).min(samples.len());
+    let start = start.map(to_sample).unwrap_or(0);
+    let end = end.map(to_sample).unwrap_or_else(|| samples.len());
+    ensure!(
+        end > start,
+        "end ({}s) must be after start ({}s)",
+        end as f64 / sample_rate,
+        start as f64 / sample_rate
+    );
+    Ok((start, end))
+}
+
+impl<S> Samples for Slice<S>
+where
+    S: Samples,
+{
+    fn len(&self) -> u64 {
+        self.end - self.start
+    }
+
+    fn sample_rate(&self) -> u64 {
+        self.inner.sample_rate()
+    }
+
+    fn centre_frequency(&self) -> Option<u64> {
+        self.inner.centre_frequency()
+    }
+
+    fn read_at(&self, off: u64, buf: &mut [Complex<f32>]) -> usize {
+        assert!(off < self.len());
+        let available = usize_from((self.len() - off).min(buf.len() as u64));
+        self.inner.read_at(self.start + off, &mut buf[..available])
+    }
+}
@@ -0,0 +1,431 @@
+//! A waterfall in the terminal, for when there's no window to open: two rows of ffts to each
+//! character, as the colours of the top and bottom halves of a `▀`.
+
+use std::io::{self, Write};
+use std::ops::RangeInclusive;
+
+use anyhow::Result;
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::style::{
+    Attribute, Color, Print, ResetColor, SetAttribute, SetBackgroundColor, SetForegroundColor,
+};
+use crossterm::terminal::{self, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{cursor, execute, queue};
+
+use crate::axis::{format_seconds, format_si, text_header};
+use crate::colormap::Colormap;
+use crate::eui::view::View;
+use crate::ffts::{auto_range, take_fft, FftConfig, FftResult, Scale, Windowing};
+use crate::render::Axes;
+use crate::Samples;
+
+/// Characters for the times, down the left.
+const MARGIN: u16 = 12;
+
+/// The frequencies at the top; the readout and the status line at the bottom.
+const HEADER_LINES: u16 = 1;
+const FOOTER_LINES: u16 = 2;
+
+/// A time label every this many lines.
+const TIME_LABEL_EVERY: u16 = 4;
+
+/// The fft widths on offer, as powers of two.
+const FFT_POWERS: RangeInclusive<u32> = 4..=16;
+
+/// How much one press of a zoom key zooms.
+const ZOOM: f64 = 0.5;
+
+const KEYS: &str =
+    "arrows: cursor, scrolling at the edges  space/pgdn, pgup: page  +/-: zoom time  \
+    ]/[: zoom frequency  home/end  0: whole file  f/F: fft width  c/C: colormap  s: dB/linear  \
+    ?: status  q: quit";
+
+#[derive(Debug, Clone)]
+pub struct Tui {
+    pub width: usize,
+    pub window: Windowing,
+    pub scale: Scale,
+    pub colormap: Colormap,
+}
+
+/// Puts the terminal back, however we leave.
+struct Screen;
+
+impl Screen {
+    fn enter() -> Result<Screen> {
+        terminal::enable_raw_mode()?;
+        execute!(io::stdout(), EnterAlternateScreen, cursor::Hide)?;
+        Ok(Screen)
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        let _ = execute!(io::stdout(), ResetColor, cursor::Show, LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// Each column's value: the loudest bin between its edges, or the one it's in, if it's
+/// narrower than a bin. `band` is the frequencies at the edges of the `row`.
+fn columns(row: &[f32], band: (f64, f64), (low, high): (f64, f64), count: usize) -> Vec<f32> {
+    let bins = row.len();
+    let bin_at = |frequency: f64| (frequency - band.0) / (band.1 - band.0) * bins as f64;
+    (0..count)
+        .map(|x| {
+            let left = low + (high - low) * x as f64 / count as f64;
+            let right = low + (high - low) * (x + 1) as f64 / count as f64;
+            let first = (bin_at(left).floor().max(0.) as usize).min(bins - 1);
+            let last = (bin_at(right).ceil().max(0.) as usize).clamp(first + 1, bins);
+            row[first..last].iter().cloned().fold(0., f32::max)
+        })
+        .collect()
+}
+
+/// `text`, cut or padded to exactly `width` characters.
+fn fit(text: &str, width: usize) -> String {
+    format!("{:width$.width$}", text, width = width)
+}
+
+struct Viewer<'s> {
+    samples: &'s dyn Samples,
+    settings: Tui,
+    view: View,
+    /// In characters of the waterfall, from its top left.
+    cursor: (u16, u16),
+    /// The keys, rather than the settings, on the status line.
+    help: bool,
+}
+
+/// What was last drawn, to read out what's under the cursor.
+struct Drawn {
+    /// Of the waterfall, in characters.
+    size: (u16, u16),
+    /// The scaled value of each half-character: rows of them, from the top.
+    values: Vec<Vec<f32>>,
+    error: Option<String>,
+}
+
+impl<'s> Viewer<'s> {
+    /// The ffts for the view, with (up to) `rows` rows.
+    fn ffts(&self, rows: usize) -> Result<FftResult, String> {
+        let sample_rate = self.samples.sample_rate() as f64;
+        let start = (self.view.start * sample_rate) as u64;
+        let end = ((self.view.end * sample_rate) as u64).min(
+            self.samples
+                .len()
+                .saturating_sub(self.settings.width as u64 + 1),
+        );
+        if end <= start + 1 {
+            return Err("nothing to show: too close to the end, or too short".to_string());
+        }
+        let rows = rows.min((end - start - 1) as usize).max(1);
+        take_fft(
+            self.samples,
+            Some((start, end)),
+            &FftConfig {
+                width: self.settings.width,
+                windowing: self.settings.window,
+            },
+            rows,
+        )
+        .map_err(|e| format!("{:#}", e))
+    }
+
+    /// The scaled value of each pixel, for a waterfall `size` characters big.
+    fn pixels(&self, (width, height): (u16, u16)) -> Result<Vec<Vec<f32>>, String> {
+        let rows = usize::from(height) * 2;
+        let fft = self.ffts(rows)?;
+        let band = Axes::for_band(self.samples.sample_rate() as f64, 0., fft.width()).frequencies;
+        Ok((0..rows)
+            .map(|y| {
+                let row = fft.get(y * fft.output_len() / rows);
+                columns(
+                    row,
+                    band,
+                    (self.view.low, self.view.high),
+                    usize::from(width),
+                )
+                .into_iter()
+                .map(|norm| self.settings.scale.apply(norm, fft.full_scale()))
+                .collect()
+            })
+            .collect())
+    }
+
+    fn centre(&self) -> f64 {
+        self.samples.centre_frequency().unwrap_or(0) as f64
+    }
+
+    /// The time and (relative) frequency at the middle of a character of the waterfall.
+    fn at(&self, (x, y): (u16, u16), (width, height): (u16, u16)) -> (f64, f64) {
+        let view = &self.view;
+        (
+            view.start + (view.end - view.start) * (f64::from(y) + 0.5) / f64::from(height),
+            view.low + (view.high - view.low) * (f64::from(x) + 0.5) / f64::from(width),
+        )
+    }
+
+    fn draw(&self, out: &mut impl Write) -> Result<Drawn> {
+        let (columns, lines) = terminal::size()?;
+        queue!(
+            out,
+            ResetColor,
+            terminal::Clear(ClearType::All),
+            cursor::MoveTo(0, 0)
+        )?;
+        if columns <= MARGIN + 2 || lines <= HEADER_LINES + FOOTER_LINES {
+            queue!(out, Print("too small"))?;
+            out.flush()?;
+            return Ok(Drawn {
+                size: (0, 0),
+                values: Vec::new(),
+                error: None,
+            });
+        }
+
+        let size = (columns - MARGIN, lines - HEADER_LINES - FOOTER_LINES);
+        let (width, height) = size;
+        let pixels = self.pixels(size);
+
+        // the frequencies at the middles of the first and last columns
+        let half = (self.view.high - self.view.low) / f64::from(width) / 2.;
+        let header = text_header(
+            usize::from(width),
+            self.view.low + half + self.centre(),
+            self.view.high - half + self.centre(),
+        );
+        queue!(out, Print(" ".repeat(usize::from(MARGIN))), Print(header))?;
+
+        // the colours are levelled to what's on screen
+        let (min, max) = match &pixels {
+            Ok(pixels) => auto_range(&mut pixels.concat()),
+            Err(_) => (0., 1.),
+        };
+        let colour = |value: f32| {
+            let [r, g, b] = self.settings.colormap.rgb_in(value, min, max);
+            Color::Rgb { r, g, b }
+        };
+
+        let resolution = (self.view.end - self.view.start) / f64::from(height);
+        for y in 0..height {
+            queue!(out, cursor::MoveTo(0, HEADER_LINES + y), ResetColor)?;
+            let label = if 0 == y % TIME_LABEL_EVERY {
+                format_seconds(self.at((0, y), size).0, resolution)
+            } else {
+                String::new()
+            };
+            queue!(
+                out,
+                Print(format!(
+                    "{:>width$} ",
+                    label,
+                    width = usize::from(MARGIN) - 1
+                ))
+            )?;
+
+            let pixels = match &pixels {
+                Ok(pixels) => pixels,
+                Err(_) => continue,
+            };
+            let (top, bottom) = (&pixels[usize::from(y) * 2], &pixels[usize::from(y) * 2 + 1]);
+            for x in 0..usize::from(width) {
+                let (upper, lower) = (colour(top[x]), colour(bottom[x]));
+                if (x as u16, y) == self.cursor {
+                    queue!(
+                        out,
+                        SetForegroundColor(Color::White),
+                        SetBackgroundColor(lower),
+                        Print('┼')
+                    )?;
+                } else {
+                    queue!(
+                        out,
+                        SetForegroundColor(upper),
+                        SetBackgroundColor(lower),
+                        Print('▀')
+                    )?;
+                }
+            }
+        }
+
+        let (values, error) = match pixels {
+            Ok(values) => (values, None),
+            Err(e) => (Vec::new(), Some(e)),
+        };
+        let drawn = Drawn {
+            size,
+            values,
+            error,
+        };
+
+        queue!(
+            out,
+            ResetColor,
+            cursor::MoveTo(0, lines - 2),
+            Print(fit(&self.readout(&drawn), usize::from(columns))),
+            cursor::MoveTo(0, lines - 1),
+            SetAttribute(Attribute::Reverse),
+            Print(fit(&self.status(), usize::from(columns))),
+            SetAttribute(Attribute::Reset),
+        )?;
+        out.flush()?;
+        Ok(drawn)
+    }
+
+    /// The time, frequency and power under the cursor.
+    fn readout(&self, drawn: &Drawn) -> String {
+        if let Some(error) = &drawn.error {
+            return error.clone();
+        }
+        let (time, frequency) = self.at(self.cursor, drawn.size);
+        let (x, y) = (usize::from(self.cursor.0), usize::from(self.cursor.1) * 2);
+        let power = drawn
+            .values
+            .get(y)
+            .zip(drawn.values.get(y + 1))
+            .map(|(top, bottom)| top[x].max(bottom[x]))
+            .map(|power| match self.settings.scale {
+                Scale::Decibels => format!("{:.1}dB", power),
+                Scale::Linear => format!("{:.3}", power),
+            })
+            .unwrap_or_default();
+        let resolution = (self.view.end - self.view.start) / f64::from(drawn.size.1.max(1));
+        format!(
+            "{} {}Hz {}",
+            format_seconds(time, resolution),
+            format_si(frequency + self.centre()),
+            power
+        )
+    }
+
+    /// The settings and the view, or the keys.
+    fn status(&self) -> String {
+        if self.help {
+            return KEYS.to_string();
+        }
+        let resolution = (self.view.end - self.view.start) / 100.;
+        format!(
+            "fft {} {} {} {} | {} to {} | {}Hz to {}Hz | ?: keys, q: quit",
+            self.settings.width,
+            self.settings.window.name(),
+            self.settings.colormap.name(),
+            match self.settings.scale {
+                Scale::Decibels => "dB",
+                Scale::Linear => "linear",
+            },
+            format_seconds(self.view.start, resolution),
+            format_seconds(self.view.end, resolution),
+            format_si(self.view.low + self.centre()),
+            format_si(self.view.high + self.centre()),
+        )
+    }
+
+    /// Do what was pressed; `false` to leave.
+    fn key(&mut self, key: KeyEvent, (width, height): (u16, u16)) -> bool {
+        let mut view = self.view;
+        let duration = view.end - view.start;
+        let bandwidth = view.high - view.low;
+        let (time, frequency) = self.at(self.cursor, (width.max(1), height.max(1)));
+        let (x, y) = self.cursor;
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return false,
+
+            // past the edges, the view moves instead
+            KeyCode::Up if 0 == y => view.pan(-duration / f64::from(height.max(1)), 0.),
+            KeyCode::Up => self.cursor.1 -= 1,
+            KeyCode::Down if y + 1 >= height => view.pan(duration / f64::from(height.max(1)), 0.),
+            KeyCode::Down => self.cursor.1 += 1,
+            KeyCode::Left if 0 == x => view.pan(0., -bandwidth / f64::from(width.max(1))),
+            KeyCode::Left => self.cursor.0 -= 1,
+            KeyCode::Right if x + 1 >= width => view.pan(0., bandwidth / f64::from(width.max(1))),
+            KeyCode::Right => self.cursor.0 += 1,
+
+            KeyCode::PageDown | KeyCode::Char(' ') => view.pan(duration, 0.),
+            KeyCode::PageUp => view.pan(-duration, 0.),
+            KeyCode::Char('+') | KeyCode::Char('=') => view.zoom_time(ZOOM, time),
+            KeyCode::Char('-') => view.zoom_time(1. / ZOOM, time),
+            KeyCode::Char(']') => view.zoom_frequency(ZOOM, frequency),
+            KeyCode::Char('[') => view.zoom_frequency(1. / ZOOM, frequency),
+            KeyCode::Home => view.pan(-view.start, 0.),
+            KeyCode::End => view.pan(View::whole(self.samples).end - view.end, 0.),
+            KeyCode::Char('0') => view = View::whole(self.samples),
+
+            KeyCode::Char('f') => self.step_fft(true),
+            KeyCode::Char('F') => self.step_fft(false),
+            KeyCode::Char('c') => self.settings.colormap = self.settings.colormap.cycle(true),
+            KeyCode::Char('C') => self.settings.colormap = self.settings.colormap.cycle(false),
+            KeyCode::Char('s') => {
+                self.settings.scale = match self.settings.scale {
+                    Scale::Decibels => Scale::Linear,
+                    Scale::Linear => Scale::Decibels,
+                }
+            }
+            KeyCode::Char('?') => self.help = !self.help,
+            _ => (),
+        }
+        view.clamp(self.samples);
+        self.view = view;
+        true
+    }
+
+    fn step_fft(&mut self, bigger: bool) {
+        let power = self.settings.width.trailing_zeros();
+        let power = if bigger {
+            power + 1
+        } else {
+            power.saturating_sub(1)
+        };
+        self.settings.width = 1 << power.clamp(*FFT_POWERS.start(), *FFT_POWERS.end());
+    }
+}
+
+/// Show the samples until `q` is pressed.
+pub fn run(samples: &dyn Samples, settings: &Tui) -> Result<()> {
+    let mut viewer = Viewer {
+        samples,
+        settings: settings.clone(),
+        view: View::whole(samples),
+        cursor: (0, 0),
+        help: false,
+    };
+
+    let _screen = Screen::enter()?;
+    let mut out = io::BufWriter::new(io::stdout());
+    loop {
+        let drawn = viewer.draw(&mut out)?;
+        let (width, height) = drawn.size;
+        viewer.cursor = (
+            viewer.cursor.0.min(width.saturating_sub(1)),
+            viewer.cursor.1.min(height.saturating_sub(1)),
+        );
+
+        // anything else, such as a resize, is just a redraw
+        if let Event::Key(key) = event::read()? {
+            if KeyEventKind::Release != key.kind && !viewer.key(key, drawn.size) {
+                return Ok(());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::columns;
+
+    #[test]
+    fn resampling() {
+        // eight bins across 800Hz; a tone in the 500 to 600Hz bin
+        let mut row = vec![0.; 8];
+        row[5] = 1.;
+        let band = (0., 800.);
+
+        // zoomed out: the loudest of each pair of bins
+        assert_eq!(vec![0., 0., 1., 0.], columns(&row, band, band, 4));
+
+        // zoomed in: each column is the bin it's in
+        let zoomed = columns(&row, band, (450., 650.), 4);
+        assert_eq!(vec![0., 1., 1., 0.], zoomed);
+    }
+}